@@ -0,0 +1,76 @@
+// Load generator for the benchmark apps, without dependencies.
+//
+//   node load.mjs http://localhost:8084/ --connections=50 --duration=10 --slow-clients=100
+//
+// Keeps `connections` requests in flight for `duration` seconds and reports
+// throughput and latency. `slow-clients` opens that many extra connections first
+// which trickle a request head and never finish it, the way a slowloris client does.
+
+import http from 'http';
+import net from 'net';
+
+const [url, ...flags] = process.argv.slice(2);
+if (!url) {
+  console.error('usage: node load.mjs <url> [--connections=50] [--duration=10] [--slow-clients=0]');
+  process.exit(1);
+}
+
+const option = (name, fallback) => {
+  const flag = flags.find((flag) => flag.startsWith(`--${name}=`));
+  return flag ? Number(flag.split('=')[1]) : fallback;
+};
+const connections = option('connections', 50);
+const duration = option('duration', 10) * 1000;
+const slowClients = option('slow-clients', 0);
+
+const target = new URL(url);
+const agent = new http.Agent({ keepAlive: false, maxSockets: Infinity });
+
+const slowSockets = [];
+for (let i = 0; i < slowClients; i++) {
+  const socket = net.connect(Number(target.port || 80), target.hostname);
+  socket.on('error', () => {});
+  socket.write(`GET ${target.pathname} HTTP/1.1\r\nHost: ${target.host}\r\n`);
+  const trickle = setInterval(() => socket.write('X-Slow: 1\r\n'), 1000);
+  socket.on('close', () => clearInterval(trickle));
+  slowSockets.push(socket);
+}
+
+const latencies = [];
+let errors = 0;
+let bytes = 0;
+const started = Date.now();
+
+const request = () =>
+  new Promise((resolve) => {
+    const sent = process.hrtime.bigint();
+    const req = http.get(url, { agent, timeout: 5000 }, (res) => {
+      res.on('data', (chunk) => (bytes += chunk.length));
+      res.on('end', () => {
+        latencies.push(Number(process.hrtime.bigint() - sent) / 1e6);
+        if (res.statusCode >= 400) errors++;
+        resolve();
+      });
+    });
+    req.on('timeout', () => req.destroy(new Error('timeout')));
+    req.on('error', () => {
+      errors++;
+      resolve();
+    });
+  });
+
+const client = async () => {
+  while (Date.now() - started < duration) await request();
+};
+
+await Promise.all(Array.from({ length: connections }, client));
+slowSockets.forEach((socket) => socket.destroy());
+
+const elapsed = (Date.now() - started) / 1000;
+latencies.sort((a, b) => a - b);
+const percentile = (p) => (latencies.length ? latencies[Math.floor((latencies.length - 1) * p)].toFixed(1) : '-');
+
+console.log(`${url} ${connections} connections, ${slowClients} slow clients, ${elapsed.toFixed(1)}s`);
+console.log(`  requests  ${latencies.length} (${(latencies.length / elapsed).toFixed(0)}/s), ${errors} errors`);
+console.log(`  latency   p50 ${percentile(0.5)}ms  p99 ${percentile(0.99)}ms  max ${percentile(1)}ms`);
+console.log(`  transfer  ${(bytes / elapsed / 1024 / 1024).toFixed(1)} MiB/s`);
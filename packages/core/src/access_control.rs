@@ -0,0 +1,350 @@
+use std::fmt::{self, Display};
+use std::net::IpAddr;
+
+use serde_json::Value;
+
+/// # Cidr
+///
+/// A block of IPv4 or IPv6 addresses, i.e. `10.0.0.0/8` or `fd00::/8`. An address
+/// without a prefix length is a block of one. IPv4-mapped blocks, `::ffff:10.0.0.0/104`,
+/// are the IPv4 block they map, `10.0.0.0/8`.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix_length: u8,
+}
+
+impl Cidr {
+    pub fn parse(cidr: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "Cidr::parse() Exception: Expected an IP address or CIDR block, i.e. 10.0.0.0/8, received {}",
+                cidr
+            )
+        };
+
+        let (address, prefix_length) = match cidr.trim().split_once('/') {
+            Some((address, prefix_length)) => (address, Some(prefix_length)),
+            None => (cidr.trim(), None),
+        };
+        let address = address
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .map_err(|_| invalid())?;
+
+        let max_prefix_length = match address {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        let prefix_length = match prefix_length {
+            Some(prefix_length) => prefix_length
+                .parse::<u8>()
+                .ok()
+                .filter(|prefix_length| *prefix_length <= max_prefix_length)
+                .ok_or_else(invalid)?,
+            None => max_prefix_length,
+        };
+
+        // IPv4-mapped blocks are kept as IPv4, the form mapped peers are compared in
+        let (address, prefix_length) = match address.to_canonical() {
+            IpAddr::V4(ip) if address.is_ipv6() => match prefix_length.checked_sub(96) {
+                Some(prefix_length) => (IpAddr::V4(ip), prefix_length),
+                None => {
+                    return Err(format!(
+                        "Cidr::parse() Exception: An IPv4-mapped block needs a prefix length of at least 96, received {}",
+                        cidr
+                    ))
+                }
+            },
+            _ => (address, prefix_length),
+        };
+
+        Ok(Cidr {
+            network: Self::mask(address, prefix_length),
+            prefix_length,
+        })
+    }
+
+    ///
+    /// Whether the address falls in the block, IPv4-mapped IPv6 addresses matching as IPv4.
+    ///
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        ip.is_ipv4() == self.network.is_ipv4() && Self::mask(ip, self.prefix_length) == self.network
+    }
+
+    fn mask(ip: IpAddr, prefix_length: u8) -> IpAddr {
+        match ip {
+            IpAddr::V4(ip) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(prefix_length))
+                    .unwrap_or(0);
+                IpAddr::V4((u32::from(ip) & mask).into())
+            }
+            IpAddr::V6(ip) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(prefix_length))
+                    .unwrap_or(0);
+                IpAddr::V6((u128::from(ip) & mask).into())
+            }
+        }
+    }
+}
+
+impl Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_length)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessAction {
+    Allow,
+    Deny,
+}
+
+/// # AccessRule
+///
+/// Allows or denies a block of addresses, written `allow 10.0.0.0/8` or `deny all`.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessRule {
+    pub action: AccessAction,
+    /// `None` for `all`, every address
+    pub cidr: Option<Cidr>,
+}
+
+impl AccessRule {
+    pub fn parse(rule: &str) -> Result<Self, String> {
+        let (action, addresses) = rule.trim().split_once(' ').ok_or_else(|| {
+            format!(
+                "AccessRule::parse() Exception: Expected allow <cidr> or deny <cidr>, received {}",
+                rule
+            )
+        })?;
+
+        let action = match action {
+            "allow" => AccessAction::Allow,
+            "deny" => AccessAction::Deny,
+            _ => {
+                return Err(format!(
+                    "AccessRule::parse() Exception: Expected allow or deny, received {} in {}",
+                    action, rule
+                ))
+            }
+        };
+        let cidr = match addresses.trim() {
+            "all" => None,
+            cidr => Some(Cidr::parse(cidr)?),
+        };
+
+        Ok(AccessRule { action, cidr })
+    }
+
+    fn matches(&self, ip: IpAddr) -> bool {
+        self.cidr.as_ref().is_none_or(|cidr| cidr.contains(ip))
+    }
+}
+
+impl Display for AccessRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = match self.action {
+            AccessAction::Allow => "allow",
+            AccessAction::Deny => "deny",
+        };
+        match &self.cidr {
+            Some(cidr) => write!(f, "{} {}", action, cidr),
+            None => write!(f, "{} all", action),
+        }
+    }
+}
+
+/// # AccessRules
+///
+/// Which client addresses may connect, from `--allow=<cidr>` and `--deny=<cidr>`,
+/// `RSRV_ACCESS` or `access` in the config file, evaluated in order.
+///
+/// ```sh
+/// $ rsrv --deny=10.0.13.0/24 --allow=10.0.0.0/8 --allow=fd00::/8 ./dist
+/// ```
+///
+/// ```toml
+/// access = ["deny 10.0.13.0/24", "allow 10.0.0.0/8", "allow ::1"]
+/// ```
+///
+/// The first rule that matches an address decides. An address no rule matches is
+/// denied when there are `allow` rules, so a list of them is an allowlist, and allowed
+/// otherwise. Mounts take their own rules with `allow=` and `deny=` options, checked
+/// after the global ones for requests under the mount.
+///
+/// Rules match the peer address, a connection the global rules deny is closed
+/// without a response, a request a mount denies gets a `403`.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccessRules {
+    pub rules: Vec<AccessRule>,
+}
+
+impl AccessRules {
+    ///
+    /// Parses the `access` list of the config file or a mount table.
+    ///
+    pub fn from_json(value: &Value) -> Result<Self, String> {
+        let rules = value.as_array().ok_or_else(|| {
+            format!(
+                "AccessRules::from_json() Exception: Expected a list of rules, i.e. [\"allow 10.0.0.0/8\"], received {}",
+                value
+            )
+        })?;
+
+        rules
+            .iter()
+            .map(|rule| match rule {
+                Value::String(rule) => AccessRule::parse(rule),
+                rule => Err(format!(
+                    "AccessRules::from_json() Exception: Expected a rule like \"allow 10.0.0.0/8\", received {}",
+                    rule
+                )),
+            })
+            .collect::<Result<Vec<AccessRule>, String>>()
+            .map(|rules| AccessRules { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn permits(&self, ip: IpAddr) -> bool {
+        match self.rules.iter().find(|rule| rule.matches(ip)) {
+            Some(rule) => rule.action == AccessAction::Allow,
+            None => !self
+                .rules
+                .iter()
+                .any(|rule| rule.action == AccessAction::Allow),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        self.rules
+            .iter()
+            .map(|rule| rule.to_string())
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    fn rules(rules: &[&str]) -> AccessRules {
+        AccessRules {
+            rules: rules
+                .iter()
+                .map(|rule| AccessRule::parse(rule).unwrap())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn parses_blocks() {
+        for (cidr, expected) in [
+            ("10.1.2.3/8", "10.0.0.0/8"),
+            ("192.168.1.7", "192.168.1.7/32"),
+            ("0.0.0.0/0", "0.0.0.0/0"),
+            ("fd00:1::/8", "fd00::/8"),
+            ("[::1]", "::1/128"),
+            ("::ffff:10.0.0.0/104", "10.0.0.0/8"),
+            ("::ffff:192.168.1.7", "192.168.1.7/32"),
+        ] {
+            assert_eq!(Cidr::parse(cidr).unwrap().to_string(), expected, "{}", cidr);
+        }
+
+        for cidr in [
+            "10.0.0.0/33",
+            "fd00::/129",
+            "10.0.0/8",
+            "10.0.0.0/",
+            "example.com",
+            "::ffff:10.0.0.0/80",
+        ] {
+            assert!(Cidr::parse(cidr).is_err(), "{}", cidr);
+        }
+    }
+
+    #[test]
+    fn contains_addresses_of_the_same_family() {
+        let private = Cidr::parse("10.0.0.0/8").unwrap();
+        assert!(private.contains(ip("10.255.0.1")));
+        assert!(!private.contains(ip("11.0.0.1")));
+        assert!(private.contains(ip("::ffff:10.0.0.1")));
+        assert!(!private.contains(ip("fd00::1")));
+
+        let mapped = Cidr::parse("::ffff:10.0.0.0/104").unwrap();
+        assert!(mapped.contains(ip("10.0.0.1")));
+        assert!(mapped.contains(ip("::ffff:10.0.0.1")));
+        assert!(!mapped.contains(ip("::ffff:11.0.0.1")));
+
+        let unique_local = Cidr::parse("fd00::/8").unwrap();
+        assert!(unique_local.contains(ip("fd12:3456::1")));
+        assert!(!unique_local.contains(ip("fe80::1")));
+        assert!(!unique_local.contains(ip("10.0.0.1")));
+
+        assert!(Cidr::parse("0.0.0.0/0").unwrap().contains(ip("8.8.8.8")));
+        assert!(Cidr::parse("::/0").unwrap().contains(ip("2001:db8::1")));
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let access = rules(&["deny 10.0.13.0/24", "allow 10.0.0.0/8", "deny 10.0.14.0/24"]);
+        assert!(!access.permits(ip("10.0.13.7")));
+        assert!(access.permits(ip("10.0.14.7")));
+        assert!(access.permits(ip("::ffff:10.0.14.7")));
+    }
+
+    #[test]
+    fn allow_rules_deny_everyone_else() {
+        let allowlist = rules(&["allow 10.0.0.0/8", "allow ::1"]);
+        assert!(allowlist.permits(ip("10.1.1.1")));
+        assert!(allowlist.permits(ip("::1")));
+        assert!(!allowlist.permits(ip("192.168.1.1")));
+        assert!(!allowlist.permits(ip("127.0.0.1")));
+
+        let denylist = rules(&["deny 192.168.0.0/16"]);
+        assert!(!denylist.permits(ip("192.168.1.1")));
+        assert!(denylist.permits(ip("10.1.1.1")));
+
+        assert!(AccessRules::default().permits(ip("192.168.1.1")));
+    }
+
+    #[test]
+    fn deny_all_ends_a_list() {
+        let access = rules(&["allow 127.0.0.1", "deny all"]);
+        assert!(access.permits(ip("127.0.0.1")));
+        assert!(!access.permits(ip("127.0.0.2")));
+        assert!(!access.permits(ip("::1")));
+
+        assert!(!rules(&["deny all", "allow 127.0.0.1"]).permits(ip("127.0.0.1")));
+    }
+
+    #[test]
+    fn parses_rules() {
+        assert_eq!(
+            AccessRule::parse("allow 10.0.0.0/8").unwrap().to_string(),
+            "allow 10.0.0.0/8"
+        );
+        assert_eq!(
+            AccessRule::parse("deny all").unwrap().to_string(),
+            "deny all"
+        );
+        for rule in ["permit 10.0.0.0/8", "allow", "deny 10.0.0.0/99"] {
+            assert!(AccessRule::parse(rule).is_err(), "{}", rule);
+        }
+    }
+}
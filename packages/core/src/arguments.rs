@@ -1,5 +1,422 @@
 use std::env;
 
+use crate::logger::LogLevel;
+
+/// # ArgumentValue
+///
+/// The type of value a flag takes, checked while parsing.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgumentValue {
+    /// Takes no value, i.e. `--fallback`
+    Switch,
+    Text,
+    /// An integer between 1 and 65535
+    Port,
+    /// One of the `LogLevel`s
+    Level,
+    /// `<prefix or hostname>:<directory>[,option...]`
+    Route,
+    /// `<status>:<path>`
+    ErrorPage,
+}
+
+/// # ArgumentSpec
+///
+/// A flag accepted on the command line, used for parsing and for `--help`.
+///
+#[derive(Debug, Clone, Copy)]
+pub struct ArgumentSpec {
+    pub name: &'static str,
+    pub short: Option<&'static str>,
+    pub value: ArgumentValue,
+    pub value_name: &'static str,
+    pub repeatable: bool,
+    pub description: &'static str,
+}
+
+impl ArgumentSpec {
+    const fn new(
+        name: &'static str,
+        value: ArgumentValue,
+        value_name: &'static str,
+        description: &'static str,
+    ) -> Self {
+        ArgumentSpec {
+            name,
+            short: None,
+            value,
+            value_name,
+            repeatable: false,
+            description,
+        }
+    }
+
+    const fn short(mut self, short: &'static str) -> Self {
+        self.short = Some(short);
+        self
+    }
+
+    const fn repeatable(mut self) -> Self {
+        self.repeatable = true;
+        self
+    }
+}
+
+/// Every flag rsrv accepts, in the order `--help` lists them.
+/// The npm wrapper passes `--dir`, `--port`, `--log-level`, `--cors`,
+/// `--fallback`, `--no-compression` and `--no-port-switching`.
+pub const ARGUMENT_SPECS: [ArgumentSpec; 52] = [
+    ArgumentSpec::new(
+        "--dir",
+        ArgumentValue::Text,
+        "directory",
+        "Serve files from a directory, may be repeated or given as positional arguments",
+    )
+    .repeatable(),
+    ArgumentSpec::new(
+        "--port",
+        ArgumentValue::Port,
+        "port",
+        "Bind the server to a port, default is 8080",
+    )
+    .short("-p"),
+    ArgumentSpec::new(
+        "--host",
+        ArgumentValue::Text,
+        "host",
+        "Bind the server to a host, default is 127.0.0.1",
+    ),
+    ArgumentSpec::new(
+        "--config",
+        ArgumentValue::Text,
+        "path",
+        "Read settings from a toml or json file, default is rsrv.toml or rsrv.json",
+    )
+    .short("-c"),
+    ArgumentSpec::new(
+        "--cors",
+        ArgumentValue::Text,
+        "origin",
+        "Set Access-Control-Allow-Origin, `true` or `*` allow every origin",
+    ),
+    ArgumentSpec::new(
+        "--cache-control",
+        ArgumentValue::Text,
+        "seconds|directives",
+        "Cache-Control for files that are neither html nor content-hashed",
+    ),
+    ArgumentSpec::new(
+        "--cache-control-html",
+        ArgumentValue::Text,
+        "seconds|directives",
+        "Cache-Control for html documents, default is no-cache",
+    ),
+    ArgumentSpec::new(
+        "--cache-control-immutable",
+        ArgumentValue::Text,
+        "seconds|directives",
+        "Cache-Control for content-hashed files",
+    ),
+    ArgumentSpec::new(
+        "--no-immutable-detection",
+        ArgumentValue::Switch,
+        "",
+        "Do not treat content-hashed file names as immutable",
+    ),
+    ArgumentSpec::new(
+        "--no-compression",
+        ArgumentValue::Switch,
+        "",
+        "Do not gzip responses",
+    ),
+    ArgumentSpec::new(
+        "--fallback",
+        ArgumentValue::Switch,
+        "",
+        "Serve index.html for requests that are not found",
+    ),
+    ArgumentSpec::new(
+        "--default-file-path",
+        ArgumentValue::Text,
+        "file",
+        "Serve a file for requests that are not found",
+    ),
+    ArgumentSpec::new(
+        "--index",
+        ArgumentValue::Text,
+        "file",
+        "File served for paths ending in /, default is index.html",
+    ),
+    ArgumentSpec::new(
+        "--mount",
+        ArgumentValue::Route,
+        "prefix:directory[,option]",
+        "Serve a directory under a url prefix, options are listing, cache-control=, fallback=, allow=, deny= and auth=",
+    )
+    .repeatable(),
+    ArgumentSpec::new(
+        "--vhost",
+        ArgumentValue::Route,
+        "hostname:directory[,option]",
+        "Serve a directory for a Host, options are index=, fallback=, header=, cert= and key=",
+    )
+    .repeatable(),
+    ArgumentSpec::new(
+        "--proxy",
+        ArgumentValue::Route,
+        "prefix:http://upstream[,timeout=]",
+        "Forward requests under a url prefix to an http upstream",
+    )
+    .repeatable(),
+    ArgumentSpec::new(
+        "--default-host",
+        ArgumentValue::Text,
+        "hostname",
+        "Virtual host that serves requests matching no other host",
+    ),
+    ArgumentSpec::new(
+        "--rules",
+        ArgumentValue::Text,
+        "path",
+        "Read redirect, rewrite and header rules from a json file",
+    ),
+    ArgumentSpec::new(
+        "--error-page",
+        ArgumentValue::ErrorPage,
+        "status:path",
+        "Serve a custom page for an error status",
+    )
+    .repeatable(),
+    ArgumentSpec::new(
+        "--uploads",
+        ArgumentValue::Switch,
+        "",
+        "Accept authenticated PUT and multipart POST uploads into the served directories",
+    ),
+    ArgumentSpec::new(
+        "--manage-files",
+        ArgumentValue::Switch,
+        "",
+        "Accept authenticated DELETE and POST /__rsrv/move requests for served files",
+    ),
+    ArgumentSpec::new(
+        "--upload-token",
+        ArgumentValue::Text,
+        "token",
+        "Bearer token uploads and file management must carry, prefer RSRV_UPLOAD_TOKEN to keep it out of ps",
+    ),
+    ArgumentSpec::new(
+        "--upload-max-size",
+        ArgumentValue::Text,
+        "size",
+        "Largest upload accepted, i.e. 512KB or 250MB, default is 100MB",
+    ),
+    ArgumentSpec::new(
+        "--webdav",
+        ArgumentValue::Switch,
+        "",
+        "Serve the directories over WebDAV, authenticated with the upload token",
+    ),
+    ArgumentSpec::new(
+        "--webdav-props",
+        ArgumentValue::Text,
+        "file",
+        "Where WebDAV properties are kept, default is .rsrv-webdav-props.json",
+    ),
+    ArgumentSpec::new(
+        "--https",
+        ArgumentValue::Switch,
+        "",
+        "Serve over HTTPS, with a generated development certificate unless --tls-cert is supplied",
+    ),
+    ArgumentSpec::new(
+        "--tls-cert",
+        ArgumentValue::Text,
+        "file",
+        "Serve over HTTPS with this PEM certificate chain, needs --tls-key",
+    ),
+    ArgumentSpec::new(
+        "--tls-key",
+        ArgumentValue::Text,
+        "file",
+        "The PEM private key for --tls-cert",
+    ),
+    ArgumentSpec::new(
+        "--http-redirect-port",
+        ArgumentValue::Port,
+        "port",
+        "Also listen for plain HTTP on this port, redirecting every request to HTTPS",
+    ),
+    ArgumentSpec::new(
+        "--hsts",
+        ArgumentValue::Text,
+        "directives",
+        "Strict-Transport-Security sent over HTTPS, true for max-age=31536000, off by default",
+    ),
+    ArgumentSpec::new(
+        "--no-http2",
+        ArgumentValue::Switch,
+        "",
+        "Serve HTTP/1.1 only, rather than also HTTP/2 over TLS and h2c with prior knowledge",
+    ),
+    ArgumentSpec::new(
+        "--http2-max-streams",
+        ArgumentValue::Text,
+        "count",
+        "Requests a client may have open at once on one HTTP/2 connection, default is 100",
+    ),
+    ArgumentSpec::new(
+        "--idle-timeout",
+        ArgumentValue::Text,
+        "seconds",
+        "Close connections that send no request for this long, default is 30, 0 for never",
+    ),
+    ArgumentSpec::new(
+        "--header-timeout",
+        ArgumentValue::Text,
+        "seconds",
+        "Answer 408 when a request head takes longer than this, default is 10, 0 for never",
+    ),
+    ArgumentSpec::new(
+        "--body-timeout",
+        ArgumentValue::Text,
+        "seconds",
+        "Answer 408 when a request body stalls for this long, default is 30, 0 for never",
+    ),
+    ArgumentSpec::new(
+        "--write-timeout",
+        ArgumentValue::Text,
+        "seconds",
+        "Drop clients that stop reading the response for this long, default is 30, 0 for never",
+    ),
+    ArgumentSpec::new(
+        "--min-rate",
+        ArgumentValue::Text,
+        "bytes",
+        "Slowest a request may arrive in bytes per second after 5 seconds, default is 240, 0 for any",
+    ),
+    ArgumentSpec::new(
+        "--rate-limit",
+        ArgumentValue::Text,
+        "requests",
+        "Requests per second each client IP may make before getting 429, default is no limit",
+    ),
+    ArgumentSpec::new(
+        "--rate-burst",
+        ArgumentValue::Text,
+        "requests",
+        "Requests a client IP may make at once on top of --rate-limit, default is the rate",
+    ),
+    ArgumentSpec::new(
+        "--max-connections-per-ip",
+        ArgumentValue::Text,
+        "count",
+        "Connections each client IP may have open at once, default is no limit",
+    ),
+    ArgumentSpec::new(
+        "--trusted-proxies",
+        ArgumentValue::Text,
+        "ip,ip",
+        "Proxies whose X-Forwarded-For names the client IP to rate limit",
+    ),
+    ArgumentSpec::new(
+        "--allow",
+        ArgumentValue::Text,
+        "cidr",
+        "Accept connections from these addresses, i.e. 10.0.0.0/8, checked in order with --deny",
+    )
+    .repeatable(),
+    ArgumentSpec::new(
+        "--deny",
+        ArgumentValue::Text,
+        "cidr",
+        "Close connections from these addresses, checked in order with --allow",
+    )
+    .repeatable(),
+    ArgumentSpec::new(
+        "--basic-auth",
+        ArgumentValue::Text,
+        "htpasswd",
+        "Ask for Basic credentials from an htpasswd file, bcrypt or SHA hashed",
+    ),
+    ArgumentSpec::new(
+        "--basic-auth-realm",
+        ArgumentValue::Text,
+        "realm",
+        "The realm browsers show when asking for Basic credentials, default is rsrv",
+    ),
+    ArgumentSpec::new(
+        "--basic-auth-exclude",
+        ArgumentValue::Text,
+        "pattern",
+        "Serve paths matching the pattern without credentials, i.e. /healthz",
+    )
+    .repeatable(),
+    ArgumentSpec::new(
+        "--live-reload",
+        ArgumentValue::Switch,
+        "",
+        "Reload pages, or swap stylesheets, when served files change",
+    ),
+    ArgumentSpec::new(
+        "--log-level",
+        ArgumentValue::Level,
+        "level",
+        "One of Info, Warn, Error, Fatal or Silent, default is Info",
+    ),
+    ArgumentSpec::new(
+        "--no-port-switching",
+        ArgumentValue::Switch,
+        "",
+        "Fail rather than switch ports when the port is taken (rsrv never switches)",
+    ),
+    ArgumentSpec::new(
+        "--help",
+        ArgumentValue::Switch,
+        "",
+        "Print this help and exit",
+    )
+    .short("-h"),
+    ArgumentSpec::new(
+        "--version",
+        ArgumentValue::Switch,
+        "",
+        "Print the version and exit",
+    )
+    .short("-V"),
+    ArgumentSpec::new(
+        "--",
+        ArgumentValue::Switch,
+        "",
+        "Treat everything that follows as a directory",
+    ),
+];
+
+/// # ParsedArguments
+///
+/// The command line, split into flags (by their long name) and positional directories.
+///
+#[derive(Debug, Clone, Default)]
+pub struct ParsedArguments {
+    pub flags: Vec<(&'static str, String)>,
+    pub positionals: Vec<String>,
+}
+
+impl ParsedArguments {
+    pub fn values_of(&self, name: &str) -> Vec<String> {
+        self.flags
+            .iter()
+            .filter(|(flag, _)| *flag == name)
+            .map(|(_, value)| value.clone())
+            .collect()
+    }
+
+    pub fn has(&self, name: &str) -> bool {
+        self.flags.iter().any(|(flag, _)| *flag == name)
+    }
+}
+
 /// # Arguments
 ///
 /// A functional struct that provides associated methods
@@ -16,113 +433,492 @@ impl Arguments {
     }
 
     ///
-    /// Provided a string slice, it will filter the result of
-    /// Self::get_command_line_args to see which command line arguments
-    /// contain the provided string
+    /// Parses a full command line, program name included, against `ARGUMENT_SPECS`.
     ///
-    /// Since its intended usage is to select variable command line arguments
-    /// based on a flag, this method strips the provided pattern from the vec of matches
-    /// that it returns.
+    /// Values may be attached, `--port=3000`, or follow the flag, `--port 3000` / `-p 3000`.
+    /// Anything that is not a flag, and everything after `--`, is a directory.
     ///
-    /// i.e.
+    pub fn parse(args: &[String]) -> Result<ParsedArguments, String> {
+        let mut parsed = ParsedArguments::default();
+        let mut remaining = args.iter().skip(1);
+
+        while let Some(arg) = remaining.next() {
+            if arg == "--" {
+                parsed.positionals.extend(remaining.cloned());
+                break;
+            }
+
+            if !arg.starts_with('-') || arg == "-" {
+                parsed.positionals.push(arg.clone());
+                continue;
+            }
+
+            let (name, attached_value) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (arg.as_str(), None),
+            };
+
+            let spec = Self::find_spec(name).ok_or_else(|| Self::unknown_flag_message(name))?;
+
+            let value = match (spec.value, attached_value) {
+                (ArgumentValue::Switch, None) => String::new(),
+                (ArgumentValue::Switch, Some(_)) => {
+                    return Err(format!(
+                        "Arguments::parse() Exception: {} does not take a value, received {}",
+                        spec.name, arg
+                    ))
+                }
+                (_, Some(value)) => value,
+                (_, None) => remaining.next().cloned().ok_or_else(|| {
+                    format!(
+                        "Arguments::parse() Exception: {} expects a <{}>",
+                        spec.name, spec.value_name
+                    )
+                })?,
+            };
+
+            Self::validate_value(spec, &value)?;
+
+            if !spec.repeatable && spec.value != ArgumentValue::Switch && parsed.has(spec.name) {
+                return Err(format!(
+                    "Arguments::parse() Exception: {} was supplied more than once.",
+                    spec.name
+                ));
+            }
+
+            parsed.flags.push((spec.name, value));
+        }
+
+        Ok(parsed)
+    }
+
     ///
-    /// ```
-    /// $ cargo run -- --port=3000
+    /// Returns the values supplied for a flag, matched on its whole name.
+    /// Switches yield an empty string for every time they were supplied.
     ///
-    /// ...
+    /// ```ignore
+    /// $ cargo run -- --port=3000
     ///
     /// Arguments::search_cli_args_on_pattern("--port=");
     /// => ["3000"]
     /// ```
     ///
-    /// ### Intended Usage
+    /// The command line is validated once at startup by `Arguments::parse`,
+    /// an invalid command line yields no values here.
     ///
-    /// ```
-    /// let port_args = Arguments::search_cli_args_on_pattern("--port=");
-    /// ```
     pub fn search_cli_args_on_pattern(flag_pattern: &str) -> Vec<String> {
-        let args = Self::get_command_line_args();
-        args.into_iter()
-            .filter(|arg| arg.contains(&flag_pattern))
-            .map(|arg| arg.replace(flag_pattern, ""))
-            .collect()
+        let name = flag_pattern.trim_end_matches('=');
+        match Self::parse(&Self::get_command_line_args()) {
+            Ok(parsed) => parsed.values_of(name),
+            Err(_) => vec![],
+        }
     }
 
+    ///
+    /// Collects `--dir=` arguments followed by positional directories.
+    ///
     pub fn find_directory_arguments() -> Vec<String> {
-        Self::search_cli_args_on_pattern("--dir=")
-    }
-
-    pub fn find_port_argument_or_get_default() -> usize {
-        let default_port = 8080usize;
-        let port_args: Vec<String> = Self::search_cli_args_on_pattern("--port=");
-        if port_args.len() == 0 {
-            default_port
-        } else {
-            let first_argument = port_args
-                .get(0)
-                .expect("Proved port_args.len() is at least 1.");
-            match first_argument.parse::<usize>() {
-                Ok(port) => port,
-                Err(_) => default_port,
-            }
-        }
+        let parsed = Self::parse(&Self::get_command_line_args()).unwrap_or_default();
+        let mut directories = parsed.values_of("--dir");
+        directories.extend(parsed.positionals);
+        directories
     }
 
-    pub fn find_host_argument_or_get_default() -> String {
-        let default_host = String::from("127.0.0.1");
-        let host_args: Vec<String> = Self::search_cli_args_on_pattern("--host=");
-        if host_args.len() == 0 {
-            default_host
-        } else {
-            host_args
-                .get(0)
-                .expect("Proved host_args.len() is at least 1.")
-                .to_owned()
-        }
+    pub fn find_config_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--config=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_port_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--port=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_host_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--host=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_cors_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--cors=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_index_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--index=")
+            .into_iter()
+            .next()
+    }
+
+    ///
+    /// `--default-file-path=<file>` names the file served when nothing else matches,
+    /// the bare `--fallback` flag is shorthand for `--default-file-path=index.html`
+    ///
+    pub fn find_fallback_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--default-file-path=")
+            .into_iter()
+            .next()
+            .or_else(|| Self::has_switch("--fallback").then(|| String::from("index.html")))
     }
 
-    pub fn find_cors_argument_or_get_default() -> Option<String> {
-        let cors_arguments = Self::search_cli_args_on_pattern("--cors=");
+    pub fn find_no_compression_argument() -> bool {
+        Self::has_switch("--no-compression")
+    }
+
+    pub fn find_live_reload_argument() -> bool {
+        Self::has_switch("--live-reload")
+    }
 
-        if cors_arguments.len() == 0 {
-            return None;
+    pub fn find_uploads_argument() -> bool {
+        Self::has_switch("--uploads")
+    }
+
+    pub fn find_manage_files_argument() -> bool {
+        Self::has_switch("--manage-files")
+    }
+
+    pub fn find_upload_token_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--upload-token=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_upload_max_size_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--upload-max-size=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_webdav_argument() -> bool {
+        Self::has_switch("--webdav")
+    }
+
+    pub fn find_webdav_props_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--webdav-props=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_https_argument() -> bool {
+        Self::has_switch("--https")
+    }
+
+    pub fn find_tls_cert_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--tls-cert=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_tls_key_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--tls-key=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_http_redirect_port_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--http-redirect-port=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_hsts_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--hsts=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_no_http2_argument() -> bool {
+        Self::has_switch("--no-http2")
+    }
+
+    pub fn find_http2_max_streams_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--http2-max-streams=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_idle_timeout_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--idle-timeout=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_header_timeout_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--header-timeout=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_body_timeout_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--body-timeout=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_write_timeout_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--write-timeout=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_min_rate_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--min-rate=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_rate_limit_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--rate-limit=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_rate_burst_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--rate-burst=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_max_connections_per_ip_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--max-connections-per-ip=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_trusted_proxies_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--trusted-proxies=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_log_level_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--log-level=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn has_switch(name: &str) -> bool {
+        !Self::search_cli_args_on_pattern(name).is_empty()
+    }
+
+    pub fn find_cache_control_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--cache-control=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_html_cache_control_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--cache-control-html=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_immutable_cache_control_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--cache-control-immutable=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_no_immutable_detection_argument() -> bool {
+        Self::has_switch("--no-immutable-detection")
+    }
+
+    pub fn format_cache_control_max_age(max_age: &str) -> String {
+        format!("private, max-age={}, must-revalidate", max_age)
+    }
+
+    pub fn find_mount_arguments() -> Vec<String> {
+        Self::search_cli_args_on_pattern("--mount=")
+    }
+
+    ///
+    /// Collects `--allow=` and `--deny=` arguments in the order they were given, as access rules.
+    ///
+    pub fn find_access_arguments() -> Vec<String> {
+        let parsed = Self::parse(&Self::get_command_line_args()).unwrap_or_default();
+        parsed
+            .flags
+            .into_iter()
+            .filter_map(|(flag, cidr)| match flag {
+                "--allow" => Some(format!("allow {}", cidr)),
+                "--deny" => Some(format!("deny {}", cidr)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn find_basic_auth_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--basic-auth=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_basic_auth_realm_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--basic-auth-realm=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_basic_auth_exclude_arguments() -> Vec<String> {
+        Self::search_cli_args_on_pattern("--basic-auth-exclude=")
+    }
+
+    pub fn find_proxy_arguments() -> Vec<String> {
+        Self::search_cli_args_on_pattern("--proxy=")
+    }
+
+    pub fn find_virtual_host_arguments() -> Vec<String> {
+        Self::search_cli_args_on_pattern("--vhost=")
+    }
+
+    pub fn find_rules_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--rules=")
+            .into_iter()
+            .next()
+    }
+
+    pub fn find_default_host_argument() -> Option<String> {
+        Self::search_cli_args_on_pattern("--default-host=")
+            .into_iter()
+            .next()
+    }
+
+    ///
+    /// Collects `--error-page=<status>:<path>` arguments,
+    /// i.e. `--error-page=404:errors/not-found.html`
+    ///
+    /// Paths are resolved against the served directories.
+    ///
+    pub fn find_error_page_arguments() -> Vec<(u16, String)> {
+        Self::search_cli_args_on_pattern("--error-page=")
+            .into_iter()
+            .filter_map(|error_page_argument| {
+                let (status, path) = error_page_argument.split_once(':')?;
+                let status = status.trim().parse::<u16>().ok()?;
+                Some((status, String::from(path.trim())))
+            })
+            .collect()
+    }
+}
+
+impl Arguments {
+    fn find_spec(name: &str) -> Option<&'static ArgumentSpec> {
+        ARGUMENT_SPECS
+            .iter()
+            .find(|spec| spec.name == name || spec.short == Some(name))
+    }
+
+    fn validate_value(spec: &ArgumentSpec, value: &str) -> Result<(), String> {
+        let is_valid = match spec.value {
+            ArgumentValue::Switch => true,
+            ArgumentValue::Text => !value.trim().is_empty(),
+            ArgumentValue::Port => matches!(value.parse::<u16>(), Ok(port) if port > 0),
+            ArgumentValue::Level => LogLevel::parse(value).is_some(),
+            ArgumentValue::Route => matches!(
+                value.split(',').next().and_then(|route| route.split_once(':')),
+                Some((from, to)) if !from.is_empty() && !to.is_empty()
+            ),
+            ArgumentValue::ErrorPage => matches!(
+                value.split_once(':'),
+                Some((status, path))
+                    if !path.trim().is_empty()
+                        && matches!(status.trim().parse::<u16>(), Ok(status) if (400..600).contains(&status))
+            ),
+        };
+
+        if is_valid {
+            return Ok(());
         }
 
-        if cors_arguments.len() > 0 {
-            let cors_argument = cors_arguments
-                .get(0)
-                .expect("Proved cors_arguments.len() is at least 1.");
-            if cors_argument.as_str() == "true" || cors_argument.as_str() == "*" {
-                return Some(String::from("*"));
+        let expected = match spec.value {
+            ArgumentValue::Port => String::from("a port between 1 and 65535"),
+            ArgumentValue::Level => String::from("one of Info, Warn, Error, Fatal or Silent"),
+            ArgumentValue::ErrorPage => {
+                String::from("<status>:<path> with a status between 400 and 599")
             }
+            _ => format!("<{}>", spec.value_name),
+        };
 
-            return Some(cors_argument.to_owned());
-        }
+        Err(format!(
+            "Arguments::parse() Exception: {} expects {}, received \"{}\"",
+            spec.name, expected, value
+        ))
+    }
 
-        None
+    fn unknown_flag_message(name: &str) -> String {
+        let suggestion = ARGUMENT_SPECS
+            .iter()
+            .map(|spec| (Self::edit_distance(name, spec.name), spec.name))
+            .filter(|(distance, _)| *distance <= 2)
+            .min_by_key(|(distance, _)| *distance);
+
+        match suggestion {
+            Some((_, flag)) => format!(
+                "Arguments::parse() Exception: Unknown flag {}, did you mean {}?",
+                name, flag
+            ),
+            None => format!("Arguments::parse() Exception: Unknown flag {}", name),
+        }
     }
 
-    pub fn find_compression_argument_or_get_default() -> Option<()> {
-        let compression_arguments = Self::search_cli_args_on_pattern("--no-compression");
-        if compression_arguments.len() > 0 {
-            Some(());
+    fn edit_distance(a: &str, b: &str) -> usize {
+        let b: Vec<char> = b.chars().collect();
+        let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+        for (i, a_char) in a.chars().enumerate() {
+            let mut current = vec![i + 1];
+            for (j, b_char) in b.iter().enumerate() {
+                let substitution = previous[j] + usize::from(a_char != *b_char);
+                current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+            }
+            previous = current;
         }
 
-        None
+        previous[b.len()]
     }
 
-    pub fn find_cache_control_argument_or_get_default() -> String {
-        let cache_control_default = String::from("private, max-age=259200, must-revalidate");
-        let cache_control_arguments = Self::search_cli_args_on_pattern("--cache-control=");
-        if cache_control_arguments.len() > 0 {
-            let supplied_cache_time = cache_control_arguments
-                .get(0)
-                .expect("Proved cache control args len() is greater than 0.")
-                .to_owned();
+    ///
+    /// Renders `--help` from `ARGUMENT_SPECS`.
+    ///
+    pub fn help_text(version: &str) -> String {
+        let usages: Vec<(String, &str)> = ARGUMENT_SPECS
+            .iter()
+            .map(|spec| {
+                let mut usage = match spec.short {
+                    Some(short) => format!("{}, {}", short, spec.name),
+                    None => format!("    {}", spec.name),
+                };
+                if spec.value != ArgumentValue::Switch {
+                    usage.push_str(&format!(" <{}>", spec.value_name));
+                }
+                (usage, spec.description)
+            })
+            .collect();
+        let width = usages
+            .iter()
+            .map(|(usage, _)| usage.len())
+            .max()
+            .unwrap_or(0);
 
-            return format!("private, max-age={}, must-revalidate", supplied_cache_time);
+        let mut help = format!(
+            "rsrv {}\nA simple, fast command line file server, implemented in rust.\n\nUsage: rsrv [options] [directories...]\n\nOptions:\n",
+            version
+        );
+        for (usage, description) in usages {
+            help.push_str(&format!(
+                "  {:width$}  {}\n",
+                usage,
+                description,
+                width = width
+            ));
         }
-
-        cache_control_default
+        help.push_str(
+            "\nEvery option may also be set in rsrv.toml or with an RSRV_* environment variable.\n",
+        );
+        help
     }
 }
@@ -0,0 +1,199 @@
+use std::future::poll_fn;
+use std::io::{self, ErrorKind, Read, Write};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use rustls::ServerConnection;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+
+/// # AsyncTlsStream
+///
+/// A TLS session over a connection on the event loop, see `EventLoop`.
+///
+/// The handshake and the request head are read without holding a thread. HTTP/2
+/// connections stay on the event loop, HTTP/1.1 ones are handed to a blocking
+/// handler with `into_parts`, the session carrying on as a `ClientStream`.
+///
+pub struct AsyncTlsStream {
+    connection: ServerConnection,
+    stream: TcpStream,
+}
+
+/// Lends a tokio socket to rustls' blocking io calls, `Pending` surfacing as `WouldBlock`.
+struct SyncIo<'a, 'b> {
+    stream: &'a mut TcpStream,
+    context: &'a mut Context<'b>,
+}
+
+impl Read for SyncIo<'_, '_> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let mut read_buffer = ReadBuf::new(buffer);
+        match Pin::new(&mut *self.stream).poll_read(self.context, &mut read_buffer) {
+            Poll::Ready(Ok(())) => Ok(read_buffer.filled().len()),
+            Poll::Ready(Err(e)) => Err(e),
+            Poll::Pending => Err(ErrorKind::WouldBlock.into()),
+        }
+    }
+}
+
+impl Write for SyncIo<'_, '_> {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        match Pin::new(&mut *self.stream).poll_write(self.context, buffer) {
+            Poll::Ready(result) => result,
+            Poll::Pending => Err(ErrorKind::WouldBlock.into()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match Pin::new(&mut *self.stream).poll_flush(self.context) {
+            Poll::Ready(result) => result,
+            Poll::Pending => Err(ErrorKind::WouldBlock.into()),
+        }
+    }
+}
+
+impl AsyncTlsStream {
+    pub fn new(connection: ServerConnection, stream: TcpStream) -> Self {
+        AsyncTlsStream { connection, stream }
+    }
+
+    pub async fn handshake(&mut self) -> io::Result<()> {
+        poll_fn(|context| self.poll_handshake(context)).await
+    }
+
+    ///
+    /// The protocol the client picked over ALPN, once the handshake is done.
+    ///
+    pub fn alpn_protocol(&self) -> Option<&[u8]> {
+        self.connection.alpn_protocol()
+    }
+
+    pub fn into_parts(self) -> (ServerConnection, TcpStream) {
+        (self.connection, self.stream)
+    }
+
+    fn poll_handshake(&mut self, context: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.connection.is_handshaking() {
+            ready!(self.poll_write_tls(context))?;
+
+            let mut io = SyncIo {
+                stream: &mut self.stream,
+                context,
+            };
+            match self.connection.read_tls(&mut io) {
+                Ok(0) => {
+                    return Poll::Ready(Err(io::Error::new(
+                        ErrorKind::UnexpectedEof,
+                        "the client closed the connection during the TLS handshake",
+                    )))
+                }
+                Ok(_) => {
+                    if let Err(e) = self.connection.process_new_packets() {
+                        let _ = self.poll_write_tls(context);
+                        return Poll::Ready(Err(io::Error::new(ErrorKind::InvalidData, e)));
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Poll::Pending,
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+
+        // The server's last handshake messages
+        self.poll_write_tls(context)
+    }
+
+    fn poll_write_tls(&mut self, context: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.connection.wants_write() {
+            let mut io = SyncIo {
+                stream: &mut self.stream,
+                context,
+            };
+            match self.connection.write_tls(&mut io) {
+                Ok(_) => (),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Poll::Pending,
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncRead for AsyncTlsStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        context: &mut Context<'_>,
+        buffer: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        loop {
+            match this.connection.reader().read(buffer.initialize_unfilled()) {
+                Ok(read) => {
+                    buffer.advance(read);
+                    return Poll::Ready(Ok(()));
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => (),
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+
+            if let Poll::Ready(Err(e)) = this.poll_write_tls(context) {
+                return Poll::Ready(Err(e));
+            }
+
+            let mut io = SyncIo {
+                stream: &mut this.stream,
+                context,
+            };
+            match this.connection.read_tls(&mut io) {
+                Ok(0) => return Poll::Ready(Ok(())),
+                Ok(_) => {
+                    if let Err(e) = this.connection.process_new_packets() {
+                        // Sends the alert explaining why the session ends
+                        let _ = this.poll_write_tls(context);
+                        return Poll::Ready(Err(io::Error::new(ErrorKind::InvalidData, e)));
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Poll::Pending,
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+    }
+}
+
+impl AsyncWrite for AsyncTlsStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        context: &mut Context<'_>,
+        buffer: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        loop {
+            let written = this.connection.writer().write(buffer)?;
+            let flushed = this.poll_write_tls(context);
+            if written > 0 || buffer.is_empty() {
+                if let Poll::Ready(Err(e)) = flushed {
+                    return Poll::Ready(Err(e));
+                }
+                return Poll::Ready(Ok(written));
+            }
+            // The session's buffer is full, wait for the socket to take some of it
+            ready!(flushed)?;
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.connection.writer().flush()?;
+        ready!(this.poll_write_tls(context))?;
+        Pin::new(&mut this.stream).poll_flush(context)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.connection.send_close_notify();
+        ready!(this.poll_write_tls(context))?;
+        Pin::new(&mut this.stream).poll_shutdown(context)
+    }
+}
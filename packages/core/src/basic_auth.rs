@@ -0,0 +1,259 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use base64::Engine;
+use sha1::{Digest, Sha1};
+
+use crate::request::Request;
+use crate::rules::RulePattern;
+use crate::upload::UploadPolicy;
+
+pub const DEFAULT_REALM: &str = "rsrv";
+
+/// Hash prefixes of the bcrypt and SHA variants `htpasswd` writes with `-B`, `-s`, `-2` and `-5`
+const SUPPORTED_HASHES: [&str; 6] = ["$2a$", "$2b$", "$2y$", "$5$", "$6$", "{SHA}"];
+
+/// Checked for unknown users, so a wrong username takes as long as a wrong password
+const UNKNOWN_USER_HASH: &str = "$2y$10$VpGyechnEVtyI6Fy0mEbGOk4TlBQOk80ohZmGsP1aC2DSpm/irHQa";
+
+/// Verified credentials remembered before the cache starts over
+const VERIFIED_CACHE_SIZE: usize = 1024;
+
+/// # Htpasswd
+///
+/// The users of an htpasswd file, one `user:hash` per line, as written by Apache's
+/// `htpasswd -B` (bcrypt), `-s` (`{SHA}`), `-2` (SHA-256 crypt) or `-5` (SHA-512 crypt).
+/// Other hashes, MD5 and plain text included, are refused when the file is loaded.
+///
+/// bcrypt is slow by design, so credentials that verified once are remembered, by
+/// digest, until the file is loaded again.
+///
+#[derive(Clone)]
+pub struct Htpasswd {
+    pub path: String,
+    users: HashMap<String, String>,
+    verified: Arc<Mutex<HashSet<[u8; 20]>>>,
+}
+
+impl fmt::Debug for Htpasswd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Htpasswd")
+            .field("path", &self.path)
+            .field("users", &self.users.len())
+            .finish()
+    }
+}
+
+impl Htpasswd {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|e| {
+            format!(
+                "Htpasswd::load() Exception: Unable to read the htpasswd file {}. {}",
+                path, e
+            )
+        })?;
+
+        let mut users: HashMap<String, String> = HashMap::new();
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (user, hash) = line.split_once(':').ok_or_else(|| {
+                format!(
+                    "Htpasswd::load() Exception: Line {} of {} is not user:hash",
+                    index + 1,
+                    path
+                )
+            })?;
+            if !SUPPORTED_HASHES
+                .iter()
+                .any(|prefix| hash.starts_with(prefix))
+            {
+                return Err(format!(
+                    "Htpasswd::load() Exception: {} on line {} of {} uses an unsupported hash, use bcrypt (htpasswd -B) or SHA (htpasswd -s, -2 or -5)",
+                    user,
+                    index + 1,
+                    path
+                ));
+            }
+            users.insert(String::from(user), String::from(hash));
+        }
+
+        if users.is_empty() {
+            return Err(format!("Htpasswd::load() Exception: {} has no users", path));
+        }
+
+        Ok(Htpasswd {
+            path: String::from(path),
+            users,
+            verified: Arc::default(),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.users.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+
+    pub fn verify(&self, user: &str, password: &str) -> bool {
+        let Some(hash) = self.users.get(user) else {
+            Self::verify_hash(password, UNKNOWN_USER_HASH);
+            return false;
+        };
+
+        let digest: [u8; 20] = Sha1::new()
+            .chain_update(user)
+            .chain_update([0])
+            .chain_update(password)
+            .chain_update([0])
+            .chain_update(hash)
+            .finalize()
+            .into();
+        if self.verified_credentials().contains(&digest) {
+            return true;
+        }
+
+        // Verified without holding the lock, bcrypt taking a while
+        if !Self::verify_hash(password, hash) {
+            return false;
+        }
+        let mut verified = self.verified_credentials();
+        if verified.len() >= VERIFIED_CACHE_SIZE {
+            verified.clear();
+        }
+        verified.insert(digest);
+        true
+    }
+
+    fn verified_credentials(&self) -> MutexGuard<'_, HashSet<[u8; 20]>> {
+        match self.verified.lock() {
+            Ok(verified) => verified,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn verify_hash(password: &str, hash: &str) -> bool {
+        match hash.strip_prefix("{SHA}") {
+            Some(expected) => {
+                let digest = base64::engine::general_purpose::STANDARD
+                    .encode(Sha1::digest(password.as_bytes()));
+                UploadPolicy::constant_time_eq(digest.as_bytes(), expected.as_bytes())
+            }
+            None => pwhash::unix::verify(password, hash),
+        }
+    }
+}
+
+/// # BasicAuthSettings
+///
+/// HTTP Basic authentication for the whole server, off by default. Turned on with
+/// `--basic-auth=<htpasswd file>`, `RSRV_BASIC_AUTH` or a `[basic_auth]` table.
+///
+/// ```toml
+/// [basic_auth]
+/// htpasswd = ".htpasswd"
+/// realm = "Preview"
+/// exclude = ["/healthz", "/status/**"]
+/// ```
+///
+/// - `realm`, `--basic-auth-realm` or `RSRV_BASIC_AUTH_REALM`, is named in the
+///   `WWW-Authenticate` challenge browsers prompt with.
+/// - `exclude`, `--basic-auth-exclude` or `RSRV_BASIC_AUTH_EXCLUDE`, are path patterns
+///   like those of `RulePattern` that are served without credentials, i.e. health
+///   checks. They apply to mount credentials too.
+///
+/// Mounts take their own htpasswd file with the `auth=` option, or `basic_auth` in
+/// a `[[mounts]]` table, asked for under the mount in place of the global one. The
+/// htpasswd files are reloaded with the config. Credentials are checked after redirect
+/// and rewrite rules, against the path a request is rewritten to.
+///
+/// Uploads, file management and WebDAV requests that carry the upload token, as a
+/// Bearer token or the Basic password, are let through on the token alone.
+///
+#[derive(Debug, Clone)]
+pub struct BasicAuthSettings {
+    pub htpasswd: Option<Htpasswd>,
+    pub realm: String,
+    pub exclude: Vec<String>,
+}
+
+impl Default for BasicAuthSettings {
+    fn default() -> Self {
+        BasicAuthSettings {
+            htpasswd: None,
+            realm: String::from(DEFAULT_REALM),
+            exclude: vec![],
+        }
+    }
+}
+
+impl BasicAuthSettings {
+    pub fn is_excluded(&self, path: &str) -> bool {
+        self.exclude
+            .iter()
+            .any(|pattern| RulePattern::new(pattern).captures(path).is_some())
+    }
+
+    pub fn challenge(&self) -> String {
+        format!(
+            "Basic realm=\"{}\", charset=\"UTF-8\"",
+            self.realm.replace(['"', '\\'], "")
+        )
+    }
+
+    pub fn describe(&self) -> String {
+        let users = match &self.htpasswd {
+            Some(htpasswd) => format!("{} users from {}", htpasswd.len(), htpasswd.path),
+            None => String::from("mounts only"),
+        };
+        let exclude = match self.exclude.is_empty() {
+            true => String::new(),
+            false => format!(", except {}", self.exclude.join(", ")),
+        };
+
+        format!(
+            "Basic auth enabled, realm \"{}\", {}{}",
+            self.realm, users, exclude
+        )
+    }
+}
+
+/// # BasicAuth
+///
+/// A functional struct that checks a request's Basic credentials against an htpasswd file.
+///
+pub struct BasicAuth;
+
+impl BasicAuth {
+    pub fn authenticates(request: &Request, htpasswd: &Htpasswd) -> bool {
+        Self::credentials(request).is_some_and(|(user, password)| htpasswd.verify(&user, &password))
+    }
+
+    fn credentials(request: &Request) -> Option<(String, String)> {
+        let authorization = request
+            .headers()
+            .map
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case("Authorization"))
+            .map(|(_, value)| value)?;
+
+        let (scheme, credentials) = authorization.trim().split_once(' ')?;
+        if !scheme.eq_ignore_ascii_case("Basic") {
+            return None;
+        }
+
+        let credentials = base64::engine::general_purpose::STANDARD
+            .decode(credentials.trim())
+            .ok()?;
+        let credentials = String::from_utf8(credentials).ok()?;
+        let (user, password) = credentials.split_once(':')?;
+        Some((String::from(user), String::from(password)))
+    }
+}
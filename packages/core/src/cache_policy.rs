@@ -0,0 +1,216 @@
+use crate::arguments::Arguments;
+
+const DEFAULT_CACHE_CONTROL: &str = "private, max-age=259200, must-revalidate";
+const DEFAULT_HTML_CACHE_CONTROL: &str = "no-cache";
+const DEFAULT_IMMUTABLE_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+const FLAG_DIRECTIVES: [&str; 9] = [
+    "public",
+    "private",
+    "no-cache",
+    "no-store",
+    "no-transform",
+    "must-revalidate",
+    "proxy-revalidate",
+    "must-understand",
+    "immutable",
+];
+
+const SECONDS_DIRECTIVES: [&str; 4] = [
+    "max-age",
+    "s-maxage",
+    "stale-while-revalidate",
+    "stale-if-error",
+];
+
+/// # CachePolicy
+///
+/// Decides the `Cache-Control` header for a response based on the path that was served.
+///
+/// - Content-hashed files (`app.3f9a1c2d.js`, `index-BxK3a9Zq.css`) never change under
+///   the same name, so they get `--cache-control-immutable=` (`public, max-age=31536000, immutable`)
+/// - Html documents get `--cache-control-html=` (`no-cache`), so new deploys are picked up
+/// - Everything else gets `--cache-control=` (`private, max-age=259200, must-revalidate`)
+///
+/// Each flag accepts either a number of seconds, which keeps the historical
+/// `private, max-age=N, must-revalidate` template, or a full list of directives, i.e.
+/// `--cache-control="public, max-age=600, stale-while-revalidate=60"`.
+/// Hash detection can be turned off with `--no-immutable-detection`.
+/// The config file sets the same values under `[cache]`, see `Config`.
+///
+#[derive(Debug, Clone)]
+pub struct CachePolicy {
+    pub default_directives: String,
+    pub html_directives: String,
+    pub immutable_directives: String,
+    pub detect_hashed_filenames: bool,
+}
+
+impl Default for CachePolicy {
+    fn default() -> Self {
+        CachePolicy {
+            default_directives: String::from(DEFAULT_CACHE_CONTROL),
+            html_directives: String::from(DEFAULT_HTML_CACHE_CONTROL),
+            immutable_directives: String::from(DEFAULT_IMMUTABLE_CACHE_CONTROL),
+            detect_hashed_filenames: true,
+        }
+    }
+}
+
+impl CachePolicy {
+    ///
+    /// Normalizes a `Cache-Control` value, rejecting directives that are unknown or malformed.
+    ///
+    /// A bare number is treated as a max-age, i.e. `600` => `private, max-age=600, must-revalidate`.
+    /// Directives may be separated by `,` or `;`.
+    ///
+    pub fn parse_directives(value: &str) -> Result<String, String> {
+        let value = value.trim();
+        if !value.is_empty() && value.chars().all(|character| character.is_ascii_digit()) {
+            return Ok(Arguments::format_cache_control_max_age(value));
+        }
+
+        let mut directives: Vec<String> = vec![];
+        for directive in value.split([',', ';']).map(|directive| directive.trim()) {
+            if directive.is_empty() {
+                continue;
+            }
+
+            let (name, argument) = match directive.split_once('=') {
+                Some((name, argument)) => (name.trim().to_lowercase(), Some(argument.trim())),
+                None => (directive.to_lowercase(), None),
+            };
+
+            match argument {
+                None if FLAG_DIRECTIVES.contains(&name.as_str()) => directives.push(name),
+                Some(seconds) if SECONDS_DIRECTIVES.contains(&name.as_str()) => {
+                    if seconds.is_empty() || !seconds.chars().all(|c| c.is_ascii_digit()) {
+                        return Err(format!(
+                            "CachePolicy::parse_directives() Exception: {} expects a number of seconds, received {}",
+                            name, directive
+                        ));
+                    }
+                    directives.push(format!("{}={}", name, seconds));
+                }
+                _ => {
+                    return Err(format!(
+                        "CachePolicy::parse_directives() Exception: Unknown or malformed Cache-Control directive {}",
+                        directive
+                    ))
+                }
+            }
+        }
+
+        if directives.is_empty() {
+            return Err(String::from(
+                "CachePolicy::parse_directives() Exception: Cache-Control must contain at least one directive.",
+            ));
+        }
+
+        Ok(directives.join(", "))
+    }
+
+    pub fn cache_control_for(&self, path: &str) -> String {
+        let file_name = path.rsplit('/').next().unwrap_or(path);
+
+        if self.detect_hashed_filenames && Self::is_content_hashed(file_name) {
+            return self.immutable_directives.clone();
+        }
+
+        if Self::is_html_document(file_name) {
+            return self.html_directives.clone();
+        }
+
+        self.default_directives.clone()
+    }
+}
+
+impl CachePolicy {
+    ///
+    /// Looks for a fingerprint in the file name, after its first segment and outside of
+    /// its extension, i.e. `app.3f9a1c2d.js`, `index-BxK3a9Zq.css` or `chunk_8e1f0b2d.mjs`.
+    ///
+    /// A fingerprint is a segment, separated by `.`, `-` or `_`, of at least 8 hex
+    /// digits, or of at least 8 ascii letters and digits with a digit among them, as
+    /// base32 and base64url hashes are. The first segment is the name itself, so
+    /// `html5shiv.js` or `sha256.js` never count.
+    ///
+    pub fn is_content_hashed(file_name: &str) -> bool {
+        let stem = match file_name.rsplit_once('.') {
+            Some((stem, _)) => stem,
+            None => return false,
+        };
+
+        stem.split(['.', '-', '_']).skip(1).any(|segment| {
+            segment.len() >= 8
+                && (segment.chars().all(|c| c.is_ascii_hexdigit())
+                    || (segment.chars().all(|c| c.is_ascii_alphanumeric())
+                        && segment.chars().any(|c| c.is_ascii_digit())))
+        })
+    }
+
+    pub fn is_html_document(file_name: &str) -> bool {
+        let extension = match file_name.rsplit_once('.') {
+            Some((_, extension)) => extension.to_lowercase(),
+            None => return false,
+        };
+
+        matches!(extension.as_str(), "html" | "htm" | "xhtml")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_fingerprinted_file_names() {
+        for file_name in [
+            "app.3f9a1c2d.js",
+            "app.deadbeef.js",
+            "index-BxK3a9Zq.css",
+            "chunk_8e1f0b2d.mjs",
+            "main.a1b2c3d4e5f6a7b8.js",
+            "main-es2015.3e5f8a9b.js",
+            "logo.5a7f0c1e.svg",
+        ] {
+            assert!(CachePolicy::is_content_hashed(file_name), "{}", file_name);
+        }
+    }
+
+    #[test]
+    fn leaves_names_with_digits_alone() {
+        for file_name in [
+            "base64.js",
+            "html5shiv.js",
+            "i18next.js",
+            "sha256.js",
+            "main-es2015.js",
+            "vue3.min.js",
+            "jquery3x.js",
+            "d3f9a1c2deadbeef.js",
+            "app.3f9a1c.js",
+            "index-BxKaaaZq.css",
+            "README",
+        ] {
+            assert!(!CachePolicy::is_content_hashed(file_name), "{}", file_name);
+        }
+    }
+
+    #[test]
+    fn fingerprinted_files_are_immutable() {
+        let cache_policy = CachePolicy::default();
+        assert_eq!(
+            cache_policy.cache_control_for("/assets/index-BxK3a9Zq.js"),
+            DEFAULT_IMMUTABLE_CACHE_CONTROL
+        );
+        assert_eq!(
+            cache_policy.cache_control_for("/vendor/html5shiv.js"),
+            DEFAULT_CACHE_CONTROL
+        );
+        assert_eq!(
+            cache_policy.cache_control_for("/index.html"),
+            DEFAULT_HTML_CACHE_CONTROL
+        );
+    }
+}
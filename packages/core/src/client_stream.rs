@@ -0,0 +1,192 @@
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use rustls::{ServerConnection, StreamOwned};
+
+use crate::http2::Http2Exchange;
+
+/// # TlsStream
+///
+/// A TLS session over a client connection, which says goodbye with a
+/// `close_notify` alert once the last handle to it is dropped.
+///
+#[derive(Debug)]
+pub struct TlsStream(StreamOwned<ServerConnection, TcpStream>);
+
+impl Drop for TlsStream {
+    fn drop(&mut self) {
+        self.0.conn.send_close_notify();
+        let _ = self.0.conn.complete_io(&mut self.0.sock);
+    }
+}
+
+/// # ClientStream
+///
+/// A connection from a client, either plain or over TLS, which every handler
+/// reads requests from and writes responses to. An HTTP/2 stream is one too,
+/// read and written in HTTP/1.1 form, see `Http2Exchange`.
+///
+/// The TLS session is shared between clones, so a handle can be moved to
+/// another thread, i.e. a delayed mock response or a WebSocket tunnel.
+/// Only one clone reads or writes at a time.
+///
+#[derive(Debug)]
+pub enum ClientStream {
+    Plain(TcpStream),
+    Tls(Arc<Mutex<TlsStream>>),
+    Http2(Arc<Http2Exchange>),
+}
+
+impl ClientStream {
+    ///
+    /// Wraps a connection whose TLS handshake is done, see `AsyncTlsStream::into_parts`.
+    ///
+    pub fn from_tls(connection: ServerConnection, stream: TcpStream) -> Self {
+        ClientStream::Tls(Arc::new(Mutex::new(TlsStream(StreamOwned::new(
+            connection, stream,
+        )))))
+    }
+
+    pub fn is_tls(&self) -> bool {
+        match self {
+            ClientStream::Plain(_) => false,
+            ClientStream::Tls(_) => true,
+            ClientStream::Http2(exchange) => exchange.is_secure(),
+        }
+    }
+
+    ///
+    /// The scheme the client connected with, `http` or `https`.
+    ///
+    pub fn scheme(&self) -> &'static str {
+        if self.is_tls() {
+            "https"
+        } else {
+            "http"
+        }
+    }
+
+    pub fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            ClientStream::Plain(stream) => stream.try_clone().map(ClientStream::Plain),
+            ClientStream::Tls(session) => Ok(ClientStream::Tls(Arc::clone(session))),
+            ClientStream::Http2(exchange) => Ok(ClientStream::Http2(Arc::clone(exchange))),
+        }
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        match self {
+            ClientStream::Http2(exchange) => Ok(exchange.peer_addr()),
+            _ => self.with_tcp_stream(|stream| stream.peer_addr()),
+        }
+    }
+
+    ///
+    /// Applies to the connection, or to the stream for HTTP/2.
+    ///
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            ClientStream::Http2(exchange) => {
+                exchange.set_read_timeout(timeout);
+                Ok(())
+            }
+            _ => self.with_tcp_stream(|stream| stream.set_read_timeout(timeout)),
+        }
+    }
+
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            ClientStream::Http2(exchange) => {
+                exchange.set_write_timeout(timeout);
+                Ok(())
+            }
+            _ => self.with_tcp_stream(|stream| stream.set_write_timeout(timeout)),
+        }
+    }
+
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        match self {
+            ClientStream::Plain(stream) => stream.shutdown(how),
+            ClientStream::Tls(session) => {
+                let mut session = Self::lock(session);
+                if how != Shutdown::Read {
+                    session.0.conn.send_close_notify();
+                    let _ = session.0.flush();
+                }
+                session.0.sock.shutdown(how)
+            }
+            ClientStream::Http2(exchange) => {
+                if how != Shutdown::Read {
+                    exchange.finish();
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn with_tcp_stream<T>(
+        &self,
+        action: impl FnOnce(&TcpStream) -> io::Result<T>,
+    ) -> io::Result<T> {
+        match self {
+            ClientStream::Plain(stream) => action(stream),
+            ClientStream::Tls(session) => action(&Self::lock(session).0.sock),
+            ClientStream::Http2(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "an HTTP/2 stream has no connection of its own",
+            )),
+        }
+    }
+
+    fn lock(session: &Mutex<TlsStream>) -> MutexGuard<'_, TlsStream> {
+        session
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Read for &ClientStream {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            ClientStream::Plain(ref stream) => (&*stream).read(buffer),
+            ClientStream::Tls(ref session) => ClientStream::lock(session).0.read(buffer),
+            ClientStream::Http2(ref exchange) => (&**exchange).read(buffer),
+        }
+    }
+}
+
+impl Write for &ClientStream {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        match *self {
+            ClientStream::Plain(ref stream) => (&*stream).write(buffer),
+            ClientStream::Tls(ref session) => ClientStream::lock(session).0.write(buffer),
+            ClientStream::Http2(ref exchange) => (&**exchange).write(buffer),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            ClientStream::Plain(ref stream) => (&*stream).flush(),
+            ClientStream::Tls(ref session) => ClientStream::lock(session).0.flush(),
+            ClientStream::Http2(ref exchange) => (&**exchange).flush(),
+        }
+    }
+}
+
+impl Read for ClientStream {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        (&*self).read(buffer)
+    }
+}
+
+impl Write for ClientStream {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        (&*self).write(buffer)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&*self).flush()
+    }
+}
@@ -0,0 +1,1500 @@
+use std::collections::HashMap;
+use std::env;
+use std::fmt::{write, Display};
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::Duration;
+
+use serde_json::{Map, Value};
+
+use crate::access_control::{AccessRule, AccessRules};
+use crate::arguments::Arguments;
+use crate::basic_auth::{BasicAuthSettings, Htpasswd};
+use crate::cache_policy::CachePolicy;
+use crate::dev_certificate::DevCertificate;
+use crate::directory::Directory;
+use crate::http2::Http2Settings;
+use crate::logger::LogLevel;
+use crate::mock::{MockBody, MockRoute};
+use crate::mount::Mount;
+use crate::proxy::ProxyRoute;
+use crate::rate_limit::RateLimitSettings;
+use crate::request::HttpMethod;
+use crate::rules::Rules;
+use crate::static_directory_manager::StaticDirectoryManager;
+use crate::timeouts::TimeoutSettings;
+use crate::tls::TlsSettings;
+use crate::upload::UploadPolicy;
+use crate::virtual_host::{VirtualHost, VirtualHosts};
+use crate::webdav::WebDavSettings;
+
+const DEFAULT_CONFIG_FILES: [&str; 2] = ["rsrv.toml", "rsrv.json"];
+
+const TOP_LEVEL_KEYS: [&str; 29] = [
+    "host",
+    "port",
+    "directories",
+    "mounts",
+    "proxies",
+    "mocks",
+    "fallback",
+    "index",
+    "vhosts",
+    "default_host",
+    "cors",
+    "compression",
+    "cache",
+    "error_pages",
+    "uploads",
+    "webdav",
+    "tls",
+    "http2",
+    "timeouts",
+    "rate_limit",
+    "access",
+    "basic_auth",
+    "rules",
+    "redirects",
+    "rewrites",
+    "headers",
+    "log_level",
+    "live_reload",
+    "$schema",
+];
+
+const CACHE_KEYS: [&str; 4] = ["default", "html", "immutable", "detect_hashed_filenames"];
+
+const UPLOAD_KEYS: [&str; 4] = ["enabled", "manage", "token", "max_size"];
+
+const WEBDAV_KEYS: [&str; 2] = ["enabled", "props_file"];
+
+const TLS_KEYS: [&str; 5] = ["enabled", "cert", "key", "redirect_port", "hsts"];
+
+const HTTP2_KEYS: [&str; 2] = ["enabled", "max_concurrent_streams"];
+
+const TIMEOUT_KEYS: [&str; 5] = ["idle", "header_read", "body_read", "write", "min_rate"];
+
+const BASIC_AUTH_KEYS: [&str; 3] = ["htpasswd", "realm", "exclude"];
+
+const RATE_LIMIT_KEYS: [&str; 4] = [
+    "requests_per_second",
+    "burst",
+    "max_connections_per_ip",
+    "trusted_proxies",
+];
+
+/// # ConfigOrigin
+///
+/// Where a setting was read from, used to point validation errors at the right place.
+///
+#[derive(Debug, Clone)]
+pub enum ConfigOrigin {
+    Argument(String),
+    Environment(String),
+    File(String, String),
+    Default,
+}
+
+impl Display for ConfigOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigOrigin::Argument(flag) => write(f, format_args!("argument {}", flag)),
+            ConfigOrigin::Environment(variable) => {
+                write(f, format_args!("environment variable {}", variable))
+            }
+            ConfigOrigin::File(path, key) => write(f, format_args!("{} key `{}`", path, key)),
+            ConfigOrigin::Default => write(f, format_args!("default")),
+        }
+    }
+}
+
+/// # Config
+///
+/// Every setting rsrv runs with, resolved once at startup and passed down to the handlers.
+///
+/// Settings are read from, in order of precedence,
+///
+/// 1. command line flags, i.e. `--port=3000`
+/// 2. `RSRV_*` environment variables, i.e. `RSRV_PORT=3000`
+/// 3. a config file, `--config=<path>` / `RSRV_CONFIG`, or else `rsrv.toml` or `rsrv.json` in the cwd
+/// 4. defaults
+///
+/// Each setting is taken whole from the highest source that supplies it,
+/// so `--dir=public` replaces, rather than extends, the file's `directories`.
+///
+/// ```toml
+/// host = "0.0.0.0"
+/// port = 3000
+/// directories = ["public"]
+/// fallback = "index.html"
+/// cors = "*"
+///
+/// [cache]
+/// html = "no-cache"
+///
+/// [tls]
+/// enabled = true
+///
+/// [http2]
+/// max_concurrent_streams = 100
+///
+/// [[mounts]]
+/// prefix = "/static"
+/// directory = "./dist/assets"
+/// listing = true
+///
+/// [[vhosts]]
+/// hostname = "*.docs.local"
+/// directories = ["./docs"]
+/// headers = { "X-Frame-Options" = "DENY" }
+///
+/// [[redirects]]
+/// source = "/blog/:slug"
+/// destination = "/posts/:slug"
+///
+/// [[mocks]]
+/// method = "GET"
+/// path = "/api/users/:id"
+/// file = "fixtures/users/:id.json"
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub config_file: Option<String>,
+    pub host: String,
+    pub port: u16,
+    pub directories: Vec<String>,
+    pub mounts: Vec<Mount>,
+    pub proxies: Vec<ProxyRoute>,
+    pub mocks: Vec<MockRoute>,
+    pub fallback_file: String,
+    pub index_file: String,
+    pub default_host: Option<String>,
+    pub cors: Option<String>,
+    pub compression: bool,
+    pub log_level: LogLevel,
+    pub live_reload: bool,
+    pub cache_policy: CachePolicy,
+    pub error_pages: Vec<(u16, String)>,
+    pub uploads: UploadPolicy,
+    pub webdav: WebDavSettings,
+    pub tls: TlsSettings,
+    pub http2: Http2Settings,
+    pub timeouts: TimeoutSettings,
+    pub rate_limit: RateLimitSettings,
+    pub access: AccessRules,
+    pub basic_auth: BasicAuthSettings,
+    pub rules: Rules,
+    pub rules_file: Option<String>,
+    /// The virtual hosts, including the default site built from `directories` and `mounts`
+    pub sites: VirtualHosts,
+}
+
+impl Config {
+    pub fn load() -> Result<Self, String> {
+        let (config_file, file) = Self::read_config_file()?;
+        let loader = ConfigLoader {
+            config_file: config_file.clone().unwrap_or_default(),
+            file,
+        };
+        loader.ensure_known_keys()?;
+
+        let host = match loader.setting(Arguments::find_host_argument(), "host", "RSRV_HOST") {
+            Some((origin, value)) => loader.expect_string(&origin, value)?,
+            None => String::from("127.0.0.1"),
+        };
+
+        let port = match loader.setting(Arguments::find_port_argument(), "port", "RSRV_PORT") {
+            Some((origin, value)) => Self::parse_port(&origin, value)?,
+            None => 8080,
+        };
+
+        let directories = loader
+            .list_setting(
+                Arguments::find_directory_arguments(),
+                "--dir",
+                "directories",
+                "RSRV_DIRECTORIES",
+                ',',
+            )?
+            .into_iter()
+            .map(|directory| Directory::get_absolute_path(&directory))
+            .collect();
+
+        let mounts = match loader.setting_value(
+            Arguments::find_mount_arguments(),
+            "--mount",
+            "mounts",
+            "RSRV_MOUNTS",
+            ' ',
+        ) {
+            Some((origin, Value::Array(entries))) => {
+                let mut mounts: Vec<Mount> = vec![];
+                for (index, entry) in entries.iter().enumerate() {
+                    let mount = match entry {
+                        Value::String(mount_argument) => Mount::parse(mount_argument),
+                        _ => Mount::from_json(entry),
+                    };
+                    mounts.push(mount.map_err(|e| Self::error(&origin.indexed(index), &e))?);
+                }
+                mounts
+            }
+            Some((origin, _)) => {
+                return Err(Self::error(&origin, "expected an array of mounts"));
+            }
+            None => vec![],
+        };
+
+        let proxies = match loader.setting_value(
+            Arguments::find_proxy_arguments(),
+            "--proxy",
+            "proxies",
+            "RSRV_PROXIES",
+            ' ',
+        ) {
+            Some((origin, Value::Array(entries))) => {
+                let mut proxies: Vec<ProxyRoute> = vec![];
+                for (index, entry) in entries.iter().enumerate() {
+                    let proxy_route = match entry {
+                        Value::String(proxy_argument) => ProxyRoute::parse(proxy_argument),
+                        _ => ProxyRoute::from_json(entry),
+                    };
+                    proxies.push(proxy_route.map_err(|e| Self::error(&origin.indexed(index), &e))?);
+                }
+                proxies
+            }
+            Some((origin, _)) => {
+                return Err(Self::error(&origin, "expected an array of proxies"));
+            }
+            None => vec![],
+        };
+
+        let mocks = Self::load_mocks(&loader)?;
+
+        let fallback_file = match loader.setting(
+            Arguments::find_fallback_argument(),
+            "fallback",
+            "RSRV_FALLBACK",
+        ) {
+            Some((origin, value)) => loader
+                .expect_string(&origin, value)?
+                .trim_start_matches('/')
+                .to_string(),
+            None => String::from("403.html"),
+        };
+
+        let index_file =
+            match loader.setting(Arguments::find_index_argument(), "index", "RSRV_INDEX") {
+                Some((origin, value)) => loader
+                    .expect_string(&origin, value)?
+                    .trim_start_matches('/')
+                    .to_string(),
+                None => String::from("index.html"),
+            };
+
+        let default_host = match loader.setting(
+            Arguments::find_default_host_argument(),
+            "default_host",
+            "RSRV_DEFAULT_HOST",
+        ) {
+            Some((origin, value)) => Some(loader.expect_string(&origin, value)?),
+            None => None,
+        };
+
+        let cors = match loader.setting(Arguments::find_cors_argument(), "cors", "RSRV_CORS") {
+            Some((_, Value::Bool(false))) => None,
+            Some((_, Value::Bool(true))) => Some(String::from("*")),
+            Some((origin, value)) => match loader.expect_string(&origin, value)?.as_str() {
+                "false" => None,
+                "true" | "*" => Some(String::from("*")),
+                origin_pattern => Some(String::from(origin_pattern)),
+            },
+            None => None,
+        };
+
+        let no_compression_argument =
+            Arguments::find_no_compression_argument().then(|| String::from("false"));
+        let compression =
+            match loader.setting(no_compression_argument, "compression", "RSRV_COMPRESSION") {
+                Some((origin, value)) => Self::parse_bool(&origin, value)?,
+                None => true,
+            };
+
+        let live_reload_argument =
+            Arguments::find_live_reload_argument().then(|| String::from("true"));
+        let live_reload =
+            match loader.setting(live_reload_argument, "live_reload", "RSRV_LIVE_RELOAD") {
+                Some((origin, value)) => Self::parse_bool(&origin, value)?,
+                None => false,
+            };
+
+        let log_level = match loader.setting(
+            Arguments::find_log_level_argument(),
+            "log_level",
+            "RSRV_LOG_LEVEL",
+        ) {
+            Some((origin, value)) => {
+                let level = loader.expect_string(&origin, value)?;
+                LogLevel::parse(&level).ok_or_else(|| {
+                    Self::error(
+                        &origin,
+                        &format!(
+                            "expected one of Info, Warn, Error, Fatal or Silent, received {}",
+                            level
+                        ),
+                    )
+                })?
+            }
+            None => LogLevel::Info,
+        };
+
+        let cache_policy = Self::load_cache_policy(&loader)?;
+        let error_pages = Self::load_error_pages(&loader)?;
+        let uploads = Self::load_upload_policy(&loader)?;
+        let webdav = Self::load_webdav_settings(&loader)?;
+        let tls = Self::load_tls_settings(&loader)?;
+        let http2 = Self::load_http2_settings(&loader)?;
+        let timeouts = Self::load_timeout_settings(&loader)?;
+        let rate_limit = Self::load_rate_limit_settings(&loader)?;
+        let access = Self::load_access_rules(&loader)?;
+        let basic_auth = Self::load_basic_auth_settings(&loader)?;
+        let (rules, rules_file) = Self::load_rules(&loader)?;
+
+        let default_static_directory_manager = StaticDirectoryManager {
+            directories,
+            mounts,
+            backup_file: fallback_file.clone(),
+            index_file: index_file.clone(),
+        };
+        let hosts = Self::load_virtual_hosts(&loader, &fallback_file, &index_file)?;
+        let sites = VirtualHosts::new(
+            hosts,
+            default_host.clone(),
+            default_static_directory_manager,
+        )?;
+
+        let config = Config {
+            config_file,
+            host,
+            port,
+            directories: sites
+                .default_host
+                .static_directory_manager
+                .directories
+                .clone(),
+            mounts: sites.default_host.static_directory_manager.mounts.clone(),
+            proxies,
+            mocks,
+            fallback_file,
+            index_file,
+            default_host,
+            cors,
+            compression,
+            log_level,
+            live_reload,
+            cache_policy,
+            error_pages,
+            uploads,
+            webdav,
+            tls,
+            http2,
+            timeouts,
+            rate_limit,
+            access,
+            basic_auth,
+            rules,
+            rules_file,
+            sites,
+        };
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    ///
+    /// Finds the proxy with the longest prefix that matches the path.
+    ///
+    pub fn find_proxy(&self, path: &str) -> Option<&ProxyRoute> {
+        self.proxies
+            .iter()
+            .filter(|proxy_route| proxy_route.matches(path))
+            .max_by_key(|proxy_route| proxy_route.prefix.len())
+    }
+
+    ///
+    /// Finds the first mock that answers the method and path, along with the path's captures.
+    ///
+    pub fn find_mock(
+        &self,
+        method: &HttpMethod,
+        path: &str,
+    ) -> Option<(&MockRoute, HashMap<String, String>)> {
+        self.mocks
+            .iter()
+            .find_map(|mock| mock.captures(method, path).map(|captures| (mock, captures)))
+    }
+
+    ///
+    /// Checks that everything the config points at on the local fs exists.
+    ///
+    pub fn validate(&self) -> Result<(), String> {
+        if self.sites.hosts.is_empty()
+            && self.directories.is_empty()
+            && self.mounts.is_empty()
+            && self.proxies.is_empty()
+            && self.mocks.is_empty()
+        {
+            return Err(String::from(
+                "Config::validate() Exception: No directories to serve. Supply --dir=<directory>, --mount=<prefix>:<directory> or --vhost=<hostname>:<directory>.",
+            ));
+        }
+
+        for directory in &self.directories {
+            Self::ensure_directory(directory, "served directory")?;
+        }
+
+        for mount in &self.mounts {
+            Self::ensure_directory(&mount.directory, &format!("mount {}", mount.prefix))?;
+        }
+
+        if (self.uploads.enabled || self.uploads.manage) && self.uploads.token.is_none() {
+            return Err(String::from(
+                "Config::validate() Exception: Uploads and file management need a token. Supply --upload-token=<token>, RSRV_UPLOAD_TOKEN or uploads.token.",
+            ));
+        }
+
+        if self.webdav.enabled && self.uploads.token.is_none() {
+            return Err(String::from(
+                "Config::validate() Exception: WebDAV needs the upload token. Supply --upload-token=<token>, RSRV_UPLOAD_TOKEN or uploads.token.",
+            ));
+        }
+
+        Self::ensure_certificate(&self.tls.cert, &self.tls.key, "--tls-cert and --tls-key")?;
+        if let Some(redirect_port) = self.tls.redirect_port {
+            if !self.tls.enabled() {
+                return Err(String::from(
+                    "Config::validate() Exception: Redirecting HTTP to HTTPS needs TLS. Supply --https, or --tls-cert=<file> and --tls-key=<file>.",
+                ));
+            }
+            if redirect_port == self.port {
+                return Err(format!(
+                    "Config::validate() Exception: The HTTP redirect port {} is the port HTTPS is served on.",
+                    redirect_port
+                ));
+            }
+        }
+
+        if self.tls.uses_development_certificate() && DevCertificate::directory().is_none() {
+            return Err(String::from(
+                "Config::validate() Exception: No user config directory to keep the development certificate in. Supply --tls-cert=<file> and --tls-key=<file> instead.",
+            ));
+        }
+
+        for mock in &self.mocks {
+            match &mock.body {
+                // Fixtures named after a capture can only be checked per request
+                MockBody::Fixture(file) if !file.contains(':') && fs::metadata(file).is_err() => {
+                    return Err(format!(
+                        "Config::validate() Exception: The fixture {} for mock {} does not exist.",
+                        file, mock.path
+                    ));
+                }
+                _ => (),
+            }
+        }
+
+        for virtual_host in &self.sites.hosts {
+            for directory in &virtual_host.static_directory_manager.directories {
+                Self::ensure_directory(
+                    directory,
+                    &format!("virtual host {}", virtual_host.hostname),
+                )?;
+            }
+
+            Self::ensure_certificate(
+                &virtual_host.tls_cert,
+                &virtual_host.tls_key,
+                &format!("the virtual host {} cert and key", virtual_host.hostname),
+            )?;
+            if virtual_host.tls_cert.is_some() && !self.tls.enabled() {
+                return Err(format!(
+                    "Config::validate() Exception: The virtual host {} has a certificate, but TLS is off. Supply --https, or --tls-cert=<file> and --tls-key=<file>, for clients without SNI.",
+                    virtual_host.hostname
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn ensure_certificate(
+        cert: &Option<String>,
+        key: &Option<String>,
+        description: &str,
+    ) -> Result<(), String> {
+        let files = match (cert, key) {
+            (Some(cert), Some(key)) => [cert, key],
+            (None, None) => return Ok(()),
+            _ => {
+                return Err(format!(
+                "Config::validate() Exception: TLS needs both a certificate and a key, supply {}.",
+                description
+            ))
+            }
+        };
+
+        match files.iter().find(|file| !Path::new(file).is_file()) {
+            Some(file) => Err(format!(
+                "Config::validate() Exception: The TLS file {} does not exist.",
+                file
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn ensure_directory(directory: &str, description: &str) -> Result<(), String> {
+        match fs::metadata(directory) {
+            Ok(metadata) if metadata.is_dir() => Ok(()),
+            Ok(_) => Err(format!(
+                "Config::validate() Exception: The {} at {} is not a directory.",
+                description, directory
+            )),
+            Err(_) => Err(format!(
+                "Config::validate() Exception: The {} at {} does not exist.",
+                description, directory
+            )),
+        }
+    }
+}
+
+impl Config {
+    fn read_config_file() -> Result<(Option<String>, Value), String> {
+        let explicit_path =
+            Arguments::find_config_argument().or_else(|| env::var("RSRV_CONFIG").ok());
+
+        let path = match explicit_path {
+            Some(path) => {
+                if fs::metadata(&path).is_err() {
+                    return Err(format!(
+                        "Config::load() Exception: The config file {} does not exist.",
+                        path
+                    ));
+                }
+                path
+            }
+            None => match DEFAULT_CONFIG_FILES
+                .iter()
+                .find(|path| fs::metadata(path).is_ok())
+            {
+                Some(path) => String::from(*path),
+                None => return Ok((None, Value::Null)),
+            },
+        };
+
+        let contents = fs::read_to_string(&path).map_err(|e| {
+            format!(
+                "Config::load() Exception: Unable to read config file {}. {}",
+                path, e
+            )
+        })?;
+
+        let value = if path.ends_with(".json") {
+            serde_json::from_str::<Value>(&contents).map_err(|e| {
+                format!(
+                    "Config::load() Exception: {} is not valid json. {}",
+                    path, e
+                )
+            })?
+        } else {
+            toml::from_str::<Value>(&contents).map_err(|e| {
+                format!(
+                    "Config::load() Exception: {} is not valid toml. {}",
+                    path, e
+                )
+            })?
+        };
+
+        if !value.is_object() {
+            return Err(format!(
+                "Config::load() Exception: {} must contain a table of settings.",
+                path
+            ));
+        }
+
+        Ok((Some(path), value))
+    }
+
+    fn load_cache_policy(loader: &ConfigLoader) -> Result<CachePolicy, String> {
+        let mut cache_policy = CachePolicy::default();
+
+        let directive_settings = [
+            (
+                Arguments::find_cache_control_argument(),
+                "--cache-control",
+                "default",
+                "RSRV_CACHE_CONTROL",
+            ),
+            (
+                Arguments::find_html_cache_control_argument(),
+                "--cache-control-html",
+                "html",
+                "RSRV_CACHE_CONTROL_HTML",
+            ),
+            (
+                Arguments::find_immutable_cache_control_argument(),
+                "--cache-control-immutable",
+                "immutable",
+                "RSRV_CACHE_CONTROL_IMMUTABLE",
+            ),
+        ];
+
+        for (argument, flag, key, variable) in directive_settings {
+            if let Some((origin, value)) =
+                loader.nested_setting(argument, flag, "cache", key, variable)
+            {
+                let directives = loader.expect_string(&origin, value)?;
+                let directives = CachePolicy::parse_directives(&directives)
+                    .map_err(|e| Self::error(&origin, &e))?;
+                match key {
+                    "default" => cache_policy.default_directives = directives,
+                    "html" => cache_policy.html_directives = directives,
+                    _ => cache_policy.immutable_directives = directives,
+                }
+            }
+        }
+
+        let no_detection_argument = if Arguments::find_no_immutable_detection_argument() {
+            Some(String::from("false"))
+        } else {
+            None
+        };
+        if let Some((origin, value)) = loader.nested_setting(
+            no_detection_argument,
+            "--no-immutable-detection",
+            "cache",
+            "detect_hashed_filenames",
+            "RSRV_IMMUTABLE_DETECTION",
+        ) {
+            cache_policy.detect_hashed_filenames = Self::parse_bool(&origin, value)?;
+        }
+
+        Ok(cache_policy)
+    }
+
+    fn load_upload_policy(loader: &ConfigLoader) -> Result<UploadPolicy, String> {
+        let mut uploads = UploadPolicy::default();
+
+        let uploads_argument = Arguments::find_uploads_argument().then(|| String::from("true"));
+        if let Some((origin, value)) = loader.nested_setting(
+            uploads_argument,
+            "--uploads",
+            "uploads",
+            "enabled",
+            "RSRV_UPLOADS",
+        ) {
+            uploads.enabled = Self::parse_bool(&origin, value)?;
+        }
+
+        let manage_argument = Arguments::find_manage_files_argument().then(|| String::from("true"));
+        if let Some((origin, value)) = loader.nested_setting(
+            manage_argument,
+            "--manage-files",
+            "uploads",
+            "manage",
+            "RSRV_MANAGE_FILES",
+        ) {
+            uploads.manage = Self::parse_bool(&origin, value)?;
+        }
+
+        if let Some((origin, value)) = loader.nested_setting(
+            Arguments::find_upload_token_argument(),
+            "--upload-token",
+            "uploads",
+            "token",
+            "RSRV_UPLOAD_TOKEN",
+        ) {
+            let token = loader.expect_string(&origin, value)?;
+            if token.trim().is_empty() {
+                return Err(Self::error(&origin, "expected a non-empty token"));
+            }
+            uploads.token = Some(token);
+        }
+
+        if let Some((origin, value)) = loader.nested_setting(
+            Arguments::find_upload_max_size_argument(),
+            "--upload-max-size",
+            "uploads",
+            "max_size",
+            "RSRV_UPLOAD_MAX_SIZE",
+        ) {
+            let max_size = match value {
+                Value::Number(bytes) => bytes.to_string(),
+                value => loader.expect_string(&origin, value)?,
+            };
+            uploads.max_size =
+                UploadPolicy::parse_size(&max_size).map_err(|e| Self::error(&origin, &e))?;
+        }
+
+        Ok(uploads)
+    }
+
+    fn load_webdav_settings(loader: &ConfigLoader) -> Result<WebDavSettings, String> {
+        let mut webdav = WebDavSettings::default();
+
+        let webdav_argument = Arguments::find_webdav_argument().then(|| String::from("true"));
+        if let Some((origin, value)) = loader.nested_setting(
+            webdav_argument,
+            "--webdav",
+            "webdav",
+            "enabled",
+            "RSRV_WEBDAV",
+        ) {
+            webdav.enabled = Self::parse_bool(&origin, value)?;
+        }
+
+        if let Some((origin, value)) = loader.nested_setting(
+            Arguments::find_webdav_props_argument(),
+            "--webdav-props",
+            "webdav",
+            "props_file",
+            "RSRV_WEBDAV_PROPS",
+        ) {
+            let props_file = loader.expect_string(&origin, value)?;
+            if props_file.trim().is_empty() {
+                return Err(Self::error(&origin, "expected a file path"));
+            }
+            webdav.props_file = props_file;
+        }
+
+        Ok(webdav)
+    }
+
+    fn load_tls_settings(loader: &ConfigLoader) -> Result<TlsSettings, String> {
+        let mut tls = TlsSettings::default();
+
+        let https_argument = Arguments::find_https_argument().then(|| String::from("true"));
+        if let Some((origin, value)) =
+            loader.nested_setting(https_argument, "--https", "tls", "enabled", "RSRV_HTTPS")
+        {
+            tls.https = Self::parse_bool(&origin, value)?;
+        }
+
+        if let Some((origin, value)) = loader.nested_setting(
+            Arguments::find_tls_cert_argument(),
+            "--tls-cert",
+            "tls",
+            "cert",
+            "RSRV_TLS_CERT",
+        ) {
+            tls.cert = Some(loader.expect_string(&origin, value)?);
+        }
+
+        if let Some((origin, value)) = loader.nested_setting(
+            Arguments::find_tls_key_argument(),
+            "--tls-key",
+            "tls",
+            "key",
+            "RSRV_TLS_KEY",
+        ) {
+            tls.key = Some(loader.expect_string(&origin, value)?);
+        }
+
+        if let Some((origin, value)) = loader.nested_setting(
+            Arguments::find_http_redirect_port_argument(),
+            "--http-redirect-port",
+            "tls",
+            "redirect_port",
+            "RSRV_HTTP_REDIRECT_PORT",
+        ) {
+            tls.redirect_port = Some(Self::parse_port(&origin, value)?);
+        }
+
+        if let Some((origin, value)) = loader.nested_setting(
+            Arguments::find_hsts_argument(),
+            "--hsts",
+            "tls",
+            "hsts",
+            "RSRV_HSTS",
+        ) {
+            let hsts = match value {
+                Value::Bool(hsts) => hsts.to_string(),
+                value => loader.expect_string(&origin, value)?,
+            };
+            tls.hsts = TlsSettings::parse_hsts(&hsts).map_err(|e| Self::error(&origin, &e))?;
+        }
+
+        Ok(tls)
+    }
+
+    fn load_http2_settings(loader: &ConfigLoader) -> Result<Http2Settings, String> {
+        let mut http2 = Http2Settings::default();
+
+        let no_http2_argument = Arguments::find_no_http2_argument().then(|| String::from("false"));
+        if let Some((origin, value)) = loader.nested_setting(
+            no_http2_argument,
+            "--no-http2",
+            "http2",
+            "enabled",
+            "RSRV_HTTP2",
+        ) {
+            http2.enabled = Self::parse_bool(&origin, value)?;
+        }
+
+        if let Some((origin, value)) = loader.nested_setting(
+            Arguments::find_http2_max_streams_argument(),
+            "--http2-max-streams",
+            "http2",
+            "max_concurrent_streams",
+            "RSRV_HTTP2_MAX_STREAMS",
+        ) {
+            let max_concurrent_streams = match &value {
+                Value::Number(number) => number.as_u64(),
+                Value::String(count) => count.trim().parse::<u64>().ok(),
+                _ => None,
+            };
+            http2.max_concurrent_streams = match max_concurrent_streams {
+                Some(count) if (1..=u32::MAX as u64).contains(&count) => count as u32,
+                _ => {
+                    return Err(Self::error(
+                        &origin,
+                        &format!("expected a positive number of streams, received {}", value),
+                    ))
+                }
+            };
+        }
+
+        Ok(http2)
+    }
+
+    fn load_timeout_settings(loader: &ConfigLoader) -> Result<TimeoutSettings, String> {
+        let mut timeouts = TimeoutSettings::default();
+
+        let timeout_settings = [
+            (
+                Arguments::find_idle_timeout_argument(),
+                "--idle-timeout",
+                "idle",
+                "RSRV_IDLE_TIMEOUT",
+                &mut timeouts.idle,
+            ),
+            (
+                Arguments::find_header_timeout_argument(),
+                "--header-timeout",
+                "header_read",
+                "RSRV_HEADER_TIMEOUT",
+                &mut timeouts.header_read,
+            ),
+            (
+                Arguments::find_body_timeout_argument(),
+                "--body-timeout",
+                "body_read",
+                "RSRV_BODY_TIMEOUT",
+                &mut timeouts.body_read,
+            ),
+            (
+                Arguments::find_write_timeout_argument(),
+                "--write-timeout",
+                "write",
+                "RSRV_WRITE_TIMEOUT",
+                &mut timeouts.write,
+            ),
+        ];
+        for (argument, flag, key, variable, timeout) in timeout_settings {
+            if let Some((origin, value)) =
+                loader.nested_setting(argument, flag, "timeouts", key, variable)
+            {
+                *timeout =
+                    Self::parse_whole_number(&origin, value, "seconds")?.map(Duration::from_secs);
+            }
+        }
+
+        if let Some((origin, value)) = loader.nested_setting(
+            Arguments::find_min_rate_argument(),
+            "--min-rate",
+            "timeouts",
+            "min_rate",
+            "RSRV_MIN_RATE",
+        ) {
+            timeouts.min_rate = Self::parse_whole_number(&origin, value, "bytes per second")?;
+        }
+
+        Ok(timeouts)
+    }
+
+    fn load_rate_limit_settings(loader: &ConfigLoader) -> Result<RateLimitSettings, String> {
+        let mut rate_limit = RateLimitSettings::default();
+
+        let limit_settings = [
+            (
+                Arguments::find_rate_limit_argument(),
+                "--rate-limit",
+                "requests_per_second",
+                "RSRV_RATE_LIMIT",
+                "requests per second",
+                &mut rate_limit.requests_per_second,
+            ),
+            (
+                Arguments::find_rate_burst_argument(),
+                "--rate-burst",
+                "burst",
+                "RSRV_RATE_BURST",
+                "requests",
+                &mut rate_limit.burst,
+            ),
+            (
+                Arguments::find_max_connections_per_ip_argument(),
+                "--max-connections-per-ip",
+                "max_connections_per_ip",
+                "RSRV_MAX_CONNECTIONS_PER_IP",
+                "connections",
+                &mut rate_limit.max_connections_per_ip,
+            ),
+        ];
+        for (argument, flag, key, variable, unit, limit) in limit_settings {
+            if let Some((origin, value)) =
+                loader.nested_setting(argument, flag, "rate_limit", key, variable)
+            {
+                *limit = Self::parse_whole_number(&origin, value, unit)?;
+            }
+        }
+
+        if let Some((origin, value)) = loader.nested_setting(
+            Arguments::find_trusted_proxies_argument(),
+            "--trusted-proxies",
+            "rate_limit",
+            "trusted_proxies",
+            "RSRV_TRUSTED_PROXIES",
+        ) {
+            let proxies = match value {
+                Value::String(proxies) => proxies
+                    .split(',')
+                    .map(|proxy| proxy.trim())
+                    .filter(|proxy| !proxy.is_empty())
+                    .map(|proxy| Value::String(String::from(proxy)))
+                    .collect(),
+                Value::Array(proxies) => proxies,
+                value => {
+                    return Err(Self::error(
+                        &origin,
+                        &format!("expected a list of IP addresses, received {}", value),
+                    ))
+                }
+            };
+
+            for (index, proxy) in proxies.into_iter().enumerate() {
+                let ip = match &proxy {
+                    Value::String(ip) => ip.parse::<IpAddr>().ok(),
+                    _ => None,
+                };
+                rate_limit.trusted_proxies.push(ip.ok_or_else(|| {
+                    Self::error(
+                        &origin.indexed(index),
+                        &format!("expected an IP address, received {}", proxy),
+                    )
+                })?);
+            }
+        }
+
+        Ok(rate_limit)
+    }
+
+    fn load_access_rules(loader: &ConfigLoader) -> Result<AccessRules, String> {
+        match loader.setting_value(
+            Arguments::find_access_arguments(),
+            "--allow/--deny",
+            "access",
+            "RSRV_ACCESS",
+            ',',
+        ) {
+            Some((origin, Value::Array(entries))) => {
+                let mut access = AccessRules::default();
+                for (index, entry) in entries.iter().enumerate() {
+                    let rule = match entry {
+                        Value::String(rule) => AccessRule::parse(rule),
+                        _ => Err(format!(
+                            "expected a rule like \"allow 10.0.0.0/8\", received {}",
+                            entry
+                        )),
+                    };
+                    access
+                        .rules
+                        .push(rule.map_err(|e| Self::error(&origin.indexed(index), &e))?);
+                }
+                Ok(access)
+            }
+            Some((origin, _)) => Err(Self::error(&origin, "expected an array of access rules")),
+            None => Ok(AccessRules::default()),
+        }
+    }
+
+    fn load_basic_auth_settings(loader: &ConfigLoader) -> Result<BasicAuthSettings, String> {
+        let mut basic_auth = BasicAuthSettings::default();
+
+        if let Some((origin, value)) = loader.nested_setting(
+            Arguments::find_basic_auth_argument(),
+            "--basic-auth",
+            "basic_auth",
+            "htpasswd",
+            "RSRV_BASIC_AUTH",
+        ) {
+            let htpasswd = loader.expect_string(&origin, value)?;
+            basic_auth.htpasswd =
+                Some(Htpasswd::load(&htpasswd).map_err(|e| Self::error(&origin, &e))?);
+        }
+
+        if let Some((origin, value)) = loader.nested_setting(
+            Arguments::find_basic_auth_realm_argument(),
+            "--basic-auth-realm",
+            "basic_auth",
+            "realm",
+            "RSRV_BASIC_AUTH_REALM",
+        ) {
+            basic_auth.realm = loader.expect_string(&origin, value)?;
+        }
+
+        let exclude_arguments = Arguments::find_basic_auth_exclude_arguments();
+        let exclude = match exclude_arguments.is_empty() {
+            false => Some((
+                ConfigOrigin::Argument(String::from("--basic-auth-exclude")),
+                Value::Array(exclude_arguments.into_iter().map(Value::String).collect()),
+            )),
+            true => loader.nested_setting(
+                None,
+                "--basic-auth-exclude",
+                "basic_auth",
+                "exclude",
+                "RSRV_BASIC_AUTH_EXCLUDE",
+            ),
+        };
+        if let Some((origin, value)) = exclude {
+            let patterns = match value {
+                Value::String(patterns) => patterns
+                    .split(',')
+                    .map(|pattern| pattern.trim())
+                    .filter(|pattern| !pattern.is_empty())
+                    .map(|pattern| Value::String(String::from(pattern)))
+                    .collect(),
+                Value::Array(patterns) => patterns,
+                value => {
+                    return Err(Self::error(
+                        &origin,
+                        &format!("expected a list of path patterns, received {}", value),
+                    ))
+                }
+            };
+
+            for (index, pattern) in patterns.into_iter().enumerate() {
+                match pattern {
+                    Value::String(pattern) if pattern.starts_with('/') => {
+                        basic_auth.exclude.push(pattern)
+                    }
+                    pattern => {
+                        return Err(Self::error(
+                            &origin.indexed(index),
+                            &format!(
+                                "expected a path pattern like /healthz, received {}",
+                                pattern
+                            ),
+                        ))
+                    }
+                }
+            }
+        }
+
+        Ok(basic_auth)
+    }
+
+    fn load_error_pages(loader: &ConfigLoader) -> Result<Vec<(u16, String)>, String> {
+        let arguments = Arguments::find_error_page_arguments();
+        if !arguments.is_empty() {
+            return Ok(arguments);
+        }
+
+        let error_pages = match loader.file.get("error_pages") {
+            Some(Value::Object(error_pages)) => error_pages,
+            Some(_) => {
+                return Err(Self::error(
+                    &loader.origin("error_pages"),
+                    "expected a table of <status> = <path>",
+                ))
+            }
+            None => return Ok(vec![]),
+        };
+
+        let mut parsed_error_pages: Vec<(u16, String)> = vec![];
+        for (status, path) in error_pages {
+            let origin = loader.origin(&format!("error_pages.{}", status));
+            let status = status
+                .parse::<u16>()
+                .ok()
+                .filter(|status| (400..600).contains(status))
+                .ok_or_else(|| {
+                    Self::error(&origin, "expected an error status between 400 and 599")
+                })?;
+            let path = loader.expect_string(&origin, path.clone())?;
+            parsed_error_pages.push((status, path));
+        }
+
+        Ok(parsed_error_pages)
+    }
+
+    fn load_rules(loader: &ConfigLoader) -> Result<(Rules, Option<String>), String> {
+        let mut rules = Rules::from_json_value(&loader.file)
+            .map_err(|e| Self::error(&loader.origin("rules"), &e))?;
+
+        let mut rules_file: Option<String> = None;
+        if let Some((origin, value)) =
+            loader.setting(Arguments::find_rules_argument(), "rules", "RSRV_RULES")
+        {
+            let rules_path = loader.expect_string(&origin, value)?;
+            rules_file = Some(rules_path.clone());
+            let file_rules = Rules::from_file(&rules_path).map_err(|e| Self::error(&origin, &e))?;
+            rules.rules.extend(file_rules.rules);
+            rules
+                .header_rules
+                .rules
+                .extend(file_rules.header_rules.rules);
+        }
+
+        Ok((rules, rules_file))
+    }
+
+    fn load_mocks(loader: &ConfigLoader) -> Result<Vec<MockRoute>, String> {
+        match loader.file.get("mocks") {
+            Some(Value::Array(entries)) => {
+                let mut mocks: Vec<MockRoute> = vec![];
+                for (index, entry) in entries.iter().enumerate() {
+                    let mock = MockRoute::from_json(entry).map_err(|e| {
+                        Self::error(&loader.origin(&format!("mocks[{}]", index)), &e)
+                    })?;
+                    mocks.push(mock);
+                }
+                Ok(mocks)
+            }
+            Some(_) => Err(Self::error(
+                &loader.origin("mocks"),
+                "expected an array of tables",
+            )),
+            None => Ok(vec![]),
+        }
+    }
+
+    fn load_virtual_hosts(
+        loader: &ConfigLoader,
+        fallback_file: &str,
+        index_file: &str,
+    ) -> Result<Vec<VirtualHost>, String> {
+        let mut virtual_hosts: Vec<VirtualHost> = vec![];
+
+        let arguments = Arguments::find_virtual_host_arguments();
+        if !arguments.is_empty() {
+            for vhost_argument in arguments {
+                VirtualHost::apply_argument(
+                    &mut virtual_hosts,
+                    &vhost_argument,
+                    fallback_file,
+                    index_file,
+                )
+                .map_err(|e| Self::error(&ConfigOrigin::Argument(String::from("--vhost")), &e))?;
+            }
+            return Ok(virtual_hosts);
+        }
+
+        match loader.file.get("vhosts") {
+            Some(Value::Array(entries)) => {
+                for (index, entry) in entries.iter().enumerate() {
+                    let virtual_host = VirtualHost::from_json(entry, fallback_file, index_file)
+                        .map_err(|e| {
+                            Self::error(&loader.origin(&format!("vhosts[{}]", index)), &e)
+                        })?;
+                    virtual_hosts.push(virtual_host);
+                }
+                Ok(virtual_hosts)
+            }
+            Some(_) => Err(Self::error(
+                &loader.origin("vhosts"),
+                "expected an array of tables",
+            )),
+            None => Ok(virtual_hosts),
+        }
+    }
+
+    fn parse_port(origin: &ConfigOrigin, value: Value) -> Result<u16, String> {
+        let port = match &value {
+            Value::Number(number) => number.as_u64(),
+            Value::String(port) => port.trim().parse::<u64>().ok(),
+            _ => None,
+        };
+
+        match port {
+            Some(port) if (1..=65535).contains(&port) => Ok(port as u16),
+            _ => Err(Self::error(
+                origin,
+                &format!("expected a port between 1 and 65535, received {}", value),
+            )),
+        }
+    }
+
+    ///
+    /// Parses a count of `unit`, `0` meaning none.
+    ///
+    fn parse_whole_number(
+        origin: &ConfigOrigin,
+        value: Value,
+        unit: &str,
+    ) -> Result<Option<u64>, String> {
+        let number = match &value {
+            Value::Number(number) => number.as_u64(),
+            Value::String(number) => number.trim().parse::<u64>().ok(),
+            _ => None,
+        };
+
+        match number {
+            Some(0) => Ok(None),
+            Some(number) => Ok(Some(number)),
+            None => Err(Self::error(
+                origin,
+                &format!("expected a whole number of {}, received {}", unit, value),
+            )),
+        }
+    }
+
+    fn parse_bool(origin: &ConfigOrigin, value: Value) -> Result<bool, String> {
+        match &value {
+            Value::Bool(flag) => Ok(*flag),
+            Value::String(flag) => match flag.trim().to_lowercase().as_str() {
+                "true" | "1" | "yes" | "on" => Ok(true),
+                "false" | "0" | "no" | "off" => Ok(false),
+                _ => Err(Self::error(
+                    origin,
+                    &format!("expected true or false, received {}", value),
+                )),
+            },
+            _ => Err(Self::error(
+                origin,
+                &format!("expected true or false, received {}", value),
+            )),
+        }
+    }
+
+    pub fn error(origin: &ConfigOrigin, message: &str) -> String {
+        format!("Config::load() Exception: Invalid {}: {}", origin, message)
+    }
+}
+
+impl ConfigOrigin {
+    fn indexed(&self, index: usize) -> ConfigOrigin {
+        match self {
+            ConfigOrigin::File(path, key) => {
+                ConfigOrigin::File(path.clone(), format!("{}[{}]", key, index))
+            }
+            origin => origin.clone(),
+        }
+    }
+}
+
+/// # ConfigLoader
+///
+/// Resolves individual settings across the command line, environment and config file.
+///
+struct ConfigLoader {
+    config_file: String,
+    file: Value,
+}
+
+impl ConfigLoader {
+    fn origin(&self, key: &str) -> ConfigOrigin {
+        ConfigOrigin::File(self.config_file.clone(), String::from(key))
+    }
+
+    ///
+    /// Finds the highest precedence source for a single valued setting.
+    ///
+    fn setting(
+        &self,
+        argument: Option<String>,
+        key: &str,
+        variable: &str,
+    ) -> Option<(ConfigOrigin, Value)> {
+        if let Some(argument) = argument {
+            return Some((
+                ConfigOrigin::Argument(format!("--{}", key.replace('_', "-"))),
+                Value::String(argument),
+            ));
+        }
+
+        if let Ok(environment_value) = env::var(variable) {
+            return Some((
+                ConfigOrigin::Environment(String::from(variable)),
+                Value::String(environment_value),
+            ));
+        }
+
+        self.file
+            .get(key)
+            .map(|value| (self.origin(key), value.clone()))
+    }
+
+    fn nested_setting(
+        &self,
+        argument: Option<String>,
+        flag: &str,
+        table: &str,
+        key: &str,
+        variable: &str,
+    ) -> Option<(ConfigOrigin, Value)> {
+        if let Some(argument) = argument {
+            return Some((
+                ConfigOrigin::Argument(String::from(flag)),
+                Value::String(argument),
+            ));
+        }
+
+        if let Ok(environment_value) = env::var(variable) {
+            return Some((
+                ConfigOrigin::Environment(String::from(variable)),
+                Value::String(environment_value),
+            ));
+        }
+
+        self.file
+            .get(table)
+            .and_then(|table_value| table_value.get(key))
+            .map(|value| (self.origin(&format!("{}.{}", table, key)), value.clone()))
+    }
+
+    ///
+    /// Finds the highest precedence source for a repeatable setting.
+    /// Environment variables hold every value in one string, split on `separator`.
+    ///
+    fn setting_value(
+        &self,
+        arguments: Vec<String>,
+        flag: &str,
+        key: &str,
+        variable: &str,
+        separator: char,
+    ) -> Option<(ConfigOrigin, Value)> {
+        if !arguments.is_empty() {
+            return Some((
+                ConfigOrigin::Argument(String::from(flag)),
+                Value::Array(arguments.into_iter().map(Value::String).collect()),
+            ));
+        }
+
+        if let Ok(environment_value) = env::var(variable) {
+            return Some((
+                ConfigOrigin::Environment(String::from(variable)),
+                Value::Array(
+                    environment_value
+                        .split(separator)
+                        .map(|value| value.trim())
+                        .filter(|value| !value.is_empty())
+                        .map(|value| Value::String(String::from(value)))
+                        .collect(),
+                ),
+            ));
+        }
+
+        self.file
+            .get(key)
+            .map(|value| (self.origin(key), value.clone()))
+    }
+
+    fn list_setting(
+        &self,
+        arguments: Vec<String>,
+        flag: &str,
+        key: &str,
+        variable: &str,
+        separator: char,
+    ) -> Result<Vec<String>, String> {
+        match self.setting_value(arguments, flag, key, variable, separator) {
+            Some((origin, Value::Array(values))) => values
+                .into_iter()
+                .enumerate()
+                .map(|(index, value)| self.expect_string(&origin.indexed(index), value))
+                .collect(),
+            Some((origin, _)) => Err(Config::error(&origin, "expected an array of strings")),
+            None => Ok(vec![]),
+        }
+    }
+
+    fn expect_string(&self, origin: &ConfigOrigin, value: Value) -> Result<String, String> {
+        match value {
+            Value::String(value) if !value.trim().is_empty() => Ok(value),
+            Value::String(_) => Err(Config::error(
+                origin,
+                "expected a value, received an empty string",
+            )),
+            value => Err(Config::error(
+                origin,
+                &format!("expected a string, received {}", value),
+            )),
+        }
+    }
+
+    fn ensure_known_keys(&self) -> Result<(), String> {
+        let table = match &self.file {
+            Value::Object(table) => table,
+            _ => return Ok(()),
+        };
+
+        Self::ensure_keys_in(table, &TOP_LEVEL_KEYS, "")
+            .map_err(|key| Config::error(&self.origin(&key), "unknown setting"))?;
+
+        if let Some(Value::Object(cache)) = table.get("cache") {
+            Self::ensure_keys_in(cache, &CACHE_KEYS, "cache.")
+                .map_err(|key| Config::error(&self.origin(&key), "unknown setting"))?;
+        }
+
+        if let Some(Value::Object(uploads)) = table.get("uploads") {
+            Self::ensure_keys_in(uploads, &UPLOAD_KEYS, "uploads.")
+                .map_err(|key| Config::error(&self.origin(&key), "unknown setting"))?;
+        }
+
+        if let Some(Value::Object(webdav)) = table.get("webdav") {
+            Self::ensure_keys_in(webdav, &WEBDAV_KEYS, "webdav.")
+                .map_err(|key| Config::error(&self.origin(&key), "unknown setting"))?;
+        }
+
+        if let Some(Value::Object(tls)) = table.get("tls") {
+            Self::ensure_keys_in(tls, &TLS_KEYS, "tls.")
+                .map_err(|key| Config::error(&self.origin(&key), "unknown setting"))?;
+        }
+
+        if let Some(Value::Object(http2)) = table.get("http2") {
+            Self::ensure_keys_in(http2, &HTTP2_KEYS, "http2.")
+                .map_err(|key| Config::error(&self.origin(&key), "unknown setting"))?;
+        }
+
+        if let Some(Value::Object(timeouts)) = table.get("timeouts") {
+            Self::ensure_keys_in(timeouts, &TIMEOUT_KEYS, "timeouts.")
+                .map_err(|key| Config::error(&self.origin(&key), "unknown setting"))?;
+        }
+
+        if let Some(Value::Object(basic_auth)) = table.get("basic_auth") {
+            Self::ensure_keys_in(basic_auth, &BASIC_AUTH_KEYS, "basic_auth.")
+                .map_err(|key| Config::error(&self.origin(&key), "unknown setting"))?;
+        }
+
+        if let Some(Value::Object(rate_limit)) = table.get("rate_limit") {
+            Self::ensure_keys_in(rate_limit, &RATE_LIMIT_KEYS, "rate_limit.")
+                .map_err(|key| Config::error(&self.origin(&key), "unknown setting"))?;
+        }
+
+        Ok(())
+    }
+
+    fn ensure_keys_in(
+        table: &Map<String, Value>,
+        known_keys: &[&str],
+        prefix: &str,
+    ) -> Result<(), String> {
+        match table.keys().find(|key| !known_keys.contains(&key.as_str())) {
+            Some(key) => Err(format!("{}{}", prefix, key)),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::config::Config;
+use crate::logger::Logger;
+use crate::tls::Tls;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// # SharedConfig
+///
+/// The configuration currently in effect, shared between the listener and the `ConfigWatcher`.
+///
+/// Each connection takes a snapshot with `current()` and keeps it until it is done,
+/// so a reload only ever applies to requests that arrive after it.
+///
+#[derive(Debug, Clone)]
+pub struct SharedConfig {
+    inner: Arc<RwLock<Arc<Config>>>,
+}
+
+impl SharedConfig {
+    pub fn new(config: Config) -> Self {
+        SharedConfig {
+            inner: Arc::new(RwLock::new(Arc::new(config))),
+        }
+    }
+
+    pub fn current(&self) -> Arc<Config> {
+        match self.inner.read() {
+            Ok(config) => Arc::clone(&config),
+            Err(poisoned) => Arc::clone(&poisoned.into_inner()),
+        }
+    }
+
+    pub fn replace(&self, config: Config) {
+        let config = Arc::new(config);
+        match self.inner.write() {
+            Ok(mut current) => *current = config,
+            Err(poisoned) => *poisoned.into_inner() = config,
+        }
+    }
+}
+
+/// # ConfigWatcher
+///
+/// Reloads the configuration when the config file, the rules file or an htpasswd file
+/// changes on disk, or when the process receives `SIGHUP`.
+///
+/// A reload re-reads every source, config file, `RSRV_*` variables and flags,
+/// and runs the same validation as startup. An invalid config is logged and
+/// discarded, the previous one stays in effect.
+///
+/// The listening socket is bound once, so changes to `host` or `port` need a restart,
+/// as do changes to which TLS certificate files are served and to the HTTP redirect port.
+///
+pub struct ConfigWatcher;
+
+impl ConfigWatcher {
+    pub fn spawn(shared_config: SharedConfig) {
+        let hangup = Arc::new(AtomicBool::new(false));
+        Self::register_hangup(&hangup);
+
+        thread::spawn(move || {
+            let mut watched_files = Self::modified_times(&shared_config.current());
+
+            loop {
+                thread::sleep(POLL_INTERVAL);
+
+                let reason = if hangup.swap(false, Ordering::Relaxed) {
+                    Some(String::from("received SIGHUP"))
+                } else {
+                    watched_files
+                        .iter()
+                        .find(|(path, modified)| Self::modified_time(path) != *modified)
+                        .map(|(path, _)| format!("{} changed", path))
+                };
+
+                if let Some(reason) = reason {
+                    Self::reload(&shared_config, &reason);
+                    watched_files = Self::modified_times(&shared_config.current());
+                }
+            }
+        });
+    }
+
+    ///
+    /// Loads and validates a fresh `Config`, swapping it in only if it is valid.
+    ///
+    pub fn reload(shared_config: &SharedConfig, reason: &str) -> bool {
+        let previous_config = shared_config.current();
+
+        match Config::load() {
+            Ok(config) => {
+                if config.host != previous_config.host || config.port != previous_config.port {
+                    Logger::warn(&format!(
+                        "Changing the address to {}:{} needs a restart, still listening on {}:{}",
+                        config.host, config.port, previous_config.host, previous_config.port
+                    ));
+                }
+
+                if config.tls.redirect_port != previous_config.tls.redirect_port {
+                    Logger::warn(
+                        "Changing the HTTP redirect port needs a restart, still redirecting on the previous one",
+                    );
+                }
+                if config.http2.enabled != previous_config.http2.enabled {
+                    Logger::warn(
+                        "Turning HTTP/2 on or off needs a restart, still serving the previous protocols",
+                    );
+                }
+                if Tls::certificate_files(&config) != Tls::certificate_files(&previous_config) {
+                    Logger::warn(
+                        "Changing which TLS certificates are served needs a restart, still serving the previous ones",
+                    );
+                }
+
+                Logger::set_level(config.log_level);
+                Logger::info(&format!(
+                    "Reloaded configuration ({}): {} rules, {} header rules, {} mounts, {} virtual hosts",
+                    reason,
+                    config.rules.rules.len(),
+                    config.rules.header_rules.rules.len(),
+                    config.mounts.len(),
+                    config.sites.hosts.len()
+                ));
+                shared_config.replace(config);
+                true
+            }
+            Err(e) => {
+                Logger::error(&format!(
+                    "Rejected configuration reload ({}), keeping the previous configuration. {}",
+                    reason, e
+                ));
+                false
+            }
+        }
+    }
+
+    fn modified_times(config: &Config) -> Vec<(String, Option<SystemTime>)> {
+        let mounts = config
+            .sites
+            .hosts
+            .iter()
+            .chain([&config.sites.default_host])
+            .flat_map(|host| &host.static_directory_manager.mounts);
+        let htpasswd_files = config
+            .basic_auth
+            .htpasswd
+            .iter()
+            .chain(mounts.filter_map(|mount| mount.basic_auth.as_ref()))
+            .map(|htpasswd| &htpasswd.path);
+
+        [&config.config_file, &config.rules_file]
+            .into_iter()
+            .flatten()
+            .chain(htpasswd_files)
+            .map(|path| (path.clone(), Self::modified_time(path)))
+            .collect()
+    }
+
+    fn modified_time(path: &str) -> Option<SystemTime> {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    #[cfg(unix)]
+    fn register_hangup(hangup: &Arc<AtomicBool>) {
+        if let Err(e) = signal_hook::flag::register(signal_hook::consts::SIGHUP, Arc::clone(hangup))
+        {
+            Logger::warn(&format!(
+                "Unable to listen for SIGHUP, reload by editing the config file instead. {}",
+                e
+            ));
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn register_hangup(_hangup: &Arc<AtomicBool>) {}
+}
@@ -1,110 +1,492 @@
+use crate::basic_auth::BasicAuth;
+use crate::client_stream::ClientStream;
+use crate::config::Config;
+use crate::error_page::ErrorPage;
+use crate::file_manager::FileManager;
+use crate::filelike::FileLike;
 use crate::headers::Headers;
-use crate::request::Request;
+use crate::live_reload::{LiveReload, LIVE_RELOAD_PATH};
+use crate::logger::Logger;
+use crate::mock::MockApi;
+use crate::mount::Mount;
+use crate::proxy::ReverseProxy;
+use crate::rate_limit::{ConnectionSlot, RateLimiter};
+use crate::request::{HttpMethod, Request};
 use crate::response::Response;
-use crate::static_directory_manager::StaticDirectoryManager;
-use crate::{filelike::FileLike, logger::Logger};
+use crate::rules::RuleAction;
+use crate::static_directory_manager::{MountLookup, StaticDirectoryManager};
+use crate::upload::Upload;
+use crate::virtual_host::VirtualHost;
+use crate::webdav::WebDav;
+use crate::webdav_locks::WebDavLocks;
 
-use std::{
-    io::{prelude::*, BufReader},
-    net::TcpStream,
-};
+use std::io::{BufReader, Read};
+use std::time::Duration;
+
+const DEFAULT_BUFFER_SIZE: usize = 8 * 1024;
 
 use serde_json;
 
 pub struct ConnectionHandler;
 
 impl ConnectionHandler {
+    ///
+    /// Answers the request on a connection. `head` is what was already read of it,
+    /// the request head the event loop waited for, read before the rest of the stream.
+    /// `slot` counts the connection against its peer until it is answered, or until
+    /// the WebSocket tunnel it becomes closes.
+    ///
     pub fn handle(
-        mut stream: TcpStream,
-        static_directory_manager_instance: StaticDirectoryManager,
+        mut stream: ClientStream,
+        head: Vec<u8>,
+        config: &Config,
+        live_reload: &LiveReload,
+        webdav_locks: &WebDavLocks,
+        rate_limiter: &RateLimiter,
+        slot: Option<ConnectionSlot>,
     ) {
-        let buf_reader = BufReader::new(&mut stream);
+        // Room for the whole head in the first fill, so none of it stays behind the buffer
+        let buf_reader = BufReader::with_capacity(
+            head.len().max(DEFAULT_BUFFER_SIZE),
+            head.as_slice().chain(&mut stream),
+        );
         let request_result = Request::new(buf_reader);
         match request_result {
-            Ok(request) => {
-                let path = request.path();
+            Ok(mut request) => {
+                if let Err(retry_after) =
+                    Self::take_rate_limit(&request, &stream, config, rate_limiter)
+                {
+                    Self::respond_rate_limited(&request, retry_after, config, &mut stream);
+                    return;
+                }
 
-                let file_result = static_directory_manager_instance
-                    .search_for_file_path_in_approved_directories(path);
+                if config.live_reload && request.path() == LIVE_RELOAD_PATH {
+                    if Self::authenticate(&request, config, &mut stream) {
+                        live_reload.subscribe(stream);
+                    }
+                    return;
+                }
 
-                let file = match file_result {
-                    Ok(file_contents) => file_contents,
-                    Err(_) => {
-                        let backup_file_result = static_directory_manager_instance
-                            .search_for_file_path_in_approved_directories(&format!(
-                                "/{}",
-                                static_directory_manager_instance.backup_file.as_str()
-                            ));
-
-                        match backup_file_result {
-                            Ok(file) => file,
-                            Err(_) => {
-                                Self::handle_request_with_error(
-                                    String::from(
-                                        "ConnectionHandler::SimpleException Failed to read requested file.",
-                                    ),
-                                    &mut stream,
-                                );
-                                return;
-                            }
+                match config.rules.evaluate_request(&request) {
+                    RuleAction::Redirect(status, location) => {
+                        Logger::info(&format!(
+                            "Redirecting {} -> {} ({})",
+                            request.path(),
+                            location,
+                            status
+                        ));
+                        Response::redirect(status, &location).respond(&mut stream);
+                        return;
+                    }
+                    RuleAction::Rewrite(path, query) => {
+                        Logger::info(&format!("Rewriting {} -> {}", request.path(), path));
+                        request.set_path(path);
+                        request.set_query(query);
+                    }
+                    RuleAction::Continue => (),
+                }
+
+                // Checked after rewrites, which may land under a mount with its own credentials
+                if !Self::authenticate(&request, config, &mut stream) {
+                    return;
+                }
+
+                if let Some((mock, captures)) = config.find_mock(request.method(), request.path()) {
+                    MockApi::respond(&request, mock, &captures, stream, config);
+                    return;
+                }
+
+                if let Some(proxy_route) = config.find_proxy(request.path()) {
+                    ReverseProxy::forward(&request, proxy_route, &mut stream, config, slot);
+                    return;
+                }
+
+                let virtual_host = config
+                    .sites
+                    .resolve(request.headers().get_header_by_key("Host"));
+                let static_directory_manager_instance = &virtual_host.static_directory_manager;
+
+                if let Some(mount) =
+                    Self::request_mount(&request, static_directory_manager_instance, config)
+                {
+                    if !Self::mount_permits(&request, mount, &stream) {
+                        ErrorPage::respond(
+                            403,
+                            format!(
+                                "Access to {} is not allowed from your address.",
+                                request.path()
+                            ),
+                            Some(&request),
+                            static_directory_manager_instance,
+                            &config.error_pages,
+                            &mut stream,
+                        );
+                        return;
+                    }
+                }
+
+                if config.webdav.enabled {
+                    if WebDav::handles(&request) {
+                        WebDav::handle(
+                            &request,
+                            static_directory_manager_instance,
+                            config,
+                            webdav_locks,
+                            &mut stream,
+                        );
+                        return;
+                    }
+
+                    // Clients fetch files by the percent-encoded hrefs PROPFIND lists
+                    match WebDav::decode_path(request.path(), &config.webdav) {
+                        Ok(path) => request.set_path(path),
+                        Err((status, message)) => {
+                            ErrorPage::respond(
+                                status,
+                                message,
+                                Some(&request),
+                                static_directory_manager_instance,
+                                &config.error_pages,
+                                &mut stream,
+                            );
+                            return;
                         }
                     }
-                };
-
-                let accept_encoding_header =
-                    match request.headers().get_header_by_key("Accept-Encoding") {
-                        Some(header) => header.clone(),
-                        None => String::new(),
-                    };
-
-                let compressed = accept_encoding_header.contains("gzip");
-
-                let response = Response::new(
-                    String::from("HTTP/1.1"),
-                    200,
-                    String::from("OK"),
-                    Headers::construct_outgoing_headers(request, &file, compressed).map,
-                    file,
-                    compressed,
-                );
+                }
+
+                if config.uploads.manage && FileManager::handles(&request) {
+                    FileManager::handle(
+                        &request,
+                        static_directory_manager_instance,
+                        config,
+                        &mut stream,
+                    );
+                    return;
+                }
+
+                if config.uploads.enabled
+                    && matches!(request.method(), HttpMethod::PUT | HttpMethod::POST)
+                {
+                    Upload::handle(
+                        &request,
+                        static_directory_manager_instance,
+                        config,
+                        &mut stream,
+                    );
+                    return;
+                }
 
-                response.respond(&mut stream);
+                if let Some(index_path) =
+                    static_directory_manager_instance.resolve_index_file(request.path())
+                {
+                    request.set_path(index_path);
+                }
+
+                match static_directory_manager_instance.find_mount(request.path()) {
+                    Some(mount) => Self::handle_mounted_request(
+                        request,
+                        mount,
+                        virtual_host,
+                        config,
+                        &mut stream,
+                    ),
+                    None => Self::handle_root_request(request, virtual_host, config, &mut stream),
+                }
             }
-            Err(e) => Self::handle_request_with_error(e, &mut stream),
+            Err(e) => Self::handle_request_with_error(e, config, &mut stream),
         }
     }
 
-    pub fn handle_request_with_error(e: String, stream: &mut TcpStream) {
-        let connection_error = ConnectionError::new(e);
-        let error_body = connection_error.get_error_as_json_string();
-
-        let response_headers = Headers::new(vec![
-            (String::from("Content-Length"), error_body.len().to_string()),
-            (
-                String::from("Content-Type"),
-                String::from(Headers::format_content_type_header_based_on_request_path(
-                    ".json",
-                )),
+    fn handle_root_request(
+        mut request: Request,
+        virtual_host: &VirtualHost,
+        config: &Config,
+        stream: &mut ClientStream,
+    ) {
+        let static_directory_manager_instance = &virtual_host.static_directory_manager;
+        let path = request.path().clone();
+
+        let file_result =
+            static_directory_manager_instance.search_for_file_path_in_approved_directories(&path);
+
+        let file = match file_result {
+            Ok(file_contents) => file_contents,
+            Err(_) => {
+                let backup_file_path = format!(
+                    "/{}",
+                    static_directory_manager_instance.backup_file.as_str()
+                );
+                let backup_file_result = static_directory_manager_instance
+                    .search_for_file_path_in_approved_directories(&backup_file_path);
+
+                match backup_file_result {
+                    Ok(file) => {
+                        request.set_path(backup_file_path);
+                        file
+                    }
+                    Err(_) => {
+                        ErrorPage::respond(
+                            404,
+                            format!("The requested path {} could not be found.", path),
+                            Some(&request),
+                            static_directory_manager_instance,
+                            &config.error_pages,
+                            stream,
+                        );
+                        return;
+                    }
+                }
+            }
+        };
+
+        Self::respond_with_file(request, file, None, virtual_host, config, stream);
+    }
+
+    fn handle_mounted_request(
+        mut request: Request,
+        mount: &Mount,
+        virtual_host: &VirtualHost,
+        config: &Config,
+        stream: &mut ClientStream,
+    ) {
+        let static_directory_manager_instance = &virtual_host.static_directory_manager;
+        let path = request.path().clone();
+
+        match static_directory_manager_instance.search_for_file_path_in_mount(mount, &path) {
+            MountLookup::File(file) => {
+                Self::respond_with_file(request, file, Some(mount), virtual_host, config, stream)
+            }
+            MountLookup::Fallback(file, fallback_path) => {
+                request.set_path(fallback_path);
+                Self::respond_with_file(request, file, Some(mount), virtual_host, config, stream);
+            }
+            MountLookup::Listing(listing) => {
+                request.set_path(String::from("/index.html"));
+                Self::respond_with_file(
+                    request,
+                    FileLike::TextFile(listing),
+                    Some(mount),
+                    virtual_host,
+                    config,
+                    stream,
+                );
+            }
+            MountLookup::Redirect(location) => Response::redirect(301, &location).respond(stream),
+            MountLookup::NotFound => ErrorPage::respond(
+                404,
+                format!("The requested path {} could not be found.", path),
+                Some(&request),
+                static_directory_manager_instance,
+                &config.error_pages,
+                stream,
             ),
-        ]);
+        }
+    }
+
+    fn respond_with_file(
+        request: Request,
+        file: FileLike,
+        mount: Option<&Mount>,
+        virtual_host: &VirtualHost,
+        config: &Config,
+        stream: &mut ClientStream,
+    ) {
+        let accept_encoding_header = match request.headers().get_header_by_key("Accept-Encoding") {
+            Some(header) => header.clone(),
+            None => String::new(),
+        };
+
+        let compressed = config.compression && accept_encoding_header.contains("gzip");
+
+        let file = match file {
+            FileLike::TextFile(html)
+                if config.live_reload
+                    && Headers::format_content_type_header_based_on_request_path(
+                        request.path(),
+                    )
+                    .starts_with("text/html") =>
+            {
+                FileLike::TextFile(LiveReload::inject_client_script(&html))
+            }
+            file => file,
+        };
+
+        let headers = Headers::construct_outgoing_headers(
+            request,
+            &file,
+            compressed,
+            mount,
+            &virtual_host.headers,
+            stream.is_tls(),
+            config,
+        );
 
         let response = Response::new(
             String::from("HTTP/1.1"),
-            500,
-            String::from("SERVER ERROR"),
-            response_headers.map,
-            FileLike::TextFile(error_body),
-            false,
+            200,
+            String::from("OK"),
+            headers.map,
+            file,
+            compressed,
         );
 
-        let stream_write_result = stream.write_all(response.build_as_string().as_bytes());
+        response.respond(stream);
+    }
+
+    ///
+    /// The mount the request falls under. WebDAV requests are matched by their decoded
+    /// path, as that is what they act on.
+    ///
+    fn request_mount<'a>(
+        request: &Request,
+        static_directory_manager: &'a StaticDirectoryManager,
+        config: &Config,
+    ) -> Option<&'a Mount> {
+        let path = match config.webdav.enabled {
+            true => WebDav::decode_path(request.path(), &config.webdav)
+                .unwrap_or_else(|_| request.path().clone()),
+            false => request.path().clone(),
+        };
+        static_directory_manager.find_mount(&path)
+    }
+
+    ///
+    /// Whether the access rules of the mount let the client in.
+    ///
+    fn mount_permits(request: &Request, mount: &Mount, stream: &ClientStream) -> bool {
+        let Ok(peer_addr) = stream.peer_addr() else {
+            return true;
+        };
+
+        let permitted = mount.access.permits(peer_addr.ip());
+        if !permitted {
+            Logger::warn(&format!(
+                "Denied {} {} from {} by the access rules of {}",
+                request.method().as_str(),
+                request.path(),
+                peer_addr,
+                mount.prefix
+            ));
+        }
+        permitted
+    }
+
+    ///
+    /// Checks the request's Basic credentials, answering `401` when they are missing or
+    /// wrong. A mount with its own htpasswd file takes the place of the global one.
+    /// A write carrying the upload token is signed in by the token, as a request has
+    /// room for one set of credentials only.
+    ///
+    fn authenticate(request: &Request, config: &Config, stream: &mut ClientStream) -> bool {
+        let virtual_host = config
+            .sites
+            .resolve(request.headers().get_header_by_key("Host"));
+        let static_directory_manager = &virtual_host.static_directory_manager;
+        let htpasswd = Self::request_mount(request, static_directory_manager, config)
+            .and_then(|mount| mount.basic_auth.as_ref())
+            .or(config.basic_auth.htpasswd.as_ref());
 
-        match stream_write_result {
-            Ok(_) => (),
-            Err(e) => {
-                Logger::error(&format!("{:?}", e));
+        let authenticated = match htpasswd {
+            Some(htpasswd) => {
+                config.basic_auth.is_excluded(request.path())
+                    || BasicAuth::authenticates(request, htpasswd)
+                    || Self::is_token_authorized_write(request, config)
             }
+            None => true,
+        };
+        if !authenticated {
+            Self::respond_unauthenticated(request, static_directory_manager, config, stream);
         }
+        authenticated
+    }
+
+    ///
+    /// Whether the request is for a write handler, which checks the upload token itself,
+    /// and carries that token.
+    ///
+    fn is_token_authorized_write(request: &Request, config: &Config) -> bool {
+        let writes = (config.webdav.enabled && WebDav::handles(request))
+            || (config.uploads.manage && FileManager::handles(request))
+            || (config.uploads.enabled
+                && matches!(request.method(), HttpMethod::PUT | HttpMethod::POST));
+        writes && config.uploads.authorizes(request)
+    }
+
+    fn respond_unauthenticated(
+        request: &Request,
+        static_directory_manager: &StaticDirectoryManager,
+        config: &Config,
+        stream: &mut ClientStream,
+    ) {
+        Logger::warn(&format!(
+            "Refusing unauthenticated {} {}",
+            request.method().as_str(),
+            request.path()
+        ));
+        ErrorPage::respond_with_headers(
+            401,
+            String::from("Sign in to see this page."),
+            &[("WWW-Authenticate", &config.basic_auth.challenge())],
+            Some(request),
+            static_directory_manager,
+            &config.error_pages,
+            stream,
+        );
+    }
+
+    ///
+    /// Takes a request from the client's rate limit, or returns how long until it may retry.
+    ///
+    fn take_rate_limit(
+        request: &Request,
+        stream: &ClientStream,
+        config: &Config,
+        rate_limiter: &RateLimiter,
+    ) -> Result<(), Duration> {
+        let Ok(peer_addr) = stream.peer_addr() else {
+            return Ok(());
+        };
+        let client_ip = config.rate_limit.client_ip(
+            peer_addr.ip(),
+            request.headers().get_header_by_key("X-Forwarded-For"),
+        );
+        rate_limiter.take(client_ip, &config.rate_limit)
+    }
+
+    fn respond_rate_limited(
+        request: &Request,
+        retry_after: Duration,
+        config: &Config,
+        stream: &mut ClientStream,
+    ) {
+        // Whole seconds, rounded up so the retry lands after the bucket refills
+        let retry_after = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+        Logger::warn(&format!(
+            "Rate limited {} {}, retry after {}s",
+            request.method().as_str(),
+            request.path(),
+            retry_after
+        ));
+        ErrorPage::respond_with_headers(
+            429,
+            String::from("Too many requests, slow down and retry later."),
+            &[("Retry-After", &retry_after.to_string())],
+            Some(request),
+            &config.sites.default_host.static_directory_manager,
+            &config.error_pages,
+            stream,
+        );
+    }
+
+    pub fn handle_request_with_error(e: String, config: &Config, stream: &mut ClientStream) {
+        ErrorPage::respond(
+            500,
+            e,
+            None,
+            &config.sites.default_host.static_directory_manager,
+            &config.error_pages,
+            stream,
+        );
     }
 }
 
@@ -0,0 +1,279 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use chrono::{Datelike, Utc};
+use rcgen::{
+    date_time_ymd, BasicConstraints, CertificateParams, DistinguishedName, DnType,
+    ExtendedKeyUsagePurpose, IsCa, Issuer, KeyPair, KeyUsagePurpose,
+};
+
+use crate::logger::Logger;
+use crate::tls::CertificateFiles;
+
+const CA_COMMON_NAME: &str = "rsrv development CA";
+const CA_VALIDITY_DAYS: i64 = 10 * 365;
+/// Browsers refuse leaf certificates valid for longer than 398 days
+const LEAF_VALIDITY_DAYS: i64 = 397;
+const LEAF_RENEWAL_AGE: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+const LEAF_NAMES: [&str; 3] = ["localhost", "127.0.0.1", "::1"];
+
+/// # DevCertificate
+///
+/// The certificate `--https` serves when no `--tls-cert` is supplied, signed by a
+/// local certificate authority rsrv creates on first use.
+///
+/// ```sh
+/// $ rsrv --https --dir=public
+/// ```
+///
+/// Both are kept in the user's config directory, i.e. `~/.config/rsrv` on Linux,
+/// so the CA only needs to be trusted once. The leaf certificate covers `localhost`,
+/// `127.0.0.1` and `::1`, and is renewed with the same CA once it is a year old.
+/// Deleting the directory starts over with a fresh CA.
+///
+pub struct DevCertificate;
+
+impl DevCertificate {
+    pub fn directory() -> Option<PathBuf> {
+        dirs::config_dir().map(|config_dir| config_dir.join("rsrv"))
+    }
+
+    ///
+    /// The leaf certificate and key, whether or not they were generated yet.
+    ///
+    pub fn files() -> Option<CertificateFiles> {
+        let directory = Self::directory()?;
+        Some(CertificateFiles {
+            hostname: None,
+            cert: directory
+                .join("localhost.pem")
+                .to_string_lossy()
+                .to_string(),
+            key: directory
+                .join("localhost-key.pem")
+                .to_string_lossy()
+                .to_string(),
+        })
+    }
+
+    ///
+    /// Creates the CA and leaf certificate if they are missing, and renews the
+    /// leaf when it is due.
+    ///
+    pub fn ensure() -> Result<CertificateFiles, String> {
+        let (directory, files) = match (Self::directory(), Self::files()) {
+            (Some(directory), Some(files)) => (directory, files),
+            _ => {
+                return Err(String::from(
+                    "DevCertificate::ensure() Exception: No user config directory to keep the development certificate in. Supply --tls-cert=<file> and --tls-key=<file> instead.",
+                ))
+            }
+        };
+        let ca_cert = directory.join("rsrv-ca.pem");
+        let ca_key = directory.join("rsrv-ca-key.pem");
+
+        fs::create_dir_all(&directory).map_err(|e| {
+            format!(
+                "DevCertificate::ensure() Exception: Unable to create {}. {}",
+                directory.display(),
+                e
+            )
+        })?;
+
+        let created_ca = !ca_cert.is_file() || !ca_key.is_file();
+        if created_ca {
+            Self::create_ca(&ca_cert, &ca_key)?;
+        }
+
+        let leaf_modified = Self::modified_time(Path::new(&files.cert));
+        let leaf_is_due = match leaf_modified {
+            Some(modified) => {
+                !Path::new(&files.key).is_file()
+                    || modified.elapsed().unwrap_or_default() >= LEAF_RENEWAL_AGE
+                    || Self::modified_time(&ca_cert) > Some(modified)
+            }
+            None => true,
+        };
+        if leaf_is_due {
+            Self::create_leaf(&ca_key, &files)?;
+            Logger::info(&format!(
+                "Created a development certificate for {} at {}",
+                LEAF_NAMES.join(", "),
+                files.cert
+            ));
+        }
+
+        if created_ca {
+            Self::echo_trust_instructions(&ca_cert);
+        } else {
+            Logger::info(&format!(
+                "Development certificate signed by {}, trust it once to avoid browser warnings",
+                ca_cert.display()
+            ));
+        }
+
+        Ok(files)
+    }
+
+    fn create_ca(ca_cert: &Path, ca_key: &Path) -> Result<(), String> {
+        let key_pair = KeyPair::generate().map_err(Self::error)?;
+        let certificate = Self::ca_params()
+            .self_signed(&key_pair)
+            .map_err(Self::error)?;
+
+        Self::write(ca_key, &key_pair.serialize_pem(), true)?;
+        Self::write(ca_cert, &certificate.pem(), false)?;
+        Logger::info(&format!(
+            "Created the certificate authority {} at {}",
+            CA_COMMON_NAME,
+            ca_cert.display()
+        ));
+        Ok(())
+    }
+
+    fn create_leaf(ca_key: &Path, files: &CertificateFiles) -> Result<(), String> {
+        let ca_key_pem = fs::read_to_string(ca_key).map_err(|e| {
+            format!(
+                "DevCertificate::create_leaf() Exception: Unable to read {}. {}",
+                ca_key.display(),
+                e
+            )
+        })?;
+        let ca_key_pair = KeyPair::from_pem(&ca_key_pem).map_err(Self::error)?;
+        // Rebuilt rather than parsed, a certificate is signed with its issuer's name and key
+        let issuer = Issuer::new(Self::ca_params(), ca_key_pair);
+
+        let mut params = CertificateParams::new(
+            LEAF_NAMES
+                .iter()
+                .map(|name| String::from(*name))
+                .collect::<Vec<String>>(),
+        )
+        .map_err(Self::error)?;
+        params
+            .distinguished_name
+            .push(DnType::CommonName, LEAF_NAMES[0]);
+        params.key_usages = vec![
+            KeyUsagePurpose::DigitalSignature,
+            KeyUsagePurpose::KeyEncipherment,
+        ];
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+        params.use_authority_key_identifier_extension = true;
+        Self::set_validity(&mut params, LEAF_VALIDITY_DAYS);
+
+        let key_pair = KeyPair::generate().map_err(Self::error)?;
+        let certificate = params.signed_by(&key_pair, &issuer).map_err(Self::error)?;
+
+        Self::write(Path::new(&files.key), &key_pair.serialize_pem(), true)?;
+        Self::write(Path::new(&files.cert), &certificate.pem(), false)
+    }
+
+    fn ca_params() -> CertificateParams {
+        let mut distinguished_name = DistinguishedName::new();
+        distinguished_name.push(DnType::CommonName, CA_COMMON_NAME);
+        distinguished_name.push(DnType::OrganizationName, "rsrv");
+
+        let mut params = CertificateParams::default();
+        params.distinguished_name = distinguished_name;
+        params.is_ca = IsCa::Ca(BasicConstraints::Constrained(0));
+        params.key_usages = vec![
+            KeyUsagePurpose::KeyCertSign,
+            KeyUsagePurpose::CrlSign,
+            KeyUsagePurpose::DigitalSignature,
+        ];
+        Self::set_validity(&mut params, CA_VALIDITY_DAYS);
+        params
+    }
+
+    fn echo_trust_instructions(ca_cert: &Path) {
+        let ca_cert = ca_cert.display();
+        Logger::info(
+            "Trust the certificate authority once, so browsers accept the development certificate:",
+        );
+        Logger::info(&format!(
+            "  macOS:   sudo security add-trusted-cert -d -r trustRoot -k /Library/Keychains/System.keychain \"{}\"",
+            ca_cert
+        ));
+        Logger::info(&format!(
+            "  Debian:  sudo cp \"{}\" /usr/local/share/ca-certificates/rsrv-ca.crt && sudo update-ca-certificates",
+            ca_cert
+        ));
+        Logger::info(&format!(
+            "  Fedora:  sudo cp \"{}\" /etc/pki/ca-trust/source/anchors/rsrv-ca.pem && sudo update-ca-trust",
+            ca_cert
+        ));
+        Logger::info(&format!(
+            "  Windows: certutil -user -addstore Root \"{}\"",
+            ca_cert
+        ));
+        Logger::info(&format!(
+            "  Firefox: Settings > Privacy & Security > View Certificates > Authorities > Import \"{}\"",
+            ca_cert
+        ));
+        Logger::info(&format!(
+            "  Node.js: NODE_EXTRA_CA_CERTS=\"{}\", curl: --cacert \"{}\"",
+            ca_cert, ca_cert
+        ));
+    }
+
+    ///
+    /// Valid from yesterday, to allow for clock skew, for `days` from today.
+    ///
+    fn set_validity(params: &mut CertificateParams, days: i64) {
+        let not_before = (Utc::now() - chrono::Duration::days(1)).date_naive();
+        let not_after = (Utc::now() + chrono::Duration::days(days)).date_naive();
+
+        params.not_before = date_time_ymd(
+            not_before.year(),
+            not_before.month() as u8,
+            not_before.day() as u8,
+        );
+        params.not_after = date_time_ymd(
+            not_after.year(),
+            not_after.month() as u8,
+            not_after.day() as u8,
+        );
+    }
+
+    fn modified_time(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    ///
+    /// Writes a file, readable only by the user when it holds a `private` key.
+    ///
+    fn write(path: &Path, contents: &str, private: bool) -> Result<(), String> {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        if private {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        #[cfg(not(unix))]
+        let _ = private;
+
+        let written = options
+            .open(path)
+            .and_then(|mut file| file.write_all(contents.as_bytes()));
+
+        written.map_err(|e| {
+            format!(
+                "DevCertificate::write() Exception: Unable to write {}. {}",
+                path.display(),
+                e
+            )
+        })
+    }
+
+    fn error(e: rcgen::Error) -> String {
+        format!(
+            "DevCertificate::ensure() Exception: Unable to generate the development certificate. {}",
+            e
+        )
+    }
+}
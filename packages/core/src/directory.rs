@@ -34,6 +34,28 @@ impl Directory {
     }
 }
 
+impl Directory {
+    ///
+    /// Resolves a directory argument against the current working directory,
+    /// leaving absolute paths untouched and stripping trailing slashes.
+    ///
+    pub fn get_absolute_path(directory: &str) -> String {
+        let directory = directory.trim_end_matches('/');
+        if directory.starts_with('/') {
+            return String::from(directory);
+        }
+
+        match env::current_dir() {
+            Ok(current_dir) => format!(
+                "{}/{}",
+                current_dir.to_string_lossy(),
+                directory.trim_start_matches("./")
+            ),
+            Err(_) => String::from(directory),
+        }
+    }
+}
+
 impl Directory {
     pub fn ensure_directory_integrity(directories: &Vec<String>) -> bool {
         /* Set up a stateful variable to be returned */
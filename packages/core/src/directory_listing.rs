@@ -0,0 +1,88 @@
+use std::error::Error;
+use std::fs;
+
+use crate::error_page::ErrorPage;
+
+const DIRECTORY_LISTING_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+  <head>
+    <meta charset="utf-8" />
+    <meta name="viewport" content="width=device-width, initial-scale=1" />
+    <title>Index of {path}</title>
+    <style>
+      body {
+        margin: 0 auto;
+        max-width: 720px;
+        padding: 24px;
+        font-family: system-ui, -apple-system, sans-serif;
+        background: #1b1b1f;
+        color: #e4e4e7;
+      }
+      a {
+        color: #80acf8;
+        text-decoration: none;
+      }
+      li {
+        padding: 4px 0;
+        list-style: none;
+      }
+    </style>
+  </head>
+  <body>
+    <h1>Index of {path}</h1>
+    <ul>
+{entries}
+    </ul>
+  </body>
+</html>
+"#;
+
+/// # DirectoryListing
+///
+/// Renders an html index of the entries in a directory.
+///
+pub struct DirectoryListing;
+
+impl DirectoryListing {
+    ///
+    /// `url_path` is the request path of the directory and must end with a `/`,
+    /// `absolute_directory` is where that directory lives on the local fs.
+    ///
+    pub fn render(url_path: &str, absolute_directory: &str) -> Result<String, Box<dyn Error>> {
+        let mut entries: Vec<(bool, String)> = vec![];
+        for entry in fs::read_dir(absolute_directory)? {
+            let entry = entry?;
+            let is_directory = entry.file_type()?.is_dir();
+            let name = entry.file_name().to_string_lossy().to_string();
+            entries.push((is_directory, name));
+        }
+
+        // Directories first, then files, each alphabetically
+        entries.sort_by(|(a_is_dir, a_name), (b_is_dir, b_name)| {
+            b_is_dir.cmp(a_is_dir).then_with(|| a_name.cmp(b_name))
+        });
+
+        let mut entries_as_html: Vec<String> = vec![];
+        if url_path != "/" {
+            entries_as_html.push(String::from("      <li><a href=\"../\">../</a></li>"));
+        }
+        for (is_directory, name) in entries {
+            let display_name = if is_directory {
+                format!("{}/", name)
+            } else {
+                name
+            };
+            let escaped_name = ErrorPage::escape_html(&display_name);
+            entries_as_html.push(format!(
+                "      <li><a href=\"{}{}\">{}</a></li>",
+                ErrorPage::escape_html(url_path),
+                escaped_name,
+                escaped_name
+            ));
+        }
+
+        Ok(DIRECTORY_LISTING_TEMPLATE
+            .replace("{path}", &ErrorPage::escape_html(url_path))
+            .replace("{entries}", &entries_as_html.join("\n")))
+    }
+}
@@ -0,0 +1,240 @@
+use crate::client_stream::ClientStream;
+use crate::connection::ConnectionError;
+use crate::filelike::FileLike;
+use crate::headers::Headers;
+use crate::request::Request;
+use crate::response::Response;
+use crate::static_directory_manager::StaticDirectoryManager;
+use crate::status::StatusCode;
+
+const DEFAULT_ERROR_PAGE_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+  <head>
+    <meta charset="utf-8" />
+    <meta name="viewport" content="width=device-width, initial-scale=1" />
+    <title>{status} {reason}</title>
+    <style>
+      body {
+        margin: 0;
+        min-height: 100vh;
+        display: flex;
+        align-items: center;
+        justify-content: center;
+        font-family: system-ui, -apple-system, sans-serif;
+        background: #1b1b1f;
+        color: #e4e4e7;
+      }
+      main {
+        text-align: center;
+        padding: 24px;
+      }
+      h1 {
+        margin: 0;
+        font-size: 72px;
+        color: #f74c00;
+      }
+      h2 {
+        margin: 8px 0 16px;
+        font-weight: 500;
+      }
+      p {
+        color: #a1a1aa;
+      }
+      footer {
+        margin-top: 32px;
+        font-size: 12px;
+        color: #71717a;
+      }
+    </style>
+  </head>
+  <body>
+    <main>
+      <h1>{status}</h1>
+      <h2>{reason}</h2>
+      <p>{message}</p>
+      <footer>rsrv</footer>
+    </main>
+  </body>
+</html>
+"#;
+
+/// # ErrorPage
+///
+/// A functional struct that builds and writes error responses.
+///
+/// Error bodies are negotiated against the request's `Accept` header.
+/// Clients that prefer `application/json` receive the `ConnectionError` json blob,
+/// everyone else receives an html page. The html page is resolved, in order, from
+/// the configured error pages (`--error-page=<status>:<path>`), a `<status>.html` file in the served
+/// directories, and finally the built-in default page.
+///
+pub struct ErrorPage;
+
+impl ErrorPage {
+    pub fn respond(
+        status: u16,
+        message: String,
+        request: Option<&Request>,
+        static_directory_manager: &StaticDirectoryManager,
+        error_pages: &[(u16, String)],
+        stream: &mut ClientStream,
+    ) {
+        Self::respond_with_headers(
+            status,
+            message,
+            &[],
+            request,
+            static_directory_manager,
+            error_pages,
+            stream,
+        );
+    }
+
+    ///
+    /// Responds like `respond`, adding headers the status calls for, i.e. `WWW-Authenticate` on a `401`.
+    ///
+    pub fn respond_with_headers(
+        status: u16,
+        message: String,
+        headers: &[(&str, &str)],
+        request: Option<&Request>,
+        static_directory_manager: &StaticDirectoryManager,
+        error_pages: &[(u16, String)],
+        stream: &mut ClientStream,
+    ) {
+        let accept_header =
+            request.and_then(|request| request.headers().get_header_by_key("Accept"));
+
+        let mut response = if Self::prefers_json(accept_header) {
+            Self::build_json_response(status, message)
+        } else {
+            Self::build_html_response(status, message, static_directory_manager, error_pages)
+        };
+        for (key, value) in headers {
+            response.insert_header(key, value);
+        }
+
+        response.respond(stream);
+    }
+}
+
+impl ErrorPage {
+    ///
+    /// Decides whether the client would rather receive json than html.
+    ///
+    /// Requests without an `Accept` header keep the historical json behavior.
+    /// Otherwise the quality values of `application/json` and `text/html` are compared,
+    /// with wildcards counting towards html so that browsers sending `*/*` get a page.
+    ///
+    pub fn prefers_json(accept_header: Option<&String>) -> bool {
+        let accept_header = match accept_header {
+            Some(header) => header,
+            None => return true,
+        };
+
+        let mut json_quality = 0f32;
+        let mut html_quality = 0f32;
+
+        for media_range in accept_header.split(',') {
+            let mut parameters = media_range.split(';').map(|part| part.trim());
+            let media_type = parameters.next().unwrap_or_default().to_lowercase();
+            let quality = parameters
+                .find_map(|parameter| parameter.strip_prefix("q="))
+                .and_then(|quality| quality.parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            match media_type.as_str() {
+                "application/json" | "application/*" => json_quality = json_quality.max(quality),
+                "text/html" | "text/*" | "*/*" => html_quality = html_quality.max(quality),
+                _ => (),
+            }
+        }
+
+        json_quality > html_quality
+    }
+
+    pub fn find_custom_error_page(
+        status: u16,
+        static_directory_manager: &StaticDirectoryManager,
+        error_pages: &[(u16, String)],
+    ) -> Option<FileLike> {
+        let configured_page = error_pages
+            .iter()
+            .find(|(configured_status, _)| *configured_status == status)
+            .map(|(_, path)| path);
+
+        let page_path = match configured_page {
+            Some(path) => format!("/{}", path.trim_start_matches('/')),
+            None => format!("/{}.html", status),
+        };
+
+        static_directory_manager.find_optional_file(&page_path)
+    }
+
+    pub fn render_default_error_page(status: u16, message: &str) -> String {
+        DEFAULT_ERROR_PAGE_TEMPLATE
+            .replace("{status}", &status.to_string())
+            .replace("{reason}", StatusCode::reason_phrase(status))
+            .replace("{message}", &Self::escape_html(message))
+    }
+
+    pub fn escape_html(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+            .replace('\'', "&#39;")
+    }
+}
+
+impl ErrorPage {
+    fn build_json_response(status: u16, message: String) -> Response {
+        let error_body = ConnectionError::new(message).get_error_as_json_string();
+        let response_headers = Headers::new(vec![
+            (String::from("Content-Length"), error_body.len().to_string()),
+            (
+                String::from("Content-Type"),
+                Headers::format_content_type_header_based_on_request_path(".json"),
+            ),
+        ]);
+
+        Response::new(
+            String::from("HTTP/1.1"),
+            status,
+            String::from(StatusCode::reason_phrase(status)),
+            response_headers.map,
+            FileLike::TextFile(error_body),
+            false,
+        )
+    }
+
+    fn build_html_response(
+        status: u16,
+        message: String,
+        static_directory_manager: &StaticDirectoryManager,
+        error_pages: &[(u16, String)],
+    ) -> Response {
+        let page = match Self::find_custom_error_page(status, static_directory_manager, error_pages)
+        {
+            Some(page) => page,
+            None => FileLike::TextFile(Self::render_default_error_page(status, &message)),
+        };
+
+        let response_headers = Headers::new(vec![
+            (String::from("Content-Length"), page.len().to_string()),
+            (
+                String::from("Content-Type"),
+                Headers::format_content_type_header_based_on_request_path(".html"),
+            ),
+        ]);
+
+        Response::new(
+            String::from("HTTP/1.1"),
+            status,
+            String::from(StatusCode::reason_phrase(status)),
+            response_headers.map,
+            page,
+            false,
+        )
+    }
+}
@@ -0,0 +1,437 @@
+use std::future::Future;
+use std::io;
+use std::net::{SocketAddr, TcpListener};
+use std::process;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use rustls::{ServerConfig, ServerConnection};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::runtime::Runtime;
+use tokio::time::Instant;
+
+use crate::async_tls::AsyncTlsStream;
+use crate::client_stream::ClientStream;
+use crate::config::Config;
+use crate::config_watcher::SharedConfig;
+use crate::connection::ConnectionHandler;
+use crate::http2::Http2;
+use crate::live_reload::LiveReload;
+use crate::logger::Logger;
+use crate::rate_limit::{ConnectionSlot, RateLimiter};
+use crate::response::Response;
+use crate::timeouts::{TimeoutSettings, Timeouts};
+use crate::webdav_locks::WebDavLocks;
+
+/// A request head that has not ended by now is refused with `431`
+pub const MAX_HEAD_SIZE: usize = 64 * 1024;
+
+const READ_SIZE: usize = 8 * 1024;
+/// Pause after a failed accept, i.e. when out of file descriptors, rather than spinning
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(50);
+/// Seconds a peer over `max_connections_per_ip` is told to wait, in `Retry-After`
+const CONNECTION_RETRY_AFTER: u64 = 1;
+
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+/// # EventLoop
+///
+/// The connection layer, an epoll based tokio runtime that accepts connections and
+/// waits on them without holding a thread each.
+///
+/// A connection costs a thread only while its request is being answered. The TLS
+/// handshake and the request head are read on the event loop, then the request is
+/// handed to `ConnectionHandler` on tokio's blocking pool, where handlers read the
+/// body and write the response with blocking io as before. Idle clients, and ones
+/// that trickle in their headers, wait on the event loop instead, until they run
+/// out of time, see `TimeoutSettings`.
+///
+/// HTTP/2 connections stay on the event loop for their lifetime, see `Http2`.
+///
+pub struct EventLoop;
+
+/// How reading a request head ended
+enum HeadRead {
+    Complete(Vec<u8>),
+    /// The client left, or never started a request within the idle timeout
+    Closed,
+    Rejected(u16, &'static str),
+}
+
+/// What every connection is served with
+#[derive(Clone)]
+struct ConnectionContext {
+    shared_config: SharedConfig,
+    live_reload: LiveReload,
+    webdav_locks: WebDavLocks,
+    rate_limiter: RateLimiter,
+    tls_config: Option<Arc<ServerConfig>>,
+    http2_enabled: bool,
+}
+
+impl EventLoop {
+    pub fn runtime() -> &'static Runtime {
+        RUNTIME.get_or_init(|| {
+            tokio::runtime::Builder::new_multi_thread()
+                .thread_name("rsrv-event-loop")
+                .enable_all()
+                .build()
+                .unwrap_or_else(|e| {
+                    Logger::fatal(&format!(
+                        "EventLoop::runtime() Exception: Unable to start the event loop. {}",
+                        e
+                    ));
+                    process::exit(1);
+                })
+        })
+    }
+
+    ///
+    /// Accepts connections on the listener until the process exits.
+    ///
+    pub fn listen(
+        server: TcpListener,
+        shared_config: SharedConfig,
+        live_reload: LiveReload,
+        webdav_locks: WebDavLocks,
+        rate_limiter: RateLimiter,
+        tls_config: Option<Arc<ServerConfig>>,
+    ) {
+        let context = ConnectionContext {
+            // Fixed at startup, like the protocols TLS offers over ALPN
+            http2_enabled: shared_config.current().http2.enabled,
+            shared_config,
+            live_reload,
+            webdav_locks,
+            rate_limiter,
+            tls_config,
+        };
+
+        Self::runtime().block_on(async move {
+            let listener = match server
+                .set_nonblocking(true)
+                .and_then(|_| tokio::net::TcpListener::from_std(server))
+            {
+                Ok(listener) => listener,
+                Err(e) => {
+                    Logger::fatal(&format!(
+                        "EventLoop::listen() Exception: Unable to listen on the event loop. {}",
+                        e
+                    ));
+                    process::exit(1);
+                }
+            };
+
+            loop {
+                match listener.accept().await {
+                    Ok((stream, peer_addr)) => {
+                        let config = context.shared_config.current();
+                        if !config.access.permits(peer_addr.ip()) {
+                            Logger::warn(&format!(
+                                "Denied the connection from {} by the access rules",
+                                peer_addr
+                            ));
+                            continue;
+                        }
+
+                        let slot = context
+                            .rate_limiter
+                            .open_connection(peer_addr.ip(), &config.rate_limit);
+                        tokio::spawn(Self::serve(stream, peer_addr, slot, context.clone()));
+                    }
+                    Err(e) => {
+                        Logger::error(&format!("Stream Corrupted: {:#?}", e));
+                        tokio::time::sleep(ACCEPT_BACKOFF).await;
+                    }
+                }
+            }
+        });
+    }
+
+    ///
+    /// Serves a connection, `slot` being `None` when its peer is over
+    /// `max_connections_per_ip`, answered with `429` once it can be.
+    ///
+    async fn serve(
+        stream: TcpStream,
+        peer_addr: SocketAddr,
+        slot: Option<ConnectionSlot>,
+        context: ConnectionContext,
+    ) {
+        let config = context.shared_config.current();
+        let served = match &context.tls_config {
+            Some(tls_config) => {
+                Self::serve_tls(
+                    stream,
+                    peer_addr,
+                    slot,
+                    Arc::clone(tls_config),
+                    config,
+                    &context,
+                )
+                .await
+            }
+            None => Self::serve_plain(stream, peer_addr, slot, config, &context).await,
+        };
+
+        if let Err(e) = served {
+            Logger::warn(&format!(
+                "EventLoop::serve() Exception: Dropped the connection from {}. {}",
+                peer_addr, e
+            ));
+        }
+    }
+
+    async fn serve_plain(
+        mut stream: TcpStream,
+        peer_addr: SocketAddr,
+        slot: Option<ConnectionSlot>,
+        config: Arc<Config>,
+        context: &ConnectionContext,
+    ) -> io::Result<()> {
+        let Some(slot) = slot else {
+            Self::reject_connection(&mut stream, peer_addr, &config).await;
+            return Ok(());
+        };
+        let idle_deadline = Self::deadline(config.timeouts.idle);
+
+        if context.http2_enabled {
+            match Self::within(idle_deadline, Http2::has_preface(&stream)).await {
+                Some(true) => {
+                    Http2::serve(
+                        stream,
+                        false,
+                        peer_addr,
+                        context.shared_config.clone(),
+                        context.live_reload.clone(),
+                        context.webdav_locks.clone(),
+                        context.rate_limiter.clone(),
+                    )
+                    .await;
+                    return Ok(());
+                }
+                Some(false) => (),
+                None => return Ok(()),
+            }
+        }
+
+        let head = match Self::read_head(&mut stream, idle_deadline, &config.timeouts).await? {
+            HeadRead::Complete(head) => head,
+            HeadRead::Closed => return Ok(()),
+            HeadRead::Rejected(status, reason) => {
+                let response = Response::empty(status);
+                Self::reject(&mut stream, response, reason, peer_addr, &config.timeouts).await;
+                return Ok(());
+            }
+        };
+
+        let stream = ClientStream::Plain(Self::into_blocking(stream)?);
+        Self::handle(stream, head, slot, config, context)
+    }
+
+    async fn serve_tls(
+        stream: TcpStream,
+        peer_addr: SocketAddr,
+        slot: Option<ConnectionSlot>,
+        tls_config: Arc<ServerConfig>,
+        config: Arc<Config>,
+        context: &ConnectionContext,
+    ) -> io::Result<()> {
+        let idle_deadline = Self::deadline(config.timeouts.idle);
+        let connection = ServerConnection::new(tls_config).map_err(io::Error::other)?;
+        let mut stream = AsyncTlsStream::new(connection, stream);
+
+        match Self::within(idle_deadline, stream.handshake()).await {
+            Some(Ok(())) => (),
+            Some(Err(e)) => {
+                Logger::error(&format!("Unable to start a TLS session: {}", e));
+                return Ok(());
+            }
+            None => return Ok(()),
+        }
+
+        let Some(slot) = slot else {
+            Self::reject_connection(&mut stream, peer_addr, &config).await;
+            return Ok(());
+        };
+
+        if stream.alpn_protocol() == Some(b"h2") {
+            Http2::serve(
+                stream,
+                true,
+                peer_addr,
+                context.shared_config.clone(),
+                context.live_reload.clone(),
+                context.webdav_locks.clone(),
+                context.rate_limiter.clone(),
+            )
+            .await;
+            return Ok(());
+        }
+
+        let head = match Self::read_head(&mut stream, idle_deadline, &config.timeouts).await? {
+            HeadRead::Complete(head) => head,
+            HeadRead::Closed => return Ok(()),
+            HeadRead::Rejected(status, reason) => {
+                let response = Response::empty(status);
+                Self::reject(&mut stream, response, reason, peer_addr, &config.timeouts).await;
+                return Ok(());
+            }
+        };
+
+        let (connection, stream) = stream.into_parts();
+        let stream = ClientStream::from_tls(connection, Self::into_blocking(stream)?);
+        Self::handle(stream, head, slot, config, context)
+    }
+
+    ///
+    /// Answers the request on the blocking pool, the connection closing once it is done.
+    /// From here on reads and writes are bounded by the body and write timeouts.
+    ///
+    fn handle(
+        stream: ClientStream,
+        head: Vec<u8>,
+        slot: ConnectionSlot,
+        config: Arc<Config>,
+        context: &ConnectionContext,
+    ) -> io::Result<()> {
+        stream.set_read_timeout(config.timeouts.body_read)?;
+        stream.set_write_timeout(config.timeouts.write)?;
+        let context = context.clone();
+
+        tokio::task::spawn_blocking(move || {
+            ConnectionHandler::handle(
+                stream,
+                head,
+                &config,
+                &context.live_reload,
+                &context.webdav_locks,
+                &context.rate_limiter,
+                Some(slot),
+            );
+        });
+        Ok(())
+    }
+
+    ///
+    /// Reads until the blank line that ends the request head. The request has until
+    /// `idle_deadline` to start, then `header_read` to finish, at no less than `min_rate`.
+    /// Anything read past the head starts the body.
+    ///
+    async fn read_head<T: AsyncRead + Unpin>(
+        stream: &mut T,
+        idle_deadline: Option<Instant>,
+        timeouts: &TimeoutSettings,
+    ) -> io::Result<HeadRead> {
+        let mut head = Vec::with_capacity(READ_SIZE);
+        let mut chunk = vec![0u8; READ_SIZE];
+        let mut deadline = idle_deadline;
+        let mut started = std::time::Instant::now();
+
+        loop {
+            let read = match Self::within(deadline, stream.read(&mut chunk)).await {
+                Some(read) => read?,
+                None if head.is_empty() => return Ok(HeadRead::Closed),
+                None => return Ok(HeadRead::Rejected(408, "the request head took too long")),
+            };
+            if read == 0 {
+                return Ok(match head.is_empty() {
+                    true => HeadRead::Closed,
+                    false => HeadRead::Complete(head),
+                });
+            }
+
+            if head.is_empty() {
+                deadline = Self::deadline(timeouts.header_read);
+                started = std::time::Instant::now();
+            }
+            // A blank line may straddle the previous read
+            let searched_from = head.len().saturating_sub(2);
+            head.extend_from_slice(&chunk[..read]);
+
+            if Self::has_blank_line(&head[searched_from..]) {
+                return Ok(HeadRead::Complete(head));
+            }
+            if head.len() >= MAX_HEAD_SIZE {
+                return Ok(HeadRead::Rejected(
+                    431,
+                    "the request head is larger than 64 KiB",
+                ));
+            }
+            if Timeouts::below_min_rate(head.len() as u64, started, timeouts.min_rate) {
+                return Ok(HeadRead::Rejected(
+                    408,
+                    "the request head arrived slower than the minimum rate",
+                ));
+            }
+        }
+    }
+
+    ///
+    /// Answers a peer over `max_connections_per_ip` without reading its request.
+    ///
+    async fn reject_connection<T: AsyncWrite + Unpin>(
+        stream: &mut T,
+        peer_addr: SocketAddr,
+        config: &Config,
+    ) {
+        let mut response = Response::empty(429);
+        response.insert_header("Retry-After", &CONNECTION_RETRY_AFTER.to_string());
+        let reason = "it has too many connections open";
+        Self::reject(stream, response, reason, peer_addr, &config.timeouts).await;
+    }
+
+    ///
+    /// Answers a request the event loop refused to read, then closes the connection.
+    ///
+    async fn reject<T: AsyncWrite + Unpin>(
+        stream: &mut T,
+        response: Response,
+        reason: &str,
+        peer_addr: SocketAddr,
+        timeouts: &TimeoutSettings,
+    ) {
+        Logger::warn(&format!(
+            "Rejected the request from {} ({}), {}",
+            peer_addr,
+            response.status(),
+            reason
+        ));
+
+        let response = response.build_as_string();
+        let written = async {
+            stream.write_all(response.as_bytes()).await?;
+            stream.shutdown().await
+        };
+        let _ = Self::within(Self::deadline(timeouts.write), written).await;
+    }
+
+    ///
+    /// Whether the bytes hold an empty line, ending in `\n` like `Request::new` reads them.
+    ///
+    fn has_blank_line(bytes: &[u8]) -> bool {
+        bytes.windows(2).any(|window| window == b"\n\n")
+            || bytes.windows(3).any(|window| window == b"\n\r\n")
+    }
+
+    fn deadline(timeout: Option<Duration>) -> Option<Instant> {
+        timeout.map(|timeout| Instant::now() + timeout)
+    }
+
+    ///
+    /// Runs the future until the deadline, `None` when it passes first.
+    ///
+    async fn within<F: Future>(deadline: Option<Instant>, future: F) -> Option<F::Output> {
+        match deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline, future).await.ok(),
+            None => Some(future.await),
+        }
+    }
+
+    fn into_blocking(stream: TcpStream) -> io::Result<std::net::TcpStream> {
+        let stream = stream.into_std()?;
+        stream.set_nonblocking(false)?;
+        Ok(stream)
+    }
+}
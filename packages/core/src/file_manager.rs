@@ -0,0 +1,198 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::client_stream::ClientStream;
+use crate::config::Config;
+use crate::logger::Logger;
+use crate::request::{HttpMethod, Request};
+use crate::response::Response;
+use crate::static_directory_manager::StaticDirectoryManager;
+use crate::upload::{Upload, BEARER_CHALLENGE};
+
+pub const MOVE_PATH: &str = "/__rsrv/move";
+
+/// Move requests are a small json object, anything larger is refused
+const MAX_MOVE_REQUEST_SIZE: u64 = 64 * 1024;
+
+/// # FileManager
+///
+/// A functional struct that deletes and moves files in the served directories,
+/// enabled with `--manage-files`, `RSRV_MANAGE_FILES` or `uploads.manage`.
+/// Requests carry the same bearer token as uploads.
+///
+/// - `DELETE /path` removes a file, or a directory along with everything in it,
+///   answering `204`, or `404` when there is nothing at the path
+/// - `POST /__rsrv/move` with `{ "from": "/path", "to": "/other/path" }` renames a file or
+///   directory, creating the destination's parent directories, answering `204`
+///
+/// Moves stay within the served directory they start in, and refuse with `409` to replace
+/// an existing destination unless `"overwrite": true` is given, which never replaces a directory.
+/// Paths are confined like uploads, and the served directories themselves cannot be removed.
+///
+/// ```sh
+/// $ curl -X DELETE -H "Authorization: Bearer $TOKEN" http://preview:8080/pr-12/
+/// $ curl -H "Authorization: Bearer $TOKEN" -d '{"from": "/pr-12", "to": "/archive/pr-12"}' http://preview:8080/__rsrv/move
+/// ```
+///
+pub struct FileManager;
+
+impl FileManager {
+    pub fn handles(request: &Request) -> bool {
+        match request.method() {
+            HttpMethod::DELETE => true,
+            HttpMethod::POST => request.path() == MOVE_PATH,
+            _ => false,
+        }
+    }
+
+    pub fn handle(
+        request: &Request,
+        static_directory_manager: &StaticDirectoryManager,
+        config: &Config,
+        stream: &mut ClientStream,
+    ) {
+        if !config.uploads.authorizes(request) {
+            return Upload::respond_unauthorized(
+                request,
+                BEARER_CHALLENGE,
+                static_directory_manager,
+                config,
+                stream,
+            );
+        }
+
+        let result = match request.method() {
+            HttpMethod::DELETE => Self::delete(request.path(), static_directory_manager),
+            _ => Self::move_file(request, static_directory_manager, config, stream),
+        };
+
+        Upload::respond_with_result(result, request, static_directory_manager, config, stream);
+    }
+
+    pub fn delete(
+        path: &str,
+        static_directory_manager: &StaticDirectoryManager,
+    ) -> Result<Response, (u16, String)> {
+        // A symbolic link is removed itself, rather than what it points at
+        let (root, target) = Upload::resolve_path(path, static_directory_manager)?;
+        if target == root {
+            return Err((
+                403,
+                format!("Refusing to delete the served directory {}.", path),
+            ));
+        }
+
+        let metadata = fs::symlink_metadata(&target)
+            .map_err(|_| (404, format!("{} does not exist.", path)))?;
+        let removed = if metadata.is_dir() {
+            fs::remove_dir_all(&target)
+        } else {
+            fs::remove_file(&target)
+        };
+        removed.map_err(|e| Self::io_error(&format!("Unable to delete {}.", path), e))?;
+
+        Logger::info(&format!("Deleted {} -> {}", path, target.display()));
+        Ok(Upload::build_response(204, None, &[]))
+    }
+
+    fn move_file(
+        request: &Request,
+        static_directory_manager: &StaticDirectoryManager,
+        config: &Config,
+        stream: &mut ClientStream,
+    ) -> Result<Response, (u16, String)> {
+        Upload::ensure_body_allowed(request, MAX_MOVE_REQUEST_SIZE)?;
+        let mut body: Vec<u8> = vec![];
+        let mut body_reader = Upload::body_reader(request, stream, config.timeouts.min_rate);
+        Upload::copy_body(request, &mut body_reader, &mut body, MAX_MOVE_REQUEST_SIZE)?;
+
+        let move_request: Value = serde_json::from_slice(&body)
+            .map_err(|e| (400, format!("The move request is not valid json. {}", e)))?;
+        let path_of = |key: &str| {
+            move_request[key]
+                .as_str()
+                .filter(|path| path.starts_with('/'))
+                .map(String::from)
+                .ok_or_else(|| {
+                    (
+                        400,
+                        format!("The move request needs a \"{}\" path starting with /.", key),
+                    )
+                })
+        };
+        let from = path_of("from")?;
+        let to = path_of("to")?;
+        let overwrite = move_request["overwrite"].as_bool().unwrap_or(false);
+
+        // Symbolic links are moved, or replaced, themselves
+        let (from_root, source) = Upload::resolve_path(&from, static_directory_manager)?;
+        let (to_root, destination) = Upload::resolve_path(&to, static_directory_manager)?;
+
+        if from_root != to_root {
+            return Err((
+                409,
+                format!(
+                    "{} and {} are in different served directories, moves stay within one.",
+                    from, to
+                ),
+            ));
+        }
+        if source == from_root || destination == to_root {
+            return Err((403, String::from("Refusing to move a served directory.")));
+        }
+        if destination.starts_with(&source) {
+            return Err((409, format!("Unable to move {} into itself.", from)));
+        }
+
+        let source_metadata = fs::symlink_metadata(&source)
+            .map_err(|_| (404, format!("{} does not exist.", from)))?;
+
+        if let Ok(destination_metadata) = fs::symlink_metadata(&destination) {
+            if !overwrite {
+                return Err((409, format!("{} already exists.", to)));
+            }
+            if destination_metadata.is_dir() || source_metadata.is_dir() {
+                return Err((
+                    409,
+                    format!("{} already exists, directories are never overwritten.", to),
+                ));
+            }
+        }
+
+        let destination_parent = destination.parent().unwrap_or(&to_root);
+        Self::create_parent(&to_root, destination_parent, &to)?;
+        fs::rename(&source, &destination)
+            .map_err(|e| Self::io_error(&format!("Unable to move {} to {}.", from, to), e))?;
+
+        Logger::info(&format!(
+            "Moved {} -> {} ({} -> {})",
+            from,
+            to,
+            source.display(),
+            destination.display()
+        ));
+        Ok(Upload::build_response(204, None, &[]))
+    }
+
+    fn create_parent(root: &Path, parent: &Path, path: &str) -> Result<(), (u16, String)> {
+        fs::create_dir_all(parent).map_err(|e| {
+            Self::io_error(&format!("Unable to create the directory for {}.", path), e)
+        })?;
+        Upload::ensure_confined(root, parent)
+    }
+
+    pub fn io_error(message: &str, e: std::io::Error) -> (u16, String) {
+        let status = match e.kind() {
+            ErrorKind::NotFound => 404,
+            ErrorKind::AlreadyExists | ErrorKind::NotADirectory | ErrorKind::DirectoryNotEmpty => {
+                409
+            }
+            ErrorKind::PermissionDenied => 403,
+            _ => 500,
+        };
+        (status, format!("{} {}", message, e))
+    }
+}
@@ -0,0 +1,182 @@
+use serde_json::Value;
+
+use crate::headers::Headers;
+use crate::rules::RulePattern;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderOperation {
+    /// Replace any existing value
+    Set(String, String),
+    /// Join onto any existing value with `, `
+    Append(String, String),
+    Remove(String),
+}
+
+/// # HeaderRule
+///
+/// A set of header operations applied to responses whose path matches a glob.
+///
+/// Globs containing a `/` are matched against the whole path using the
+/// same syntax as redirect and rewrite sources (`/fonts/*`, `/assets/**`),
+/// globs without one are matched against the file name (`*.html`).
+///
+#[derive(Debug, Clone)]
+pub struct HeaderRule {
+    pub source: String,
+    pub operations: Vec<HeaderOperation>,
+    pattern: RulePattern,
+}
+
+impl HeaderRule {
+    pub fn new(source: &str, operations: Vec<HeaderOperation>) -> Self {
+        HeaderRule {
+            source: String::from(source),
+            operations,
+            pattern: RulePattern::new(source),
+        }
+    }
+
+    pub fn from_json(value: &Value) -> Result<Self, String> {
+        let source = value["source"].as_str().ok_or_else(|| {
+            format!(
+                "HeaderRule::from_json() Exception: Header rule is missing a \"source\": {}",
+                value
+            )
+        })?;
+
+        let mut operations: Vec<HeaderOperation> = vec![];
+
+        for (key, is_append) in [("set", false), ("append", true)] {
+            match &value[key] {
+                Value::Null => (),
+                Value::Object(headers) => {
+                    for (name, header_value) in headers {
+                        let header_value = header_value.as_str().ok_or_else(|| {
+                            format!(
+                                "HeaderRule::from_json() Exception: Value of {} must be a string in {}",
+                                name, source
+                            )
+                        })?;
+                        operations.push(if is_append {
+                            HeaderOperation::Append(name.clone(), String::from(header_value))
+                        } else {
+                            HeaderOperation::Set(name.clone(), String::from(header_value))
+                        });
+                    }
+                }
+                _ => {
+                    return Err(format!(
+                        "HeaderRule::from_json() Exception: \"{}\" must be an object in {}",
+                        key, source
+                    ))
+                }
+            }
+        }
+
+        match &value["remove"] {
+            Value::Null => (),
+            Value::Array(names) => {
+                for name in names {
+                    let name = name.as_str().ok_or_else(|| {
+                        format!(
+                            "HeaderRule::from_json() Exception: \"remove\" must list header names in {}",
+                            source
+                        )
+                    })?;
+                    operations.push(HeaderOperation::Remove(String::from(name)));
+                }
+            }
+            _ => {
+                return Err(format!(
+                    "HeaderRule::from_json() Exception: \"remove\" must be an array in {}",
+                    source
+                ))
+            }
+        }
+
+        if operations.is_empty() {
+            return Err(format!(
+                "HeaderRule::from_json() Exception: Header rule for {} has no \"set\", \"append\" or \"remove\".",
+                source
+            ));
+        }
+
+        Ok(HeaderRule::new(source, operations))
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        if self.source.contains('/') {
+            return self.pattern.captures(path).is_some();
+        }
+
+        let file_name = path.rsplit('/').next().unwrap_or(path);
+        RulePattern::glob_matches(&self.source, file_name)
+    }
+}
+
+/// # HeaderRules
+///
+/// Per-path response header rules, read from the `"headers"` array of the `--rules=` file.
+///
+/// ```json
+/// {
+///   "headers": [
+///     { "source": "/assets/**", "set": { "Cache-Control": "public, max-age=31536000, immutable" } },
+///     { "source": "*.html", "set": { "Cache-Control": "no-cache" } },
+///     { "source": "/fonts/*", "set": { "Access-Control-Allow-Origin": "*" }, "append": { "Vary": "Origin" } },
+///     { "source": "/**", "remove": ["X-Site"] }
+///   ]
+/// }
+/// ```
+///
+/// Every matching rule is applied, in the order they are listed,
+/// so later rules override earlier ones.
+///
+#[derive(Debug, Clone, Default)]
+pub struct HeaderRules {
+    pub rules: Vec<HeaderRule>,
+}
+
+impl HeaderRules {
+    pub fn from_json_value(value: &Value) -> Result<Self, String> {
+        let mut rules: Vec<HeaderRule> = vec![];
+        match value {
+            Value::Null => (),
+            Value::Array(entries) => {
+                for entry in entries {
+                    rules.push(HeaderRule::from_json(entry)?);
+                }
+            }
+            _ => {
+                return Err(String::from(
+                    "HeaderRules::from_json_value() Exception: \"headers\" must be an array.",
+                ))
+            }
+        }
+        Ok(HeaderRules { rules })
+    }
+
+    pub fn apply(&self, headers: &mut Headers, path: &str) {
+        for rule in self.rules.iter().filter(|rule| rule.matches(path)) {
+            for operation in &rule.operations {
+                match operation {
+                    HeaderOperation::Set(name, value) => {
+                        headers.remove_header_by_key_ignoring_case(name);
+                        headers.map.insert(name.clone(), value.clone());
+                    }
+                    HeaderOperation::Append(name, value) => {
+                        let appended_value = match headers.remove_header_by_key_ignoring_case(name)
+                        {
+                            Some(existing_value) => format!("{}, {}", existing_value, value),
+                            None => value.clone(),
+                        };
+                        headers.map.insert(name.clone(), appended_value);
+                    }
+                    HeaderOperation::Remove(name) => {
+                        headers.remove_header_by_key_ignoring_case(name);
+                    }
+                }
+            }
+        }
+    }
+}
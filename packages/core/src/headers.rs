@@ -1,7 +1,8 @@
 use std::collections::HashMap;
 
-use crate::arguments::Arguments;
+use crate::config::Config;
 use crate::filelike::FileLike;
+use crate::mount::Mount;
 use crate::request::Request;
 
 #[derive(Debug, Clone)]
@@ -17,15 +18,38 @@ impl Headers {
         }
         Headers { map }
     }
-    pub fn construct_outgoing_headers(request: Request, file: &FileLike, compressed: bool) -> Self {
+    pub fn construct_outgoing_headers(
+        request: Request,
+        file: &FileLike,
+        compressed: bool,
+        mount: Option<&Mount>,
+        site_headers: &[(String, String)],
+        secure: bool,
+        config: &Config,
+    ) -> Self {
         let mut headers = Self::new(vec![]);
         Self::add_content_type_outgoing_header(&mut headers, &request);
-        Self::add_cache_control_outgoing_header(&mut headers);
+        Self::add_cache_control_outgoing_header(&mut headers, &request, mount, config);
+        if secure {
+            Self::add_strict_transport_security_outgoing_header(&mut headers, config);
+        }
         if compressed {
-            Self::add_content_encoding_outgoing_header(&mut headers, &request);
+            Self::add_content_encoding_outgoing_header(&mut headers, &request, config);
         } else {
             Self::add_content_length_outgoing_header(&mut headers, file);
         }
+        if let Some(cors) = &config.cors {
+            headers
+                .map
+                .insert(String::from("Access-Control-Allow-Origin"), cors.clone());
+        }
+        for (key, value) in site_headers {
+            headers.map.insert(key.clone(), value.clone());
+        }
+        config
+            .rules
+            .header_rules
+            .apply(&mut headers, request.path());
 
         headers
     }
@@ -41,6 +65,18 @@ impl Headers {
         }
         kv_header_vec
     }
+    ///
+    /// Removes a header regardless of the casing it was inserted with, returning its value.
+    ///
+    pub fn remove_header_by_key_ignoring_case(&mut self, key: &str) -> Option<String> {
+        let existing_key = self
+            .map
+            .keys()
+            .find(|existing_key| existing_key.eq_ignore_ascii_case(key))
+            .cloned()?;
+        self.map.remove(&existing_key)
+    }
+
     pub fn get_header_by_key(&self, key: &str) -> Option<&String> {
         let header = self.map.get(&String::from(key));
         match header {
@@ -110,11 +146,12 @@ impl Headers {
         }
     }
 
-    fn add_content_encoding_outgoing_header(headers: &mut Self, request: &Request) {
-        let disabled_compression = match Arguments::find_compression_argument_or_get_default() {
-            Some(_) => true,
-            None => false,
-        };
+    fn add_content_encoding_outgoing_header(
+        headers: &mut Self,
+        request: &Request,
+        config: &Config,
+    ) {
+        let disabled_compression = !config.compression;
 
         let null_accept_encoding_header = String::new();
         let derefd_accept_encoding_header = request
@@ -146,14 +183,33 @@ impl Headers {
         );
     }
 
-    fn add_cache_control_outgoing_header(headers: &mut Self) {
-        let cache_control_header_value = Arguments::find_cache_control_argument_or_get_default();
+    fn add_cache_control_outgoing_header(
+        headers: &mut Self,
+        request: &Request,
+        mount: Option<&Mount>,
+        config: &Config,
+    ) {
+        let cache_control_header_value = match mount.and_then(|mount| mount.cache_control.clone()) {
+            Some(mount_cache_control) => mount_cache_control,
+            None => config.cache_policy.cache_control_for(request.path()),
+        };
         let cache_control_header_key = String::from("Cache-Control");
         headers
             .map
             .insert(cache_control_header_key, cache_control_header_value);
     }
 
+    ///
+    /// Only sent over TLS, browsers ignore `Strict-Transport-Security` on plain HTTP.
+    ///
+    fn add_strict_transport_security_outgoing_header(headers: &mut Self, config: &Config) {
+        if let Some(hsts) = &config.tls.hsts {
+            headers
+                .map
+                .insert(String::from("Strict-Transport-Security"), hsts.clone());
+        }
+    }
+
     fn add_content_length_outgoing_header(headers: &mut Self, file: &FileLike) {
         headers
             .map
@@ -0,0 +1,764 @@
+use std::future::{poll_fn, Future};
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use bytes::Bytes;
+use h2::server::SendResponse;
+use h2::{Reason, RecvStream, SendStream};
+use http::header::{HeaderName, HeaderValue, CONTENT_LENGTH, COOKIE, HOST};
+use http::Method;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+use crate::client_stream::ClientStream;
+use crate::config_watcher::SharedConfig;
+use crate::connection::ConnectionHandler;
+use crate::live_reload::LiveReload;
+use crate::logger::Logger;
+use crate::rate_limit::RateLimiter;
+use crate::webdav_locks::WebDavLocks;
+
+/// What a client sends first on an HTTP/2 connection it opens without negotiating
+pub const PREFACE: &[u8; 24] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+pub const DEFAULT_MAX_CONCURRENT_STREAMS: u32 = 100;
+
+const STREAM_WINDOW_SIZE: u32 = 1024 * 1024;
+const CONNECTION_WINDOW_SIZE: u32 = 8 * 1024 * 1024;
+/// Chunks of a body in flight between a stream and its handler, bounding memory per stream
+const BODY_CHANNEL_SIZE: usize = 16;
+/// How long to wait for the rest of a preface that arrived split across packets
+const PREFACE_WAIT: Duration = Duration::from_millis(500);
+
+/// Headers that describe an HTTP/1.1 connection, which HTTP/2 forbids
+const CONNECTION_HEADERS: [&str; 6] = [
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "transfer-encoding",
+    "upgrade",
+    "te",
+];
+
+/// # Http2Settings
+///
+/// Whether rsrv speaks HTTP/2, on by default. Turned off with `--no-http2`,
+/// `RSRV_HTTP2=false` or an `[http2]` table.
+///
+/// ```toml
+/// [http2]
+/// enabled = true
+/// max_concurrent_streams = 100
+/// ```
+///
+/// `max_concurrent_streams`, `--http2-max-streams` or `RSRV_HTTP2_MAX_STREAMS` caps
+/// the requests a client may have open at once on one connection.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Http2Settings {
+    pub enabled: bool,
+    pub max_concurrent_streams: u32,
+}
+
+impl Default for Http2Settings {
+    fn default() -> Self {
+        Http2Settings {
+            enabled: true,
+            max_concurrent_streams: DEFAULT_MAX_CONCURRENT_STREAMS,
+        }
+    }
+}
+
+impl Http2Settings {
+    pub fn describe(&self) -> String {
+        format!(
+            "HTTP/2 enabled, h2 over TLS and h2c with prior knowledge, {} concurrent streams per connection",
+            self.max_concurrent_streams
+        )
+    }
+}
+
+/// # Http2
+///
+/// Serves HTTP/2 connections, negotiated with ALPN `h2` over TLS or opened with
+/// the HTTP/2 preface on plain connections (h2c with prior knowledge).
+///
+/// Connections stay on the event loop, see `EventLoop`, where the `h2` crate takes
+/// care of framing, HPACK, flow control and the concurrent stream limit. Each stream is
+/// handed to `ConnectionHandler` on a blocking thread as an HTTP/1.1 request, read
+/// from and answered through a `Http2Exchange`, so every handler behaves as it
+/// does over HTTP/1.1. Streams of one connection are handled in parallel.
+///
+/// The timeouts apply as they do over HTTP/1.1. A connection without open streams is
+/// closed with a GOAWAY after `idle`, a body that stalls past `body_read` is answered
+/// `408` by its handler, and a stream whose client reads no more of the response for
+/// `write` is reset.
+///
+pub struct Http2;
+
+/// What every stream on a connection is answered with
+#[derive(Clone)]
+struct ExchangeContext {
+    secure: bool,
+    peer_addr: SocketAddr,
+    shared_config: SharedConfig,
+    live_reload: LiveReload,
+    webdav_locks: WebDavLocks,
+    rate_limiter: RateLimiter,
+}
+
+impl Http2 {
+    ///
+    /// Whether a plain connection opens with the HTTP/2 preface, without consuming it.
+    ///
+    pub async fn has_preface(stream: &TcpStream) -> bool {
+        let mut buffer = [0u8; PREFACE.len()];
+        let mut waited = Duration::ZERO;
+
+        loop {
+            let peeked = match stream.peek(&mut buffer).await {
+                Ok(peeked) => peeked,
+                Err(_) => return false,
+            };
+            if peeked == 0 || buffer[..peeked] != PREFACE[..peeked] {
+                return false;
+            }
+            if peeked == PREFACE.len() {
+                return true;
+            }
+            if waited >= PREFACE_WAIT {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            waited += Duration::from_millis(5);
+        }
+    }
+
+    pub async fn serve<T>(
+        stream: T,
+        secure: bool,
+        peer_addr: SocketAddr,
+        shared_config: SharedConfig,
+        live_reload: LiveReload,
+        webdav_locks: WebDavLocks,
+        rate_limiter: RateLimiter,
+    ) where
+        T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let context = ExchangeContext {
+            secure,
+            peer_addr,
+            shared_config,
+            live_reload,
+            webdav_locks,
+            rate_limiter,
+        };
+        let served = Self::serve_connection(stream, context).await;
+
+        match served {
+            Ok(()) => (),
+            // Clients leaving without a GOAWAY is routine
+            Err(e) if Self::is_disconnect(&e) => (),
+            Err(e) => Logger::warn(&format!(
+                "Http2::serve() Exception: HTTP/2 connection closed. {}",
+                e
+            )),
+        }
+    }
+
+    fn is_disconnect(e: &h2::Error) -> bool {
+        e.get_io().is_some_and(|e| {
+            matches!(
+                e.kind(),
+                ErrorKind::ConnectionReset | ErrorKind::UnexpectedEof | ErrorKind::BrokenPipe
+            )
+        })
+    }
+
+    async fn serve_connection<T>(stream: T, context: ExchangeContext) -> Result<(), h2::Error>
+    where
+        T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let config = context.shared_config.current();
+        let idle = config.timeouts.idle;
+        let handshake = h2::server::Builder::new()
+            .max_concurrent_streams(config.http2.max_concurrent_streams)
+            .initial_window_size(STREAM_WINDOW_SIZE)
+            .initial_connection_window_size(CONNECTION_WINDOW_SIZE)
+            .handshake::<_, Bytes>(stream);
+        let mut connection = match Self::within(idle, handshake).await {
+            Some(connection) => connection?,
+            None => return Ok(()),
+        };
+
+        let activity = StreamActivity::new();
+        let mut closing = false;
+        loop {
+            let accepted = match idle.filter(|_| !closing) {
+                Some(idle) => {
+                    let deadline = activity.idle_deadline(idle);
+                    match tokio::time::timeout_at(deadline, connection.accept()).await {
+                        Ok(accepted) => accepted,
+                        Err(_) => {
+                            // Streams may have opened or closed since, moving the deadline
+                            if activity.idle_deadline(idle) <= Instant::now() {
+                                connection.graceful_shutdown();
+                                closing = true;
+                            }
+                            continue;
+                        }
+                    }
+                }
+                None => connection.accept().await,
+            };
+            let Some(accepted) = accepted else {
+                break;
+            };
+            let (request, respond) = accepted?;
+            tokio::spawn(Self::exchange(
+                request,
+                respond,
+                context.clone(),
+                activity.clone(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    async fn within<F: Future>(timeout: Option<Duration>, future: F) -> Option<F::Output> {
+        match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, future).await.ok(),
+            None => Some(future.await),
+        }
+    }
+
+    async fn exchange(
+        request: http::Request<RecvStream>,
+        respond: SendResponse<Bytes>,
+        context: ExchangeContext,
+        activity: StreamActivity,
+    ) {
+        activity.open();
+        let (parts, body) = request.into_parts();
+        let chunked = !body.is_end_stream() && !parts.headers.contains_key(CONTENT_LENGTH);
+        let head_only = parts.method == Method::HEAD;
+
+        let (request_sender, request_receiver) = mpsc::channel(BODY_CHANNEL_SIZE);
+        let (response_sender, response_receiver) = mpsc::channel(BODY_CHANNEL_SIZE);
+        let config = context.shared_config.current();
+        let write_timeout = Arc::new(Mutex::new(config.timeouts.write));
+        let exchange = Http2Exchange {
+            secure: context.secure,
+            peer_addr: context.peer_addr,
+            request: Mutex::new(ExchangeRequest {
+                pending: Self::request_head(&parts, chunked),
+                position: 0,
+                receiver: request_receiver,
+            }),
+            response: Mutex::new(Some(response_sender)),
+            read_timeout: Mutex::new(config.timeouts.body_read),
+            write_timeout: Arc::clone(&write_timeout),
+            runtime: Handle::current(),
+        };
+
+        tokio::task::spawn_blocking(move || {
+            ConnectionHandler::handle(
+                ClientStream::Http2(Arc::new(exchange)),
+                Vec::new(),
+                &config,
+                &context.live_reload,
+                &context.webdav_locks,
+                &context.rate_limiter,
+                // The HTTP/2 connection is counted as a whole while it is served
+                None,
+            )
+        });
+        tokio::spawn(Self::forward_request_body(body, request_sender, chunked));
+
+        Self::forward_response(respond, response_receiver, head_only, write_timeout).await;
+        activity.close();
+    }
+
+    ///
+    /// The request as HTTP/1.1 would have sent it, a body without a length being chunked.
+    ///
+    fn request_head(parts: &http::request::Parts, chunked: bool) -> Vec<u8> {
+        let target = parts
+            .uri
+            .path_and_query()
+            .map(|path_and_query| path_and_query.as_str())
+            .unwrap_or("/");
+        let mut head = format!("{} {} HTTP/2.0\r\n", parts.method, target);
+
+        if !parts.headers.contains_key(HOST) {
+            if let Some(authority) = parts.uri.authority() {
+                head.push_str(&format!("Host: {}\r\n", authority));
+            }
+        }
+        for name in parts.headers.keys() {
+            let values: Vec<&str> = parts
+                .headers
+                .get_all(name)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .collect();
+            let separator = if name == COOKIE { "; " } else { ", " };
+            head.push_str(&format!(
+                "{}: {}\r\n",
+                Self::canonical_name(name.as_str()),
+                values.join(separator)
+            ));
+        }
+        if chunked {
+            head.push_str("Transfer-Encoding: chunked\r\n");
+        }
+        head.push_str("\r\n");
+
+        head.into_bytes()
+    }
+
+    ///
+    /// HTTP/2 header names are lowercase, handlers look them up as `Accept-Encoding`.
+    ///
+    fn canonical_name(name: &str) -> String {
+        name.split('-')
+            .map(|word| {
+                let mut characters = word.chars();
+                match characters.next() {
+                    Some(first) => first.to_ascii_uppercase().to_string() + characters.as_str(),
+                    None => String::new(),
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("-")
+    }
+
+    async fn forward_request_body(
+        mut body: RecvStream,
+        sender: mpsc::Sender<Vec<u8>>,
+        chunked: bool,
+    ) {
+        while let Some(data) = body.data().await {
+            let data = match data {
+                Ok(data) => data,
+                // The handler reads a short body and answers accordingly
+                Err(_) => return,
+            };
+            let length = data.len();
+            let data = match chunked {
+                true => [
+                    format!("{:x}\r\n", length).as_bytes(),
+                    &data,
+                    b"\r\n".as_slice(),
+                ]
+                .concat(),
+                false => data.to_vec(),
+            };
+
+            if length == 0 {
+                continue;
+            }
+            if sender.send(data).await.is_err() {
+                return;
+            }
+            // Only once the handler has room for more, so a slow upload slows the client
+            let _ = body.flow_control().release_capacity(length);
+        }
+
+        if chunked {
+            let _ = sender.send(b"0\r\n\r\n".to_vec()).await;
+        }
+    }
+
+    ///
+    /// Reads the handler's HTTP/1.1 response and sends it as HTTP/2 frames,
+    /// until the handler is done or the client resets the stream. A client that
+    /// leaves no room for more of the response within `write_timeout` has the stream reset.
+    ///
+    async fn forward_response(
+        mut respond: SendResponse<Bytes>,
+        mut receiver: mpsc::Receiver<Vec<u8>>,
+        head_only: bool,
+        write_timeout: Arc<Mutex<Option<Duration>>>,
+    ) {
+        let mut head = Vec::new();
+        let head_end = loop {
+            let chunk = tokio::select! {
+                chunk = receiver.recv() => chunk,
+                _ = poll_fn(|cx| respond.poll_reset(cx)) => return,
+            };
+            match chunk {
+                Some(chunk) => head.extend_from_slice(&chunk),
+                None => {
+                    respond.send_reset(Reason::INTERNAL_ERROR);
+                    return;
+                }
+            }
+            if let Some(head_end) = head.windows(4).position(|window| window == b"\r\n\r\n") {
+                break head_end;
+            }
+        };
+
+        let (response, chunked) = match Self::parse_response_head(&head[..head_end]) {
+            Some(response) => response,
+            None => {
+                respond.send_reset(Reason::INTERNAL_ERROR);
+                return;
+            }
+        };
+        let mut send_stream = match respond.send_response(response, false) {
+            Ok(send_stream) => send_stream,
+            Err(_) => return,
+        };
+
+        let mut decoder = chunked.then(ChunkedDecoder::default);
+        let mut pending = head.split_off(head_end + 4);
+        loop {
+            let data = match &mut decoder {
+                Some(decoder) => decoder.decode(&pending),
+                None => pending,
+            };
+            if !head_only && !data.is_empty() {
+                let timeout = *Http2Exchange::lock(&write_timeout);
+                match Self::within(timeout, Self::send_data(&mut send_stream, data)).await {
+                    Some(Ok(())) => (),
+                    Some(Err(_)) => return,
+                    None => {
+                        Logger::warn(&format!(
+                            "Http2::forward_response() Exception: Reset a stream, the client read none of the response for {}s.",
+                            timeout.unwrap_or_default().as_secs()
+                        ));
+                        send_stream.send_reset(Reason::CANCEL);
+                        return;
+                    }
+                }
+            }
+            if decoder.as_ref().is_some_and(ChunkedDecoder::is_done) {
+                break;
+            }
+
+            let chunk = tokio::select! {
+                chunk = receiver.recv() => chunk,
+                _ = poll_fn(|cx| send_stream.poll_reset(cx)) => return,
+            };
+            pending = match chunk {
+                Some(chunk) => chunk,
+                None => break,
+            };
+        }
+
+        let _ = send_stream.send_data(Bytes::new(), true);
+    }
+
+    fn parse_response_head(head: &[u8]) -> Option<(http::Response<()>, bool)> {
+        let head = String::from_utf8_lossy(head);
+        let mut lines = head.split("\r\n");
+        let status = lines
+            .next()?
+            .split(' ')
+            .nth(1)?
+            .parse::<u16>()
+            .ok()
+            .filter(|status| *status >= 200)?;
+
+        let mut response = http::Response::builder().status(status).body(()).ok()?;
+        let mut chunked = false;
+        for line in lines {
+            let (key, value) = match line.split_once(':') {
+                Some(header) => header,
+                None => continue,
+            };
+            let key = key.trim().to_lowercase();
+            if key == "transfer-encoding" {
+                chunked = value.to_lowercase().contains("chunked");
+            }
+            if CONNECTION_HEADERS.contains(&key.as_str()) {
+                continue;
+            }
+
+            if let (Ok(key), Ok(value)) = (
+                HeaderName::from_bytes(key.as_bytes()),
+                HeaderValue::from_str(value.trim()),
+            ) {
+                response.headers_mut().append(key, value);
+            }
+        }
+
+        Some((response, chunked))
+    }
+
+    ///
+    /// Sends data as the stream's flow control window allows.
+    ///
+    async fn send_data(
+        send_stream: &mut SendStream<Bytes>,
+        data: Vec<u8>,
+    ) -> Result<(), h2::Error> {
+        let mut data = Bytes::from(data);
+
+        while !data.is_empty() {
+            send_stream.reserve_capacity(data.len());
+            let capacity = match poll_fn(|cx| send_stream.poll_capacity(cx)).await {
+                Some(capacity) => capacity?,
+                None => return Err(h2::Error::from(Reason::CANCEL)),
+            };
+            if capacity == 0 {
+                continue;
+            }
+            send_stream.send_data(data.split_to(capacity.min(data.len())), false)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// # StreamActivity
+///
+/// The streams open on a connection and since when it has had none, telling when
+/// the connection has been idle for long enough to close.
+///
+#[derive(Clone)]
+struct StreamActivity {
+    state: Arc<Mutex<(usize, Instant)>>,
+}
+
+impl StreamActivity {
+    fn new() -> Self {
+        StreamActivity {
+            state: Arc::new(Mutex::new((0, Instant::now()))),
+        }
+    }
+
+    fn open(&self) {
+        Http2Exchange::lock(&self.state).0 += 1;
+    }
+
+    fn close(&self) {
+        let mut state = Http2Exchange::lock(&self.state);
+        state.0 -= 1;
+        state.1 = Instant::now();
+    }
+
+    ///
+    /// When the connection counts as idle, `idle` from now while streams are open.
+    ///
+    fn idle_deadline(&self, idle: Duration) -> Instant {
+        let (open, since) = *Http2Exchange::lock(&self.state);
+        match open {
+            0 => since + idle,
+            _ => Instant::now() + idle,
+        }
+    }
+}
+
+/// # ChunkedDecoder
+///
+/// Strips chunked transfer encoding from a body as it arrives in arbitrary pieces,
+/// i.e. one relayed from a proxy upstream.
+///
+#[derive(Debug, Default)]
+struct ChunkedDecoder {
+    state: ChunkState,
+    size_line: String,
+}
+
+#[derive(Debug, Default)]
+enum ChunkState {
+    #[default]
+    Size,
+    Data(usize),
+    DataEnd,
+    /// Trailer lines after the last chunk, with the length of the current line
+    Trailers(usize),
+    Done,
+}
+
+impl ChunkedDecoder {
+    fn decode(&mut self, input: &[u8]) -> Vec<u8> {
+        let mut output = Vec::with_capacity(input.len());
+        let mut position = 0;
+
+        while position < input.len() {
+            match &mut self.state {
+                ChunkState::Size => {
+                    let byte = input[position];
+                    position += 1;
+                    if byte != b'\n' {
+                        self.size_line.push(byte as char);
+                        continue;
+                    }
+                    let size = self.size_line.split(';').next().unwrap_or_default().trim();
+                    self.state = match usize::from_str_radix(size, 16) {
+                        Ok(0) | Err(_) => ChunkState::Trailers(0),
+                        Ok(size) => ChunkState::Data(size),
+                    };
+                    self.size_line.clear();
+                }
+                ChunkState::Data(remaining) => {
+                    let length = (*remaining).min(input.len() - position);
+                    output.extend_from_slice(&input[position..position + length]);
+                    position += length;
+                    *remaining -= length;
+                    if *remaining == 0 {
+                        self.state = ChunkState::DataEnd;
+                    }
+                }
+                ChunkState::DataEnd => {
+                    if input[position] == b'\n' {
+                        self.state = ChunkState::Size;
+                    }
+                    position += 1;
+                }
+                ChunkState::Trailers(line_length) => {
+                    match input[position] {
+                        b'\n' if *line_length == 0 => self.state = ChunkState::Done,
+                        b'\n' => *line_length = 0,
+                        b'\r' => (),
+                        _ => *line_length += 1,
+                    }
+                    position += 1;
+                }
+                ChunkState::Done => break,
+            }
+        }
+
+        output
+    }
+
+    fn is_done(&self) -> bool {
+        matches!(self.state, ChunkState::Done)
+    }
+}
+
+/// # Http2Exchange
+///
+/// One HTTP/2 stream as its handler sees it, a `ClientStream` that reads the request
+/// in HTTP/1.1 form and takes the response in HTTP/1.1 form. Both directions are
+/// bounded channels to the connection's task, waited on no longer than the read and
+/// write timeouts, like a socket would be.
+///
+#[derive(Debug)]
+pub struct Http2Exchange {
+    secure: bool,
+    peer_addr: SocketAddr,
+    request: Mutex<ExchangeRequest>,
+    response: Mutex<Option<mpsc::Sender<Vec<u8>>>>,
+    read_timeout: Mutex<Option<Duration>>,
+    /// Shared with the task sending the response, which resets the stream once it expires
+    write_timeout: Arc<Mutex<Option<Duration>>>,
+    /// The runtime the channels belong to, for waiting on them with a timeout
+    runtime: Handle,
+}
+
+#[derive(Debug)]
+struct ExchangeRequest {
+    pending: Vec<u8>,
+    position: usize,
+    receiver: mpsc::Receiver<Vec<u8>>,
+}
+
+impl Http2Exchange {
+    pub fn is_secure(&self) -> bool {
+        self.secure
+    }
+
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer_addr
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) {
+        *Self::lock(&self.read_timeout) = timeout;
+    }
+
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) {
+        *Self::lock(&self.write_timeout) = timeout;
+    }
+
+    ///
+    /// Ends the response, anything written afterwards fails.
+    ///
+    pub fn finish(&self) {
+        Self::lock(&self.response).take();
+    }
+
+    fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+        mutex
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    ///
+    /// Waits on one of the channels from the handler's thread, `None` once `timeout` passes.
+    ///
+    fn wait<F: Future>(&self, timeout: Option<Duration>, future: F) -> Option<F::Output> {
+        match timeout {
+            Some(timeout) => self
+                .runtime
+                .block_on(tokio::time::timeout(timeout, future))
+                .ok(),
+            None => Some(self.runtime.block_on(future)),
+        }
+    }
+
+    fn timed_out(message: &str) -> io::Error {
+        io::Error::new(ErrorKind::TimedOut, message)
+    }
+}
+
+impl Read for &Http2Exchange {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let timeout = *Http2Exchange::lock(&self.read_timeout);
+        let mut request = Http2Exchange::lock(&self.request);
+
+        while request.position == request.pending.len() {
+            match self.wait(timeout, request.receiver.recv()) {
+                Some(Some(chunk)) => {
+                    request.pending = chunk;
+                    request.position = 0;
+                }
+                Some(None) => return Ok(0),
+                None => {
+                    return Err(Http2Exchange::timed_out(
+                        "the HTTP/2 stream sent no more of the body",
+                    ))
+                }
+            }
+        }
+
+        let start = request.position;
+        let length = buffer.len().min(request.pending.len() - start);
+        buffer[..length].copy_from_slice(&request.pending[start..start + length]);
+        request.position += length;
+        Ok(length)
+    }
+}
+
+impl Write for &Http2Exchange {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        // Cloned, so a slow client never blocks `finish` behind the lock
+        let sender = Http2Exchange::lock(&self.response).clone();
+        let timeout = *Http2Exchange::lock(&self.write_timeout);
+
+        let sent = sender.map(|sender| self.wait(timeout, sender.send(buffer.to_vec())));
+        match sent {
+            Some(Some(Ok(()))) => Ok(buffer.len()),
+            Some(None) => Err(Http2Exchange::timed_out(
+                "the HTTP/2 client stopped reading the response",
+            )),
+            _ => Err(io::Error::new(
+                ErrorKind::BrokenPipe,
+                "the HTTP/2 stream is closed",
+            )),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+use std::io::BufReader;
+use std::net::TcpListener;
+use std::thread;
+
+use crate::client_stream::ClientStream;
+use crate::config::Config;
+use crate::config_watcher::SharedConfig;
+use crate::logger::Logger;
+use crate::request::Request;
+use crate::response::Response;
+use crate::virtual_host::VirtualHosts;
+
+/// # HttpsRedirect
+///
+/// The plain HTTP listener opened with `--http-redirect-port`, which answers every
+/// request with a `308 Permanent Redirect` to the same path on the HTTPS origin,
+/// keeping the method and body of form posts.
+///
+/// ```sh
+/// $ rsrv --https --port=8443 --http-redirect-port=8080
+/// ```
+///
+/// The redirect keeps the hostname the client asked for, so virtual hosts are
+/// redirected to themselves. It runs on its own thread, apart from the HTTPS listener.
+///
+pub struct HttpsRedirect;
+
+impl HttpsRedirect {
+    pub fn spawn(listener: TcpListener, shared_config: SharedConfig) {
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let config = shared_config.current();
+                        if let Ok(peer_addr) = stream.peer_addr() {
+                            if !config.access.permits(peer_addr.ip()) {
+                                Logger::warn(&format!(
+                                    "Denied the connection from {} by the access rules",
+                                    peer_addr
+                                ));
+                                continue;
+                            }
+                        }
+                        Self::redirect(ClientStream::Plain(stream), &config);
+                    }
+                    Err(e) => {
+                        Logger::error(&format!("Stream Corrupted: {:#?}", e));
+                    }
+                }
+            }
+        });
+    }
+
+    fn redirect(mut stream: ClientStream, config: &Config) {
+        let request = match Request::new(BufReader::new(&mut stream)) {
+            Ok(request) => request,
+            Err(e) => {
+                Logger::warn(&format!(
+                    "HttpsRedirect::redirect() Exception: Dropped an unreadable request. {}",
+                    e
+                ));
+                return;
+            }
+        };
+
+        let location = Self::location(&request, config);
+        Logger::info(&format!(
+            "Redirecting {} -> {} (308)",
+            request.path(),
+            location
+        ));
+        Response::redirect(308, &location).respond(&mut stream);
+    }
+
+    ///
+    /// The HTTPS url for a request, on the port HTTPS is served on.
+    ///
+    pub fn location(request: &Request, config: &Config) -> String {
+        let hostname = request
+            .headers()
+            .get_header_by_key("Host")
+            .map(|host_header| VirtualHosts::strip_port(host_header))
+            .filter(|hostname| Self::is_hostname(hostname))
+            .map(String::from)
+            .unwrap_or_else(|| Self::listening_hostname(config));
+
+        let port = match config.port {
+            443 => String::new(),
+            port => format!(":{}", port),
+        };
+        let path = match request.path().starts_with('/') {
+            true => request.path().as_str(),
+            false => "/",
+        };
+        let query = match request.query() {
+            Some(query) => format!("?{}", query),
+            None => String::new(),
+        };
+
+        format!("https://{}{}{}{}", hostname, port, path, query)
+    }
+
+    ///
+    /// Guards the redirect against `Host` headers that would point it elsewhere,
+    /// i.e. ones carrying a path or credentials.
+    ///
+    fn is_hostname(hostname: &str) -> bool {
+        !hostname.is_empty()
+            && hostname
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '[' | ']' | ':'))
+    }
+
+    fn listening_hostname(config: &Config) -> String {
+        match config.host.as_str() {
+            "0.0.0.0" | "::" | "[::]" => String::from("localhost"),
+            host if host.contains(':') && !host.starts_with('[') => format!("[{}]", host),
+            host => String::from(host),
+        }
+    }
+}
@@ -1,46 +1,177 @@
+pub mod access_control;
 pub mod arguments;
+pub mod async_tls;
+pub mod basic_auth;
 pub mod cache;
+pub mod cache_policy;
+pub mod client_stream;
+pub mod config;
+pub mod config_watcher;
 pub mod connection;
 pub mod default_file;
+pub mod dev_certificate;
 pub mod directory;
+pub mod directory_listing;
+pub mod error_page;
+pub mod event_loop;
+pub mod file_manager;
 pub mod filelike;
 pub mod gzip;
+pub mod header_rules;
 pub mod headers;
 pub mod hostname;
+pub mod http2;
+pub mod https_redirect;
+pub mod live_reload;
 pub mod logger;
+pub mod mock;
+pub mod mount;
 pub mod port;
+pub mod proxy;
+pub mod rate_limit;
 pub mod request;
 pub mod response;
+pub mod rules;
 pub mod static_directory_manager;
+pub mod status;
+pub mod timeouts;
+pub mod tls;
+pub mod upload;
+pub mod virtual_host;
+pub mod webdav;
+pub mod webdav_locks;
+pub mod webdav_properties;
 
-use std::{env, error::Error, net::TcpListener, process};
+use std::{error::Error, net::TcpListener, process};
 
 use arguments::Arguments;
-use connection::ConnectionHandler;
-use default_file::DefaultFile;
-use directory::Directory;
+use cache_policy::CachePolicy;
+use config::Config;
+use config_watcher::{ConfigWatcher, SharedConfig};
+use dev_certificate::DevCertificate;
+use event_loop::EventLoop;
+use https_redirect::HttpsRedirect;
+use live_reload::LiveReload;
 use logger::Logger;
-use static_directory_manager::StaticDirectoryManager;
+use rate_limit::RateLimiter;
+use rules::{RuleKind, Rules};
+use tls::Tls;
+use virtual_host::VirtualHosts;
+use webdav_locks::WebDavLocks;
 
-const VERSION: &str = "1.1.0";
+const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 pub fn run() {
-    echo_rsrv_process_started();
+    let parsed_arguments =
+        Arguments::parse(&Arguments::get_command_line_args()).unwrap_or_else(|e| {
+            Logger::fatal(&e);
+            Logger::fatal("Run `rsrv --help` to see every flag. Exiting process.");
+            process::exit(2);
+        });
+
+    if parsed_arguments.has("--help") {
+        print!("{}", Arguments::help_text(VERSION));
+        process::exit(0);
+    }
 
-    if !ensure_directories_exist() {
-        Logger::error("Supplied a static directory that was not found. Exiting process.");
+    if parsed_arguments.has("--version") {
+        println!("rsrv {}", VERSION);
+        process::exit(0);
+    }
+
+    let config = Config::load().unwrap_or_else(|e| {
+        Logger::fatal(&e);
+        Logger::fatal("Invalid configuration. Exiting process.");
         process::exit(1);
+    });
+
+    Logger::set_level(config.log_level);
+    echo_rsrv_process_started();
+
+    echo_config_file(&config);
+    echo_route_table(&config.sites);
+    echo_proxies(&config);
+    echo_mocks(&config);
+    echo_rules(&config.rules);
+    echo_cache_policy(&config.cache_policy);
+    if config.uploads.enabled {
+        Logger::info(&config.uploads.describe());
+    }
+    if config.uploads.manage {
+        Logger::info(
+            "File management enabled: DELETE removes files and directories, POST /__rsrv/move moves them, bearer token required",
+        );
+    }
+    if config.webdav.enabled {
+        Logger::info(&config.webdav.describe());
+    }
+    if config.tls.enabled() {
+        Logger::info(&config.tls.describe());
+    }
+    if config.http2.enabled {
+        Logger::info(&config.http2.describe());
+    }
+    Logger::info(&config.timeouts.describe());
+    if config.basic_auth.htpasswd.is_some() {
+        Logger::info(&config.basic_auth.describe());
+    }
+    if !config.access.is_empty() {
+        Logger::info(&format!("Access rules: {}", config.access.describe()));
+    }
+    if config.rate_limit.is_enabled() {
+        Logger::info(&config.rate_limit.describe());
+    }
+    if config.live_reload {
+        Logger::info("Live reload enabled, html responses subscribe to /__rsrv/live-reload");
     }
 
-    let server = get_server().unwrap_or_else(|e| {
-        Logger::error(&format!(
+    let server = get_server(&config).unwrap_or_else(|e| {
+        Logger::fatal(&format!(
             "ExceptionThrown while setting up server.\n{:#?}",
             e
         ));
         process::exit(1);
     });
 
-    listen(server);
+    if config.tls.uses_development_certificate() {
+        DevCertificate::ensure().unwrap_or_else(|e| {
+            Logger::fatal(&e);
+            Logger::fatal("Unable to set up the development certificate. Exiting process.");
+            process::exit(1);
+        });
+    }
+
+    let tls_config = Tls::server_config(&config).unwrap_or_else(|e| {
+        Logger::fatal(&e);
+        Logger::fatal("Unable to set up TLS. Exiting process.");
+        process::exit(1);
+    });
+
+    let redirect_server = config.tls.redirect_port.map(|redirect_port| {
+        get_redirect_server(&config, redirect_port).unwrap_or_else(|e| {
+            Logger::fatal(&format!(
+                "ExceptionThrown while setting up the HTTP redirect listener.\n{:#?}",
+                e
+            ));
+            process::exit(1);
+        })
+    });
+
+    let shared_config = SharedConfig::new(config);
+    ConfigWatcher::spawn(shared_config.clone());
+    if let Some(redirect_server) = redirect_server {
+        HttpsRedirect::spawn(redirect_server, shared_config.clone());
+    }
+    let live_reload = LiveReload::spawn(shared_config.clone());
+
+    EventLoop::listen(
+        server,
+        shared_config,
+        live_reload,
+        WebDavLocks::default(),
+        RateLimiter::default(),
+        tls_config,
+    );
 }
 
 pub fn echo_rsrv_process_started() {
@@ -48,55 +179,126 @@ pub fn echo_rsrv_process_started() {
     Logger::info(&static_server_started_log);
 }
 
-pub fn ensure_directories_exist() -> bool {
-    let directory_arguments = Arguments::find_directory_arguments();
-    Directory::ensure_directory_integrity(&directory_arguments)
+pub fn echo_config_file(config: &Config) {
+    match &config.config_file {
+        Some(config_file) => Logger::info(&format!(
+            "Loaded config file {} (overridden by RSRV_* environment variables and flags)",
+            config_file
+        )),
+        None => Logger::info("No config file found, using RSRV_* environment variables and flags"),
+    }
 }
 
-pub fn get_directories_as_paths() -> Vec<String> {
-    let directory_arguments = Arguments::find_directory_arguments();
-    let dirs_as_options = Directory::get_absolute_paths_from_dir_args(&directory_arguments);
-    let mut dirs_as_paths: Vec<String> = vec![];
+pub fn echo_route_table(virtual_hosts: &VirtualHosts) {
+    let mut mounts = virtual_hosts
+        .default_host
+        .static_directory_manager
+        .mounts
+        .clone();
+    mounts.sort_by_key(|mount| std::cmp::Reverse(mount.prefix.len()));
 
-    dirs_as_options
-        .into_iter()
-        .for_each(|dir_option| match dir_option {
-            Some(dir) => {
-                dirs_as_paths.push(dir);
+    Logger::info("Route table (longest prefix wins):");
+    for mount in &mounts {
+        Logger::info(&format!("  {}", mount.describe()));
+    }
+    for directory in &virtual_hosts
+        .default_host
+        .static_directory_manager
+        .directories
+    {
+        Logger::info(&format!("  / -> {}", directory));
+    }
+
+    if !virtual_hosts.hosts.is_empty() {
+        Logger::info(&format!(
+            "Virtual hosts (unmatched hostnames are served by {}):",
+            &virtual_hosts.default_host.hostname
+        ));
+        for virtual_host in &virtual_hosts.hosts {
+            for line in virtual_host.describe() {
+                Logger::info(&format!("  {}", line));
             }
-            None => (),
-        });
+        }
+    }
+}
+
+pub fn echo_proxies(config: &Config) {
+    if !config.proxies.is_empty() {
+        Logger::info("Proxies (longest prefix wins, checked before files):");
+    }
+    for proxy_route in &config.proxies {
+        Logger::info(&format!("  {}", proxy_route.describe()));
+    }
+}
+
+pub fn echo_mocks(config: &Config) {
+    if !config.mocks.is_empty() {
+        Logger::info("Mocks (first match wins, checked before proxies and files):");
+    }
+    for mock in &config.mocks {
+        Logger::info(&format!("  {}", mock.describe()));
+    }
+}
+
+pub fn echo_rules(rules: &Rules) {
+    if !rules.rules.is_empty() {
+        Logger::info("Rules (first match wins):");
+    }
+    for rule in &rules.rules {
+        let kind = match rule.kind {
+            RuleKind::Redirect(status) => format!("redirect {}", status),
+            RuleKind::Rewrite => String::from("rewrite"),
+        };
+        Logger::info(&format!(
+            "  {} -> {} [{}]",
+            &rule.source, &rule.destination, kind
+        ));
+    }
 
-    dirs_as_paths
+    if !rules.header_rules.rules.is_empty() {
+        Logger::info("Header rules (applied in order):");
+    }
+    for header_rule in &rules.header_rules.rules {
+        Logger::info(&format!(
+            "  {} {:?}",
+            &header_rule.source, &header_rule.operations
+        ));
+    }
 }
 
-pub fn get_server() -> Result<TcpListener, Box<dyn Error>> {
-    let port_argument = Arguments::find_port_argument_or_get_default();
-    let host_argument = Arguments::find_host_argument_or_get_default();
-    let host_and_port_string = format!("{host_argument}:{port_argument}");
+pub fn echo_cache_policy(cache_policy: &CachePolicy) {
+    Logger::info("Cache-Control policy:");
+    if cache_policy.detect_hashed_filenames {
+        Logger::info(&format!(
+            "  content-hashed files: {}",
+            &cache_policy.immutable_directives
+        ));
+    }
+    Logger::info(&format!(
+        "  html documents: {}",
+        &cache_policy.html_directives
+    ));
+    Logger::info(&format!(
+        "  everything else: {}",
+        &cache_policy.default_directives
+    ));
+}
+
+pub fn get_server(config: &Config) -> Result<TcpListener, Box<dyn Error>> {
+    let host_and_port_string = format!("{}:{}", config.host, config.port);
 
     let listener = TcpListener::bind(host_and_port_string)?;
     Ok(listener)
 }
 
-pub fn listen(server: TcpListener) {
-    for stream in server.incoming() {
-        match stream {
-            Ok(stream) => {
-                let args: Vec<String> = env::args().collect();
-                let dirs_as_paths = get_directories_as_paths();
-
-                ConnectionHandler::handle(
-                    stream,
-                    StaticDirectoryManager {
-                        directories: dirs_as_paths,
-                        backup_file: DefaultFile::get_default_file_or_default(&args),
-                    },
-                );
-            }
-            Err(e) => {
-                Logger::error(&format!("Stream Corrupted: {:#?}", e));
-            }
-        }
-    }
+pub fn get_redirect_server(
+    config: &Config,
+    redirect_port: u16,
+) -> Result<TcpListener, Box<dyn Error>> {
+    let listener = TcpListener::bind(format!("{}:{}", config.host, redirect_port))?;
+    Logger::info(&format!(
+        "Redirecting plain HTTP on {}:{} to HTTPS on port {}",
+        config.host, redirect_port, config.port
+    ));
+    Ok(listener)
 }
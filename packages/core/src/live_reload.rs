@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::client_stream::ClientStream;
+use crate::config::Config;
+use crate::config_watcher::SharedConfig;
+use crate::logger::Logger;
+
+pub const LIVE_RELOAD_PATH: &str = "/__rsrv/live-reload";
+
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+const CLIENT_WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+const IGNORED_DIRECTORIES: [&str; 3] = [".git", "node_modules", "target"];
+
+const CLIENT_SCRIPT: &str = r#"<script data-rsrv-live-reload>
+(() => {
+  const source = new EventSource("/__rsrv/live-reload");
+  source.addEventListener("reload", () => location.reload());
+  source.addEventListener("css", (event) => {
+    let swapped = false;
+    for (const link of document.querySelectorAll('link[rel="stylesheet"]')) {
+      const url = new URL(link.href);
+      if (url.origin === location.origin && url.pathname.endsWith(event.data)) {
+        url.searchParams.set("rsrv-reload", Date.now());
+        link.href = url.href;
+        swapped = true;
+      }
+    }
+    if (!swapped) location.reload();
+  });
+})();
+</script>
+"#;
+
+/// # LiveReload
+///
+/// Refreshes browsers when served files change, enabled with `--live-reload`.
+///
+/// Html responses get a small client script that subscribes to the
+/// Server-Sent Events endpoint at `/__rsrv/live-reload`. A watcher thread polls
+/// every served directory and, when something changes, sends either
+///
+/// - `event: css` with the changed stylesheet's path, when only stylesheets changed,
+///   which the client swaps in place without reloading the page
+/// - `event: reload` for anything else, which reloads the page
+///
+/// Subscribed streams are held here rather than by the connection handler,
+/// so they do not block other requests.
+///
+#[derive(Debug, Clone, Default)]
+pub struct LiveReload {
+    clients: Arc<Mutex<Vec<ClientStream>>>,
+}
+
+impl LiveReload {
+    pub fn spawn(shared_config: SharedConfig) -> Self {
+        let live_reload = LiveReload::default();
+        let watcher = live_reload.clone();
+
+        thread::spawn(move || {
+            let mut snapshot: Option<HashMap<String, SystemTime>> = None;
+            let mut last_event = Instant::now();
+
+            loop {
+                thread::sleep(POLL_INTERVAL);
+
+                let config = shared_config.current();
+                if !config.live_reload {
+                    snapshot = None;
+                    continue;
+                }
+
+                let current_snapshot = Self::snapshot(&config);
+                if let Some(previous_snapshot) = &snapshot {
+                    let changed_paths = Self::changed_paths(previous_snapshot, &current_snapshot);
+                    if !changed_paths.is_empty() {
+                        watcher.notify(&changed_paths);
+                        last_event = Instant::now();
+                    }
+                }
+                snapshot = Some(current_snapshot);
+
+                if last_event.elapsed() >= HEARTBEAT_INTERVAL {
+                    watcher.send(": heartbeat\n\n");
+                    last_event = Instant::now();
+                }
+            }
+        });
+
+        live_reload
+    }
+
+    ///
+    /// Answers a request for the event stream, keeping the stream open for future events.
+    ///
+    pub fn subscribe(&self, mut stream: ClientStream) {
+        let response_header = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\nretry: 1000\n\n";
+
+        if let Err(e) = stream.write_all(response_header.as_bytes()) {
+            Logger::error(&format!("Unable to open live reload stream. {}", e));
+            return;
+        }
+        let _ = stream.set_write_timeout(Some(CLIENT_WRITE_TIMEOUT));
+
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.push(stream);
+        }
+    }
+
+    ///
+    /// Adds the client script before `</body>`, or at the end of documents without one.
+    ///
+    pub fn inject_client_script(html: &str) -> String {
+        // Lowercased as ascii only, so positions stay those of the original html
+        match html.to_ascii_lowercase().rfind("</body>") {
+            Some(position) => format!(
+                "{}{}{}",
+                &html[..position],
+                CLIENT_SCRIPT,
+                &html[position..]
+            ),
+            None => format!("{}{}", html, CLIENT_SCRIPT),
+        }
+    }
+
+    fn notify(&self, changed_paths: &[String]) {
+        let only_stylesheets = changed_paths.iter().all(|path| path.ends_with(".css"));
+
+        if only_stylesheets {
+            for path in changed_paths {
+                Logger::info(&format!("Live reload: swapping stylesheet {}", path));
+                self.send(&format!("event: css\ndata: {}\n\n", path));
+            }
+        } else {
+            Logger::info(&format!(
+                "Live reload: {} changed, reloading pages",
+                changed_paths.join(", ")
+            ));
+            self.send("event: reload\ndata: \n\n");
+        }
+    }
+
+    ///
+    /// Writes to every subscribed client, dropping those that have gone away.
+    ///
+    fn send(&self, message: &str) {
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.retain_mut(|client| {
+                client
+                    .write_all(message.as_bytes())
+                    .and_then(|_| client.flush())
+                    .is_ok()
+            });
+        }
+    }
+}
+
+impl LiveReload {
+    ///
+    /// Maps the url path of every served file to its last modification time.
+    ///
+    fn snapshot(config: &Config) -> HashMap<String, SystemTime> {
+        let mut snapshot: HashMap<String, SystemTime> = HashMap::new();
+
+        let static_directory_managers = std::iter::once(&config.sites.default_host)
+            .chain(config.sites.hosts.iter())
+            .map(|virtual_host| &virtual_host.static_directory_manager);
+
+        for static_directory_manager in static_directory_managers {
+            for directory in &static_directory_manager.directories {
+                Self::walk(Path::new(directory), "", &mut snapshot);
+            }
+            for mount in &static_directory_manager.mounts {
+                let prefix = mount.prefix.trim_end_matches('/');
+                Self::walk(Path::new(&mount.directory), prefix, &mut snapshot);
+            }
+        }
+
+        snapshot
+    }
+
+    fn walk(directory: &Path, url_prefix: &str, snapshot: &mut HashMap<String, SystemTime>) {
+        let entries = match fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let url_path = format!("{}/{}", url_prefix, name);
+            let metadata = match entry.metadata() {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+
+            if metadata.is_dir() {
+                if !IGNORED_DIRECTORIES.contains(&name.as_str()) {
+                    Self::walk(&entry.path(), &url_path, snapshot);
+                }
+            } else if let Ok(modified) = metadata.modified() {
+                snapshot.insert(url_path, modified);
+            }
+        }
+    }
+
+    fn changed_paths(
+        previous_snapshot: &HashMap<String, SystemTime>,
+        current_snapshot: &HashMap<String, SystemTime>,
+    ) -> Vec<String> {
+        let mut changed_paths: Vec<String> = current_snapshot
+            .iter()
+            .filter(|(path, modified)| previous_snapshot.get(*path) != Some(modified))
+            .map(|(path, _)| path.clone())
+            .chain(
+                previous_snapshot
+                    .keys()
+                    .filter(|path| !current_snapshot.contains_key(*path))
+                    .cloned(),
+            )
+            .collect();
+        changed_paths.sort();
+        changed_paths
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn injects_before_the_closing_body_tag() {
+        let html = "<html><body><p>İstanbul ÇAĞ</p></BODY></html>";
+        assert_eq!(
+            LiveReload::inject_client_script(html),
+            format!(
+                "<html><body><p>İstanbul ÇAĞ</p>{}</BODY></html>",
+                CLIENT_SCRIPT
+            )
+        );
+    }
+
+    #[test]
+    fn appends_to_documents_without_a_body() {
+        assert_eq!(
+            LiveReload::inject_client_script("<p>İİİ</p>"),
+            format!("<p>İİİ</p>{}", CLIENT_SCRIPT)
+        );
+    }
+}
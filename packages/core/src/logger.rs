@@ -1,9 +1,53 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
 use colored::Colorize;
 
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+
+/// # LogLevel
+///
+/// The minimum severity written by the `Logger`, set with `--log-level=<level>`.
+/// Levels match the npm wrapper: `Info`, `Warn`, `Error`, `Fatal` and `Silent`.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Info = 0,
+    Warn = 1,
+    Error = 2,
+    Fatal = 3,
+    Silent = 4,
+}
+
+impl LogLevel {
+    pub fn parse(level: &str) -> Option<Self> {
+        match level.trim().to_lowercase().as_str() {
+            "info" => Some(LogLevel::Info),
+            "warn" => Some(LogLevel::Warn),
+            "error" => Some(LogLevel::Error),
+            "fatal" => Some(LogLevel::Fatal),
+            "silent" => Some(LogLevel::Silent),
+            _ => None,
+        }
+    }
+}
+
 pub struct Logger;
 
+impl Logger {
+    pub fn set_level(level: LogLevel) {
+        LOG_LEVEL.store(level as u8, Ordering::Relaxed);
+    }
+
+    fn enabled(level: LogLevel) -> bool {
+        level as u8 >= LOG_LEVEL.load(Ordering::Relaxed)
+    }
+}
+
 impl Logger {
     pub fn info(message: &str) {
+        if !Self::enabled(LogLevel::Info) {
+            return;
+        }
         println!(
             "{}: {}",
             "info".bold().truecolor(128, 172, 248),
@@ -12,6 +56,9 @@ impl Logger {
     }
 
     pub fn warn(message: &str) {
+        if !Self::enabled(LogLevel::Warn) {
+            return;
+        }
         println!(
             "{}: {}",
             "warn".bold().truecolor(255, 231, 110),
@@ -20,6 +67,9 @@ impl Logger {
     }
 
     pub fn error(message: &str) {
+        if !Self::enabled(LogLevel::Error) {
+            return;
+        }
         eprintln!(
             "{}: {}",
             "error".bold().truecolor(255, 78, 82),
@@ -27,7 +77,24 @@ impl Logger {
         );
     }
 
+    ///
+    /// Logs an error that stops the process.
+    ///
+    pub fn fatal(message: &str) {
+        if !Self::enabled(LogLevel::Fatal) {
+            return;
+        }
+        eprintln!(
+            "{}: {}",
+            "fatal".bold().truecolor(255, 78, 82),
+            message.bold()
+        );
+    }
+
     pub fn debug(message: &str) {
+        if !Self::enabled(LogLevel::Info) {
+            return;
+        }
         println!(
             "{}: {}",
             "debug".bold().truecolor(120, 219, 203),
@@ -0,0 +1,388 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::thread;
+use std::time::Duration;
+
+use serde_json::Value;
+
+use crate::client_stream::ClientStream;
+use crate::config::Config;
+use crate::connection::ConnectionError;
+use crate::filelike::FileLike;
+use crate::headers::Headers;
+use crate::logger::Logger;
+use crate::request::{HttpMethod, Request};
+use crate::response::Response;
+use crate::rules::RulePattern;
+use crate::status::StatusCode;
+
+const MOCK_KEYS: [&str; 7] = [
+    "method", "path", "file", "json", "status", "headers", "delay",
+];
+
+#[derive(Debug, Clone)]
+pub enum MockBody {
+    /// A json file read on every request, so edits show up without a reload
+    Fixture(String),
+    Json(Value),
+}
+
+/// # MockRoute
+///
+/// A stubbed api endpoint answering with a json fixture, declared as a `[[mocks]]`
+/// table in the config file.
+///
+/// ```toml
+/// [[mocks]]
+/// method = "GET"
+/// path = "/api/users/:id"
+/// file = "fixtures/users/:id.json"
+///
+/// [[mocks]]
+/// method = ["POST", "PUT"]
+/// path = "/api/users/**"
+/// json = { ok = false, reason = "read only" }
+/// status = 403
+/// headers = { "X-Mocked" = "true" }
+/// delay = 800
+/// ```
+///
+/// `path` is matched like a rule source, `:name` captures a segment and may be used in
+/// `file`. The body is either a fixture `file` or inline `json`. Without a `method` every
+/// method matches, `status` defaults to `200` and `delay` is a latency in milliseconds.
+///
+/// Mocks are checked in the order they are listed, before proxies and files.
+///
+#[derive(Debug, Clone)]
+pub struct MockRoute {
+    pub path: String,
+    pub methods: Vec<HttpMethod>,
+    pub body: MockBody,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub delay: Duration,
+    pattern: RulePattern,
+}
+
+impl MockRoute {
+    pub fn from_json(value: &Value) -> Result<Self, String> {
+        let table = value.as_object().ok_or_else(|| {
+            format!(
+                "MockRoute::from_json() Exception: Expected a table with a path and a file or json body, received {}",
+                value
+            )
+        })?;
+
+        if let Some(key) = table.keys().find(|key| !MOCK_KEYS.contains(&key.as_str())) {
+            return Err(format!(
+                "MockRoute::from_json() Exception: Unknown mock option {}",
+                key
+            ));
+        }
+
+        let path = value["path"].as_str().ok_or_else(|| {
+            String::from("MockRoute::from_json() Exception: Mock is missing a \"path\".")
+        })?;
+        if !path.starts_with('/') {
+            return Err(format!(
+                "MockRoute::from_json() Exception: Mock path {} must start with /",
+                path
+            ));
+        }
+
+        let methods = match &value["method"] {
+            Value::Null => vec![],
+            Value::String(method) => vec![Request::get_enumerated_method_from_string(method)],
+            Value::Array(methods) => {
+                let mut parsed_methods: Vec<HttpMethod> = vec![];
+                for method in methods {
+                    let method = method.as_str().ok_or_else(|| {
+                        format!(
+                            "MockRoute::from_json() Exception: Methods must be strings in {}",
+                            path
+                        )
+                    })?;
+                    parsed_methods.push(Request::get_enumerated_method_from_string(method));
+                }
+                parsed_methods
+            }
+            _ => {
+                return Err(format!(
+                "MockRoute::from_json() Exception: \"method\" must be a string or an array in {}",
+                path
+            ))
+            }
+        };
+
+        let body = match (&value["file"], &value["json"]) {
+            (Value::String(file), Value::Null) => MockBody::Fixture(file.clone()),
+            (Value::Null, Value::Null) => {
+                return Err(format!(
+                    "MockRoute::from_json() Exception: Mock {} needs a \"file\" or \"json\" body.",
+                    path
+                ))
+            }
+            (Value::Null, json) => MockBody::Json(json.clone()),
+            (Value::String(_), _) => {
+                return Err(format!(
+                    "MockRoute::from_json() Exception: Mock {} has both a \"file\" and a \"json\" body, expected one.",
+                    path
+                ))
+            }
+            _ => {
+                return Err(format!(
+                    "MockRoute::from_json() Exception: \"file\" must be a path in {}",
+                    path
+                ))
+            }
+        };
+
+        let status = match &value["status"] {
+            Value::Null => 200,
+            Value::Number(status) => status
+                .as_u64()
+                .filter(|status| (200..600).contains(status))
+                .map(|status| status as u16)
+                .ok_or_else(|| {
+                    format!(
+                        "MockRoute::from_json() Exception: \"status\" must be between 200 and 599 in {}",
+                        path
+                    )
+                })?,
+            _ => {
+                return Err(format!(
+                    "MockRoute::from_json() Exception: \"status\" must be a number in {}",
+                    path
+                ))
+            }
+        };
+
+        let headers = match &value["headers"] {
+            Value::Null => vec![],
+            Value::Object(headers) => {
+                let mut parsed_headers: Vec<(String, String)> = vec![];
+                for (key, header_value) in headers {
+                    let header_value = header_value.as_str().ok_or_else(|| {
+                        format!(
+                            "MockRoute::from_json() Exception: Header {} must be a string in {}",
+                            key, path
+                        )
+                    })?;
+                    parsed_headers.push((key.clone(), String::from(header_value)));
+                }
+                parsed_headers
+            }
+            _ => {
+                return Err(format!(
+                    "MockRoute::from_json() Exception: \"headers\" must be a table in {}",
+                    path
+                ))
+            }
+        };
+
+        let delay = match &value["delay"] {
+            Value::Null => Duration::ZERO,
+            Value::Number(milliseconds) => milliseconds
+                .as_u64()
+                .map(Duration::from_millis)
+                .ok_or_else(|| {
+                    format!(
+                        "MockRoute::from_json() Exception: \"delay\" must be a whole number of milliseconds in {}",
+                        path
+                    )
+                })?,
+            _ => {
+                return Err(format!(
+                    "MockRoute::from_json() Exception: \"delay\" must be a number of milliseconds in {}",
+                    path
+                ))
+            }
+        };
+
+        Ok(MockRoute {
+            path: String::from(path),
+            methods,
+            body,
+            status,
+            headers,
+            delay,
+            pattern: RulePattern::new(path),
+        })
+    }
+
+    ///
+    /// The path's captures when the mock answers this method and path.
+    ///
+    pub fn captures(&self, method: &HttpMethod, path: &str) -> Option<HashMap<String, String>> {
+        if !self.methods.is_empty() && !self.methods.contains(method) {
+            return None;
+        }
+        self.pattern.captures(path)
+    }
+
+    pub fn describe(&self) -> String {
+        let methods = if self.methods.is_empty() {
+            String::from("*")
+        } else {
+            self.methods
+                .iter()
+                .map(|method| method.as_str())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let body = match &self.body {
+            MockBody::Fixture(file) => file.clone(),
+            MockBody::Json(_) => String::from("inline json"),
+        };
+
+        let mut description = format!("{} {} -> {} [{}", methods, self.path, body, self.status);
+        if !self.delay.is_zero() {
+            description.push_str(&format!(", delay: {}ms", self.delay.as_millis()));
+        }
+        description.push(']');
+        description
+    }
+}
+
+/// # MockApi
+///
+/// A functional struct that answers requests matched by a `MockRoute`.
+///
+/// Bodies are parsed and serialized with serde_json, the same way `ConnectionError`
+/// builds its json blob, so a broken fixture answers `500` with a json error
+/// rather than sending invalid json to the client.
+///
+/// Delayed responses are written from their own thread, so the latency
+/// only applies to the mocked request.
+///
+pub struct MockApi;
+
+impl MockApi {
+    pub fn respond(
+        request: &Request,
+        mock: &MockRoute,
+        captures: &HashMap<String, String>,
+        mut stream: ClientStream,
+        config: &Config,
+    ) {
+        let (status, body) = match Self::render_body(&mock.body, captures) {
+            Ok(body) => (mock.status, body),
+            Err((status, e)) => {
+                Logger::error(&e);
+                (status, ConnectionError::new(e).get_error_as_json_string())
+            }
+        };
+
+        Logger::info(&format!(
+            "Mocking {} {} -> {}",
+            request.method().as_str(),
+            request.path(),
+            status
+        ));
+
+        let mut headers = Headers::new(vec![
+            (String::from("Content-Length"), body.len().to_string()),
+            (
+                String::from("Content-Type"),
+                Headers::format_content_type_header_based_on_request_path(".json"),
+            ),
+        ]);
+        if let Some(cors) = &config.cors {
+            headers.map.insert(
+                String::from("Access-Control-Allow-Origin"),
+                String::from(cors),
+            );
+        }
+        // Configured headers replace the defaults, i.e. a different Content-Type
+        for (key, value) in &mock.headers {
+            headers
+                .map
+                .retain(|existing_key, _| !existing_key.eq_ignore_ascii_case(key));
+            headers.map.insert(key.clone(), value.clone());
+        }
+
+        let response = Response::new(
+            String::from("HTTP/1.1"),
+            status,
+            String::from(StatusCode::reason_phrase(status)),
+            headers.map,
+            FileLike::TextFile(body),
+            false,
+        );
+
+        if mock.delay.is_zero() {
+            response.respond(&mut stream);
+        } else {
+            let delay = mock.delay;
+            thread::spawn(move || {
+                thread::sleep(delay);
+                response.respond(&mut stream);
+            });
+        }
+    }
+
+    ///
+    /// Serializes the body, or returns the status and message to answer with instead,
+    /// `404` for a fixture that does not exist and `500` for one that cannot be used.
+    ///
+    /// Captures reach the filesystem, so one with an empty, `.` or `..` segment is
+    /// answered `404` rather than letting `/api/../../secret.json` climb out of
+    /// the fixtures.
+    ///
+    fn render_body(
+        body: &MockBody,
+        captures: &HashMap<String, String>,
+    ) -> Result<String, (u16, String)> {
+        match body {
+            MockBody::Json(json) => Ok(json.to_string()),
+            MockBody::Fixture(file) => {
+                if let Some(capture) = captures
+                    .values()
+                    .find(|capture| !Self::is_confined_capture(capture))
+                {
+                    return Err((
+                        404,
+                        format!(
+                            "MockApi::respond() Exception: Refusing to read a fixture for the capture {}, it leaves the fixture's directory",
+                            capture
+                        ),
+                    ));
+                }
+                let path = RulePattern::substitute(file, captures);
+                let contents = fs::read_to_string(&path).map_err(|e| {
+                    let status = if e.kind() == ErrorKind::NotFound {
+                        404
+                    } else {
+                        500
+                    };
+                    (
+                        status,
+                        format!(
+                            "MockApi::respond() Exception: Unable to read the fixture {}. {}",
+                            path, e
+                        ),
+                    )
+                })?;
+                let json: Value = serde_json::from_str(&contents).map_err(|e| {
+                    (
+                        500,
+                        format!(
+                            "MockApi::respond() Exception: The fixture {} is not valid json. {}",
+                            path, e
+                        ),
+                    )
+                })?;
+                Ok(json.to_string())
+            }
+        }
+    }
+
+    fn is_confined_capture(capture: &str) -> bool {
+        !capture.contains('\\')
+            && !capture.contains('\0')
+            && capture
+                .split('/')
+                .all(|segment| !matches!(segment, "" | "." | ".."))
+    }
+}
@@ -0,0 +1,268 @@
+use serde_json::Value;
+
+use crate::access_control::{AccessRule, AccessRules};
+use crate::basic_auth::Htpasswd;
+use crate::cache_policy::CachePolicy;
+use crate::directory::Directory;
+
+/// # Mount
+///
+/// Maps a url prefix onto a directory on the local fs.
+///
+/// Mounts are supplied as `--mount=<prefix>:<directory>[,option...]`,
+/// or as `[[mounts]]` tables in the config file, i.e.
+///
+/// ```sh
+/// $ rsrv --mount=/static:./dist/assets,cache-control=31536000 --mount=/docs:./site,listing,fallback=index.html
+/// ```
+///
+/// Supported options are
+///
+/// - `listing` render an html index for directories without an `index.html`
+/// - `cache-control=<seconds|directives>` overrides the global cache policy for the mount,
+///   directives are separated by `;`, i.e. `cache-control=public;max-age=600`
+/// - `fallback=<file>` served (relative to the mount) when a file is not found
+/// - `allow=<cidr>` and `deny=<cidr>` restrict the mount to client addresses, in order,
+///   see `AccessRules`
+/// - `auth=<htpasswd file>` asks for Basic credentials from the file, see `BasicAuthSettings`
+///
+#[derive(Debug, Clone)]
+pub struct Mount {
+    pub prefix: String,
+    pub directory: String,
+    pub listing: bool,
+    pub cache_control: Option<String>,
+    pub fallback: Option<String>,
+    pub access: AccessRules,
+    pub basic_auth: Option<Htpasswd>,
+}
+
+impl Mount {
+    pub fn parse(mount_argument: &str) -> Result<Self, String> {
+        let mut options = mount_argument.split(',');
+        let route = options.next().unwrap_or_default();
+        let (prefix, directory) = route.split_once(':').ok_or_else(|| {
+            format!(
+                "Mount::parse() Exception: Expected <prefix>:<directory>, received {}",
+                mount_argument
+            )
+        })?;
+
+        if directory.is_empty() {
+            return Err(format!(
+                "Mount::parse() Exception: Mount {} is missing a directory.",
+                mount_argument
+            ));
+        }
+
+        let mut mount = Mount {
+            prefix: Self::normalize_prefix(prefix),
+            directory: Directory::get_absolute_path(directory),
+            listing: false,
+            cache_control: None,
+            fallback: None,
+            access: AccessRules::default(),
+            basic_auth: None,
+        };
+
+        for option in options {
+            match option.split_once('=') {
+                None if option == "listing" => mount.listing = true,
+                Some(("cache-control", directives)) => {
+                    mount.cache_control = Some(CachePolicy::parse_directives(directives)?)
+                }
+                Some(("fallback", fallback)) => {
+                    mount.fallback = Some(String::from(fallback.trim_start_matches('/')))
+                }
+                Some(("auth", htpasswd)) => mount.basic_auth = Some(Htpasswd::load(htpasswd)?),
+                Some((action @ ("allow" | "deny"), cidr)) => mount
+                    .access
+                    .rules
+                    .push(AccessRule::parse(&format!("{} {}", action, cidr))?),
+                _ => {
+                    return Err(format!(
+                        "Mount::parse() Exception: Unknown mount option {} in {}",
+                        option, mount_argument
+                    ))
+                }
+            }
+        }
+
+        Ok(mount)
+    }
+
+    ///
+    /// Parses a `[[mounts]]` table from the config file.
+    ///
+    /// ```toml
+    /// [[mounts]]
+    /// prefix = "/docs"
+    /// directory = "./site"
+    /// listing = true
+    /// cache_control = "public, max-age=600"
+    /// fallback = "index.html"
+    /// access = ["allow 10.0.0.0/8"]
+    /// basic_auth = ".htpasswd"
+    /// ```
+    ///
+    pub fn from_json(value: &Value) -> Result<Self, String> {
+        let table = value.as_object().ok_or_else(|| {
+            format!(
+                "Mount::from_json() Exception: Expected a table with a prefix and directory, received {}",
+                value
+            )
+        })?;
+
+        if let Some(key) = table.keys().find(|key| {
+            ![
+                "prefix",
+                "directory",
+                "listing",
+                "cache_control",
+                "fallback",
+                "access",
+                "basic_auth",
+            ]
+            .contains(&key.as_str())
+        }) {
+            return Err(format!(
+                "Mount::from_json() Exception: Unknown mount option {}",
+                key
+            ));
+        }
+
+        let prefix = value["prefix"].as_str().ok_or_else(|| {
+            String::from("Mount::from_json() Exception: Mount is missing a \"prefix\".")
+        })?;
+        let directory = value["directory"]
+            .as_str()
+            .filter(|directory| !directory.is_empty())
+            .ok_or_else(|| {
+                format!(
+                    "Mount::from_json() Exception: Mount {} is missing a \"directory\".",
+                    prefix
+                )
+            })?;
+
+        let listing = match &value["listing"] {
+            Value::Null => false,
+            Value::Bool(listing) => *listing,
+            _ => {
+                return Err(format!(
+                    "Mount::from_json() Exception: \"listing\" must be true or false for mount {}",
+                    prefix
+                ))
+            }
+        };
+
+        let cache_control = match &value["cache_control"] {
+            Value::Null => None,
+            Value::String(directives) => Some(CachePolicy::parse_directives(directives)?),
+            Value::Number(max_age) => Some(CachePolicy::parse_directives(&max_age.to_string())?),
+            _ => {
+                return Err(format!(
+                    "Mount::from_json() Exception: \"cache_control\" must be a string for mount {}",
+                    prefix
+                ))
+            }
+        };
+
+        let fallback = match &value["fallback"] {
+            Value::Null => None,
+            Value::String(fallback) => Some(String::from(fallback.trim_start_matches('/'))),
+            _ => {
+                return Err(format!(
+                    "Mount::from_json() Exception: \"fallback\" must be a string for mount {}",
+                    prefix
+                ))
+            }
+        };
+
+        let access = match &value["access"] {
+            Value::Null => AccessRules::default(),
+            access => AccessRules::from_json(access)?,
+        };
+
+        let basic_auth = match &value["basic_auth"] {
+            Value::Null => None,
+            Value::String(htpasswd) => Some(Htpasswd::load(htpasswd)?),
+            _ => {
+                return Err(format!(
+                    "Mount::from_json() Exception: \"basic_auth\" must be the path of an htpasswd file for mount {}",
+                    prefix
+                ))
+            }
+        };
+
+        Ok(Mount {
+            prefix: Self::normalize_prefix(prefix),
+            directory: Directory::get_absolute_path(directory),
+            listing,
+            cache_control,
+            fallback,
+            access,
+            basic_auth,
+        })
+    }
+
+    fn normalize_prefix(prefix: &str) -> String {
+        let trimmed_prefix = prefix.trim_matches('/');
+        if trimmed_prefix.is_empty() {
+            String::from("/")
+        } else {
+            format!("/{}", trimmed_prefix)
+        }
+    }
+}
+
+impl Mount {
+    ///
+    /// If the request path lives under this mount,
+    /// returns the remainder of the path relative to the mount's directory.
+    ///
+    /// Matching happens on whole path segments, so `/static` matches
+    /// `/static` and `/static/app.js` but not `/statically.js`.
+    ///
+    pub fn strip_prefix<'a>(&self, path: &'a str) -> Option<&'a str> {
+        if self.prefix == "/" {
+            return Some(path);
+        }
+
+        let remainder = path.strip_prefix(self.prefix.as_str())?;
+        if remainder.is_empty() || remainder.starts_with('/') {
+            Some(remainder)
+        } else {
+            None
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let mut options: Vec<String> = vec![];
+        if self.listing {
+            options.push(String::from("listing"));
+        }
+        if let Some(cache_control) = &self.cache_control {
+            options.push(format!("cache-control: {}", cache_control));
+        }
+        if let Some(fallback) = &self.fallback {
+            options.push(format!("fallback: {}", fallback));
+        }
+        if !self.access.is_empty() {
+            options.push(format!("access: {}", self.access.describe()));
+        }
+        if let Some(htpasswd) = &self.basic_auth {
+            options.push(format!("basic auth: {}", htpasswd.path));
+        }
+
+        if options.is_empty() {
+            format!("{} -> {}", self.prefix, self.directory)
+        } else {
+            format!(
+                "{} -> {} [{}]",
+                self.prefix,
+                self.directory,
+                options.join(", ")
+            )
+        }
+    }
+}
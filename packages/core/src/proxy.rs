@@ -0,0 +1,795 @@
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::Value;
+
+use crate::client_stream::ClientStream;
+use crate::config::Config;
+use crate::error_page::ErrorPage;
+use crate::logger::Logger;
+use crate::rate_limit::ConnectionSlot;
+use crate::request::Request;
+use crate::timeouts::{Timeouts, TransferRate};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a TLS WebSocket tunnel waits on one side before checking the other
+const TUNNEL_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Headers that describe a single connection, and so are never forwarded.
+const HOP_BY_HOP_HEADERS: [&str; 9] = [
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// # ProxyRoute
+///
+/// Forwards requests under a url prefix to an HTTP/1.1 upstream.
+///
+/// Proxies are supplied as `--proxy=<prefix>:<upstream>[,timeout=<seconds>]`, or as
+/// `[[proxies]]` tables with a `prefix`, `upstream` and `timeout` in the config file, i.e.
+///
+/// ```sh
+/// $ rsrv dist --proxy=/api:http://127.0.0.1:4000 --proxy=/auth:http://localhost:5000/v2,timeout=5
+/// ```
+///
+/// An upstream without a path receives the request path as is, `/api/users` => `/api/users`.
+/// An upstream with a path has the prefix replaced by it, `/auth/login` => `/v2/login`.
+/// `timeout` bounds every read from and write to the upstream, it defaults to 30 seconds.
+///
+#[derive(Debug, Clone)]
+pub struct ProxyRoute {
+    pub prefix: String,
+    pub upstream: String,
+    pub timeout: Duration,
+    host: String,
+    port: u16,
+    base_path: String,
+}
+
+impl ProxyRoute {
+    pub fn parse(proxy_argument: &str) -> Result<Self, String> {
+        let (prefix, target) = proxy_argument.split_once(':').ok_or_else(|| {
+            format!(
+                "ProxyRoute::parse() Exception: Expected <prefix>:<upstream>, received {}",
+                proxy_argument
+            )
+        })?;
+
+        let mut options = target.split(',');
+        let upstream = options.next().unwrap_or_default();
+        let mut proxy_route = Self::new(prefix, upstream)?;
+
+        for option in options {
+            match option.split_once('=') {
+                Some(("timeout", seconds)) => proxy_route.timeout = Self::parse_timeout(seconds)?,
+                _ => {
+                    return Err(format!(
+                        "ProxyRoute::parse() Exception: Unknown proxy option {} in {}",
+                        option, proxy_argument
+                    ))
+                }
+            }
+        }
+
+        Ok(proxy_route)
+    }
+
+    pub fn from_json(value: &Value) -> Result<Self, String> {
+        let table = value.as_object().ok_or_else(|| {
+            format!(
+                "ProxyRoute::from_json() Exception: Expected a table with a prefix and upstream, received {}",
+                value
+            )
+        })?;
+
+        if let Some(key) = table
+            .keys()
+            .find(|key| !["prefix", "upstream", "timeout"].contains(&key.as_str()))
+        {
+            return Err(format!(
+                "ProxyRoute::from_json() Exception: Unknown proxy option {}",
+                key
+            ));
+        }
+
+        let prefix = value["prefix"].as_str().ok_or_else(|| {
+            String::from("ProxyRoute::from_json() Exception: Proxy is missing a \"prefix\".")
+        })?;
+        let upstream = value["upstream"].as_str().ok_or_else(|| {
+            format!(
+                "ProxyRoute::from_json() Exception: Proxy {} is missing an \"upstream\".",
+                prefix
+            )
+        })?;
+
+        let mut proxy_route = Self::new(prefix, upstream)?;
+        match &value["timeout"] {
+            Value::Null => (),
+            Value::Number(seconds) => {
+                proxy_route.timeout = Self::parse_timeout(&seconds.to_string())?
+            }
+            _ => {
+                return Err(format!(
+                    "ProxyRoute::from_json() Exception: \"timeout\" must be a number of seconds for proxy {}",
+                    prefix
+                ))
+            }
+        }
+
+        Ok(proxy_route)
+    }
+
+    fn new(prefix: &str, upstream: &str) -> Result<Self, String> {
+        let address = match upstream.strip_prefix("http://") {
+            Some(address) => address,
+            None if upstream.starts_with("https://") => {
+                return Err(format!(
+                    "ProxyRoute::new() Exception: Upstream {} uses https, only http upstreams are supported.",
+                    upstream
+                ))
+            }
+            None => {
+                return Err(format!(
+                    "ProxyRoute::new() Exception: Upstream {} must be an http:// url.",
+                    upstream
+                ))
+            }
+        };
+
+        let (authority, base_path) = match address.find('/') {
+            Some(position) => (
+                &address[..position],
+                address[position..].trim_end_matches('/'),
+            ),
+            None => (address, ""),
+        };
+
+        let (host, port) = if authority.starts_with('[') {
+            match authority.split_once("]:") {
+                Some((host, port)) => (format!("{}]", host), Some(port)),
+                None => (String::from(authority), None),
+            }
+        } else {
+            match authority.rsplit_once(':') {
+                Some((host, port)) => (String::from(host), Some(port)),
+                None => (String::from(authority), None),
+            }
+        };
+
+        let port = match port {
+            Some(port) => port.parse::<u16>().map_err(|_| {
+                format!(
+                    "ProxyRoute::new() Exception: Upstream {} has an invalid port.",
+                    upstream
+                )
+            })?,
+            None => 80,
+        };
+
+        if host.is_empty() || host == "[]" {
+            return Err(format!(
+                "ProxyRoute::new() Exception: Upstream {} is missing a host.",
+                upstream
+            ));
+        }
+
+        let trimmed_prefix = prefix.trim_matches('/');
+        Ok(ProxyRoute {
+            prefix: format!("/{}", trimmed_prefix),
+            upstream: String::from(upstream),
+            timeout: DEFAULT_TIMEOUT,
+            host,
+            port,
+            base_path: String::from(base_path),
+        })
+    }
+
+    fn parse_timeout(seconds: &str) -> Result<Duration, String> {
+        match seconds.trim().parse::<u64>() {
+            Ok(seconds) if seconds > 0 => Ok(Duration::from_secs(seconds)),
+            _ => Err(format!(
+                "ProxyRoute::parse_timeout() Exception: Expected a positive number of seconds, received {}",
+                seconds
+            )),
+        }
+    }
+}
+
+impl ProxyRoute {
+    ///
+    /// Whether the request path lives under this proxy's prefix, on whole path segments.
+    ///
+    pub fn matches(&self, path: &str) -> bool {
+        if self.prefix == "/" {
+            return true;
+        }
+
+        match path.strip_prefix(self.prefix.as_str()) {
+            Some(remainder) => remainder.is_empty() || remainder.starts_with('/'),
+            None => false,
+        }
+    }
+
+    pub fn upstream_target(&self, path: &str, query: Option<&String>) -> String {
+        let path = if self.base_path.is_empty() {
+            String::from(path)
+        } else {
+            let remainder = match self.prefix.as_str() {
+                "/" => path,
+                prefix => path.strip_prefix(prefix).unwrap_or(path),
+            };
+            format!("{}{}", self.base_path, remainder)
+        };
+
+        let path = if path.is_empty() {
+            String::from("/")
+        } else {
+            path
+        };
+
+        match query {
+            Some(query) => format!("{}?{}", path, query),
+            None => path,
+        }
+    }
+
+    pub fn authority(&self) -> String {
+        if self.port == 80 {
+            self.host.clone()
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} -> {} [timeout: {}s]",
+            self.prefix,
+            self.upstream,
+            self.timeout.as_secs()
+        )
+    }
+}
+
+/// # ReverseProxy
+///
+/// A functional struct that forwards a request to a `ProxyRoute`'s upstream
+/// and relays the response back to the client.
+///
+/// Bodies are streamed in both directions rather than buffered. Hop-by-hop headers
+/// are dropped, `X-Forwarded-For`, `X-Forwarded-Host`, `X-Forwarded-Proto` and `Forwarded`
+/// are added, and the upstream connection is closed after each request.
+/// Upstreams that cannot be reached answer `502 Bad Gateway`,
+/// upstreams that do not answer within the timeout `504 Gateway Timeout`. The client's
+/// body is read within the `body_read` timeout, as it is for any other request.
+///
+/// WebSocket handshakes keep their `Upgrade` and `Connection` headers. Once the upstream
+/// answers `101 Switching Protocols` the client and upstream connections are spliced
+/// together on their own threads, without a timeout, until either side closes. An open
+/// tunnel keeps counting against its client's `max_connections_per_ip`.
+///
+pub struct ReverseProxy;
+
+impl ReverseProxy {
+    pub fn forward(
+        request: &Request,
+        proxy_route: &ProxyRoute,
+        stream: &mut ClientStream,
+        config: &Config,
+        slot: Option<ConnectionSlot>,
+    ) {
+        let target = proxy_route.upstream_target(request.path(), request.query());
+        let websocket_upgrade = request.is_websocket_upgrade();
+        Logger::info(&format!(
+            "Proxying {}{} {} -> {}{}",
+            if websocket_upgrade { "WebSocket " } else { "" },
+            request.method().as_str(),
+            request.path(),
+            proxy_route.authority(),
+            target
+        ));
+
+        let mut upstream = match Self::connect(proxy_route) {
+            Ok(upstream) => upstream,
+            Err(e) => {
+                return Self::respond_with_upstream_error(request, proxy_route, e, stream, config)
+            }
+        };
+
+        if let Err(e) = Self::send_request(
+            request,
+            proxy_route,
+            &target,
+            websocket_upgrade,
+            stream,
+            &mut upstream,
+            config.timeouts.min_rate,
+        ) {
+            return Self::respond_with_upstream_error(request, proxy_route, e, stream, config);
+        }
+
+        let mut upstream_reader = BufReader::new(&upstream);
+        let (status, response_head) =
+            match Self::read_response_head(&mut upstream_reader, websocket_upgrade) {
+                Ok(response) => response,
+                Err(e) => {
+                    return Self::respond_with_upstream_error(
+                        request,
+                        proxy_route,
+                        e,
+                        stream,
+                        config,
+                    )
+                }
+            };
+
+        if websocket_upgrade && status == 101 {
+            // Frames the upstream sent right after its handshake are already buffered
+            let upstream_frames = upstream_reader.buffer().to_vec();
+            return Self::open_tunnel(
+                request,
+                proxy_route,
+                &response_head,
+                &upstream_frames,
+                stream,
+                upstream,
+                slot,
+            );
+        }
+
+        if let Err(e) = stream
+            .write_all(response_head.as_bytes())
+            .and_then(|_| io::copy(&mut upstream_reader, stream))
+            .and_then(|_| stream.flush())
+        {
+            Logger::warn(&format!(
+                "ReverseProxy::forward() Exception: Response from {} was cut short. {}",
+                proxy_route.upstream, e
+            ));
+        }
+    }
+
+    fn connect(proxy_route: &ProxyRoute) -> io::Result<TcpStream> {
+        let host = proxy_route
+            .host
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+        let connect_timeout = CONNECT_TIMEOUT.min(proxy_route.timeout);
+
+        let mut last_error = io::Error::new(
+            ErrorKind::NotFound,
+            format!("{} did not resolve to an address", proxy_route.host),
+        );
+        for address in (host, proxy_route.port).to_socket_addrs()? {
+            match TcpStream::connect_timeout(&address, connect_timeout) {
+                Ok(upstream) => {
+                    upstream.set_read_timeout(Some(proxy_route.timeout))?;
+                    upstream.set_write_timeout(Some(proxy_route.timeout))?;
+                    return Ok(upstream);
+                }
+                Err(e) => last_error = e,
+            }
+        }
+
+        Err(last_error)
+    }
+
+    fn send_request(
+        request: &Request,
+        proxy_route: &ProxyRoute,
+        target: &str,
+        websocket_upgrade: bool,
+        stream: &mut ClientStream,
+        upstream: &mut TcpStream,
+        min_rate: Option<u64>,
+    ) -> io::Result<()> {
+        let client_address = stream
+            .peer_addr()
+            .map(|address| address.ip().to_string())
+            .unwrap_or_else(|_| String::from("unknown"));
+        let original_host = request.headers().get_header_by_key("Host").cloned();
+        let connection_tokens =
+            Self::connection_tokens(request.headers().get_header_by_key("Connection"));
+
+        let mut head = format!("{} {} HTTP/1.1\r\n", request.method().as_str(), target);
+        let mut forwarded_for: Option<String> = None;
+        let mut forwarded: Option<String> = None;
+
+        for (key, value) in &request.headers().map {
+            let lowercase_key = key.to_lowercase();
+            match lowercase_key.as_str() {
+                "host" => continue,
+                "x-forwarded-for" => forwarded_for = Some(value.clone()),
+                "forwarded" => forwarded = Some(value.clone()),
+                "x-forwarded-host" | "x-forwarded-proto" => continue,
+                "transfer-encoding" => head.push_str(&format!("{}: {}\r\n", key, value)),
+                "upgrade" if websocket_upgrade => head.push_str(&format!("{}: {}\r\n", key, value)),
+                _ if HOP_BY_HOP_HEADERS.contains(&lowercase_key.as_str())
+                    || connection_tokens.contains(&lowercase_key) =>
+                {
+                    continue
+                }
+                _ => head.push_str(&format!("{}: {}\r\n", key, value)),
+            }
+        }
+
+        let forwarded_for = match forwarded_for {
+            Some(forwarded_for) => format!("{}, {}", forwarded_for, client_address),
+            None => client_address.clone(),
+        };
+        let forwarded_element = match &original_host {
+            Some(host) => format!(
+                "for={};host=\"{}\";proto={}",
+                Self::forwarded_node(&client_address),
+                host,
+                stream.scheme()
+            ),
+            None => format!(
+                "for={};proto={}",
+                Self::forwarded_node(&client_address),
+                stream.scheme()
+            ),
+        };
+        let forwarded = match forwarded {
+            Some(forwarded) => format!("{}, {}", forwarded, forwarded_element),
+            None => forwarded_element,
+        };
+
+        head.push_str(&format!("Host: {}\r\n", proxy_route.authority()));
+        head.push_str(&format!("X-Forwarded-For: {}\r\n", forwarded_for));
+        if let Some(host) = &original_host {
+            head.push_str(&format!("X-Forwarded-Host: {}\r\n", host));
+        }
+        head.push_str(&format!("X-Forwarded-Proto: {}\r\n", stream.scheme()));
+        head.push_str(&format!("Forwarded: {}\r\n", forwarded));
+        if websocket_upgrade {
+            head.push_str("Connection: Upgrade\r\n\r\n");
+        } else {
+            head.push_str("Connection: close\r\n\r\n");
+        }
+
+        upstream.write_all(head.as_bytes())?;
+
+        let mut client_body = TransferRate::new(request.buffered_body().chain(&*stream), min_rate);
+        if request.is_chunked() {
+            Self::relay_chunked_body(&mut BufReader::new(client_body), upstream)?;
+        } else if let Some(content_length) = request.content_length() {
+            let copied = io::copy(
+                &mut (&mut client_body).take(content_length as u64),
+                upstream,
+            )?;
+            if copied < content_length as u64 {
+                return Err(io::Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "client closed the connection before sending the whole body",
+                ));
+            }
+        }
+
+        upstream.flush()
+    }
+
+    ///
+    /// Copies a chunked body through unchanged, stopping after the last chunk and its trailers.
+    ///
+    fn relay_chunked_body(reader: &mut impl BufRead, upstream: &mut impl Write) -> io::Result<()> {
+        loop {
+            let mut size_line = String::new();
+            if reader.read_line(&mut size_line)? == 0 {
+                return Err(io::Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "chunked body ended early",
+                ));
+            }
+            upstream.write_all(size_line.as_bytes())?;
+
+            let size = size_line
+                .trim()
+                .split(';')
+                .next()
+                .unwrap_or_default()
+                .trim();
+            let size = usize::from_str_radix(size, 16).map_err(|_| {
+                io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("invalid chunk size {}", size),
+                )
+            })?;
+
+            if size == 0 {
+                loop {
+                    let mut trailer_line = String::new();
+                    if reader.read_line(&mut trailer_line)? == 0 {
+                        return Ok(());
+                    }
+                    upstream.write_all(trailer_line.as_bytes())?;
+                    if trailer_line.trim_end().is_empty() {
+                        return Ok(());
+                    }
+                }
+            }
+
+            // The chunk's data is followed by a CRLF
+            io::copy(&mut reader.by_ref().take(size as u64 + 2), upstream)?;
+        }
+    }
+
+    ///
+    /// Reads the upstream's status line and headers, returning the status code
+    /// and the head ready to relay.
+    ///
+    /// The body is relayed byte for byte, so `Transfer-Encoding` is kept
+    /// while every other hop-by-hop header is dropped. A `101` answer to a
+    /// WebSocket handshake keeps its `Upgrade` header as well.
+    ///
+    fn read_response_head(
+        upstream_reader: &mut impl BufRead,
+        websocket_upgrade: bool,
+    ) -> io::Result<(u16, String)> {
+        let mut status_line = String::new();
+        upstream_reader.read_line(&mut status_line)?;
+        if !status_line.starts_with("HTTP/") {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "upstream sent an invalid status line {:?}",
+                    status_line.trim_end()
+                ),
+            ));
+        }
+
+        let mut header_lines: Vec<String> = vec![];
+        loop {
+            let mut header_line = String::new();
+            if upstream_reader.read_line(&mut header_line)? == 0 {
+                break;
+            }
+            let header_line = header_line.trim_end_matches(['\r', '\n']);
+            if header_line.is_empty() {
+                break;
+            }
+            header_lines.push(String::from(header_line));
+        }
+
+        let connection_tokens = Self::connection_tokens(
+            header_lines
+                .iter()
+                .filter_map(|line| line.split_once(':'))
+                .find(|(key, _)| key.trim().eq_ignore_ascii_case("Connection"))
+                .map(|(_, value)| String::from(value.trim()))
+                .as_ref(),
+        );
+
+        let status = status_line
+            .trim_end()
+            .split_once(' ')
+            .map(|(_, status)| status)
+            .unwrap_or("502 Bad Gateway");
+        let status_code = status
+            .split(' ')
+            .next()
+            .and_then(|code| code.parse::<u16>().ok())
+            .unwrap_or(502);
+        let switching_protocols = websocket_upgrade && status_code == 101;
+
+        let mut head = format!("HTTP/1.1 {}\r\n", status);
+        for header_line in &header_lines {
+            let key = header_line
+                .split_once(':')
+                .map(|(key, _)| key.trim().to_lowercase())
+                .unwrap_or_default();
+            let is_kept = key == "transfer-encoding" || (switching_protocols && key == "upgrade");
+            let is_hop_by_hop = !is_kept
+                && (HOP_BY_HOP_HEADERS.contains(&key.as_str()) || connection_tokens.contains(&key));
+            if !is_hop_by_hop {
+                head.push_str(&format!("{}\r\n", header_line));
+            }
+        }
+        if switching_protocols {
+            head.push_str("Connection: Upgrade\r\n\r\n");
+        } else {
+            head.push_str("Connection: close\r\n\r\n");
+        }
+
+        Ok((status_code, head))
+    }
+
+    ///
+    /// Relays the upstream's `101` to the client, then copies bytes between the two
+    /// connections on a thread per direction, so the listener moves on to other requests.
+    ///
+    /// When one side closes, the other is shut down for writing, which lets
+    /// the close propagate and ends the second thread.
+    ///
+    /// A TLS session can't be read and written from two threads at once, so over
+    /// TLS a single thread takes turns reading each side instead.
+    ///
+    fn open_tunnel(
+        request: &Request,
+        proxy_route: &ProxyRoute,
+        response_head: &str,
+        upstream_frames: &[u8],
+        stream: &mut ClientStream,
+        upstream: TcpStream,
+        slot: Option<ConnectionSlot>,
+    ) {
+        let mut upstream = ClientStream::Plain(upstream);
+        let opened = stream
+            .write_all(response_head.as_bytes())
+            .and_then(|_| stream.write_all(upstream_frames))
+            .and_then(|_| stream.flush())
+            .and_then(|_| upstream.write_all(request.buffered_body()))
+            .and_then(|_| upstream.flush())
+            .and_then(|_| {
+                for connection in [&*stream, &upstream] {
+                    connection.set_read_timeout(None)?;
+                    connection.set_write_timeout(None)?;
+                }
+                Ok((
+                    stream.try_clone()?,
+                    stream.try_clone()?,
+                    upstream.try_clone()?,
+                ))
+            });
+
+        let (client_reader, client_writer, upstream_writer) = match opened {
+            Ok(connections) => connections,
+            Err(e) => {
+                Logger::warn(&format!(
+                    "ReverseProxy::open_tunnel() Exception: WebSocket to {} closed during the handshake. {}",
+                    proxy_route.upstream, e
+                ));
+                return;
+            }
+        };
+
+        let description = format!("{} <-> {}", request.path(), proxy_route.upstream);
+        Logger::info(&format!("WebSocket opened {}", description));
+
+        if client_reader.is_tls() {
+            thread::spawn(move || {
+                Self::relay(client_reader, upstream);
+                Logger::info(&format!("WebSocket closed {}", description));
+                drop(slot);
+            });
+            return;
+        }
+
+        thread::spawn(move || Self::splice(client_reader, upstream_writer));
+        thread::spawn(move || {
+            Self::splice(upstream, client_writer);
+            Logger::info(&format!("WebSocket closed {}", description));
+            drop(slot);
+        });
+    }
+
+    fn splice(mut from: ClientStream, mut to: ClientStream) {
+        let _ = io::copy(&mut from, &mut to);
+        let _ = to.shutdown(Shutdown::Write);
+    }
+
+    ///
+    /// Copies bytes both ways on one thread, waiting on each side for at most
+    /// `TUNNEL_POLL_INTERVAL`, until either side closes or fails.
+    ///
+    fn relay(mut client: ClientStream, mut upstream: ClientStream) {
+        let mut buffer = [0; 16 * 1024];
+        let polling = [&client, &upstream]
+            .iter()
+            .try_for_each(|connection| connection.set_read_timeout(Some(TUNNEL_POLL_INTERVAL)));
+        if polling.is_err() {
+            return;
+        }
+
+        loop {
+            let relayed =
+                Self::relay_available(&mut client, &mut upstream, &mut buffer).and_then(|open| {
+                    Ok(open && Self::relay_available(&mut upstream, &mut client, &mut buffer)?)
+                });
+            if !matches!(relayed, Ok(true)) {
+                break;
+            }
+        }
+
+        let _ = client.shutdown(Shutdown::Both);
+        let _ = upstream.shutdown(Shutdown::Both);
+    }
+
+    ///
+    /// Copies what `from` has to offer, returning `false` once it has closed.
+    ///
+    fn relay_available(
+        from: &mut ClientStream,
+        to: &mut ClientStream,
+        buffer: &mut [u8],
+    ) -> io::Result<bool> {
+        match from.read(buffer) {
+            Ok(0) => Ok(false),
+            Ok(read) => to
+                .write_all(&buffer[..read])
+                .and_then(|_| to.flush())
+                .map(|_| true),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => Ok(true),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn connection_tokens(connection_header: Option<&String>) -> Vec<String> {
+        connection_header
+            .map(|connection_header| {
+                connection_header
+                    .split(',')
+                    .map(|token| token.trim().to_lowercase())
+                    .filter(|token| !token.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn forwarded_node(address: &str) -> String {
+        if address.contains(':') {
+            format!("\"[{}]\"", address)
+        } else {
+            String::from(address)
+        }
+    }
+
+    fn respond_with_upstream_error(
+        request: &Request,
+        proxy_route: &ProxyRoute,
+        e: io::Error,
+        stream: &mut ClientStream,
+        config: &Config,
+    ) {
+        let timed_out = matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock);
+        let (status, message) = if Timeouts::is_client_timeout(&e) {
+            (
+                408,
+                String::from("The client took too long to send the request body."),
+            )
+        } else if timed_out {
+            (
+                504,
+                format!(
+                    "The upstream {} did not respond in time.",
+                    proxy_route.upstream
+                ),
+            )
+        } else {
+            (
+                502,
+                format!(
+                    "The upstream {} could not be reached.",
+                    proxy_route.upstream
+                ),
+            )
+        };
+
+        Logger::error(&format!(
+            "ReverseProxy::forward() Exception: {} {} -> {} failed. {}",
+            request.method().as_str(),
+            request.path(),
+            proxy_route.upstream,
+            e
+        ));
+
+        ErrorPage::respond(
+            status,
+            message,
+            Some(request),
+            &config.sites.default_host.static_directory_manager,
+            &config.error_pages,
+            stream,
+        );
+    }
+}
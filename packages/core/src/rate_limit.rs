@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Buckets kept before full ones, clients that have gone quiet, are dropped
+const PRUNE_THRESHOLD: usize = 4096;
+
+/// # RateLimitSettings
+///
+/// How much each client IP may ask of rsrv, off by default. Set with `--rate-limit`,
+/// `--rate-burst`, `--max-connections-per-ip` and `--trusted-proxies`, the matching
+/// `RSRV_*` variables, or a `[rate_limit]` table, `0` turning a limit off.
+///
+/// ```toml
+/// [rate_limit]
+/// requests_per_second = 20
+/// burst = 40
+/// max_connections_per_ip = 64
+/// trusted_proxies = ["127.0.0.1", "10.0.0.2"]
+/// ```
+///
+/// - `requests_per_second` refills a token bucket per client, holding up to `burst`
+///   requests, the rate itself by default. A client with an empty bucket gets a
+///   `429 Too Many Requests` with `Retry-After`.
+/// - `max_connections_per_ip` caps the connections a peer address may have open at
+///   once, counted as they are accepted, further ones get a `429` and are closed.
+/// - `trusted_proxies` are peers whose `X-Forwarded-For` names the client the request
+///   limit applies to, for rsrv running behind a load balancer. Connections are
+///   always counted by peer address.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RateLimitSettings {
+    pub requests_per_second: Option<u64>,
+    pub burst: Option<u64>,
+    pub max_connections_per_ip: Option<u64>,
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl RateLimitSettings {
+    pub fn is_enabled(&self) -> bool {
+        self.requests_per_second.is_some() || self.max_connections_per_ip.is_some()
+    }
+
+    pub fn describe(&self) -> String {
+        let requests = match self.requests_per_second {
+            Some(rate) => format!("{} requests/s, bursts of {}", rate, self.capacity(rate)),
+            None => String::from("requests unlimited"),
+        };
+        let connections = match self.max_connections_per_ip {
+            Some(max_connections) => format!("{} connections", max_connections),
+            None => String::from("connections unlimited"),
+        };
+        let proxies = match self.trusted_proxies.is_empty() {
+            true => String::new(),
+            false => format!(
+                ", X-Forwarded-For trusted from {}",
+                self.trusted_proxies
+                    .iter()
+                    .map(|proxy| proxy.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+        };
+
+        format!(
+            "Rate limit per client IP: {}, {}{}",
+            requests, connections, proxies
+        )
+    }
+
+    ///
+    /// The client a request is limited as, its peer, or when the peer is a trusted proxy,
+    /// the nearest address in `X-Forwarded-For` that is not one.
+    ///
+    pub fn client_ip(&self, peer_ip: IpAddr, forwarded_for: Option<&String>) -> IpAddr {
+        if !self.trusted_proxies.contains(&peer_ip) {
+            return peer_ip;
+        }
+
+        let mut client_ip = peer_ip;
+        let forwarded_ips = forwarded_for
+            .into_iter()
+            .flat_map(|forwarded_for| forwarded_for.rsplit(','))
+            .map_while(Self::parse_forwarded_ip);
+        for forwarded_ip in forwarded_ips {
+            client_ip = forwarded_ip;
+            if !self.trusted_proxies.contains(&forwarded_ip) {
+                break;
+            }
+        }
+        client_ip
+    }
+
+    fn parse_forwarded_ip(address: &str) -> Option<IpAddr> {
+        let address = address.trim();
+        address.parse::<IpAddr>().ok().or_else(|| {
+            address
+                .parse::<SocketAddr>()
+                .ok()
+                .map(|address| address.ip())
+        })
+    }
+
+    fn capacity(&self, rate: u64) -> u64 {
+        self.burst.unwrap_or(rate)
+    }
+}
+
+/// # RateLimiter
+///
+/// The requests and connections of each client IP, shared across connections and
+/// kept in memory. Limits are read from the `RateLimitSettings` of each call, so a
+/// reloaded config applies straight away.
+///
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    state: Arc<Mutex<RateLimitState>>,
+}
+
+#[derive(Debug, Default)]
+struct RateLimitState {
+    buckets: HashMap<IpAddr, Bucket>,
+    connections: HashMap<IpAddr, u64>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    ///
+    /// Counts a connection from the peer, or returns `None` when it already has
+    /// `max_connections_per_ip` open. The connection is counted until the slot drops.
+    ///
+    pub fn open_connection(
+        &self,
+        peer_ip: IpAddr,
+        settings: &RateLimitSettings,
+    ) -> Option<ConnectionSlot> {
+        let mut state = self.current_state();
+        let connections = state.connections.entry(peer_ip).or_default();
+        if settings
+            .max_connections_per_ip
+            .is_some_and(|max_connections| *connections >= max_connections)
+        {
+            return None;
+        }
+
+        *connections += 1;
+        Some(ConnectionSlot {
+            rate_limiter: self.clone(),
+            peer_ip,
+        })
+    }
+
+    ///
+    /// Takes a request from the client's bucket, or returns how long until one refills.
+    ///
+    pub fn take(&self, client_ip: IpAddr, settings: &RateLimitSettings) -> Result<(), Duration> {
+        let Some(rate) = settings.requests_per_second else {
+            return Ok(());
+        };
+        let capacity = settings.capacity(rate) as f64;
+        let rate = rate as f64;
+        let now = Instant::now();
+
+        let mut state = self.current_state();
+        if state.buckets.len() > PRUNE_THRESHOLD {
+            state.buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate < capacity
+            });
+        }
+
+        let bucket = state.buckets.entry(client_ip).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        bucket.tokens =
+            (bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate).min(capacity);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+        }
+    }
+
+    fn close_connection(&self, peer_ip: IpAddr) {
+        let mut state = self.current_state();
+        if let Some(connections) = state.connections.get_mut(&peer_ip) {
+            *connections = connections.saturating_sub(1);
+            if *connections == 0 {
+                state.connections.remove(&peer_ip);
+            }
+        }
+    }
+
+    fn current_state(&self) -> MutexGuard<'_, RateLimitState> {
+        match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+/// # ConnectionSlot
+///
+/// A connection counted against its peer's `max_connections_per_ip`, released on drop.
+///
+#[derive(Debug)]
+pub struct ConnectionSlot {
+    rate_limiter: RateLimiter,
+    peer_ip: IpAddr,
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.rate_limiter.close_connection(self.peer_ip);
+    }
+}
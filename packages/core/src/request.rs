@@ -1,44 +1,78 @@
-use std::{
-    io::{prelude::*, BufReader},
-    net::TcpStream,
-};
+use std::io::{prelude::*, BufReader};
 
 use crate::headers::Headers;
 use crate::logger::Logger;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum HttpMethod {
     GET,
     POST,
     PUT,
     DELETE,
     OPTIONS,
+    HEAD,
+    PATCH,
+    /// Any other method, uppercased, i.e. `PROPFIND`
+    Other(String),
+}
+
+impl HttpMethod {
+    pub fn as_str(&self) -> &str {
+        match self {
+            HttpMethod::GET => "GET",
+            HttpMethod::POST => "POST",
+            HttpMethod::PUT => "PUT",
+            HttpMethod::DELETE => "DELETE",
+            HttpMethod::OPTIONS => "OPTIONS",
+            HttpMethod::HEAD => "HEAD",
+            HttpMethod::PATCH => "PATCH",
+            HttpMethod::Other(method) => method,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Request {
     path: String,
+    query: Option<String>,
     protocol: String,
     headers: Headers,
     method: HttpMethod,
+    buffered_body: Vec<u8>,
 }
 
 impl Request {
-    pub fn new(buffer: BufReader<&mut TcpStream>) -> Result<Self, String> {
-        let http_request: Vec<_> = buffer
-            .lines() // BufReader implements the std::io::BufRead trait, which provides the lines method. The lines method returns an iterator of Result<String, std::io::Error> by splitting the stream of data whenever it sees a newline byte.
-            .map(|result| match result {
-                Ok(line) => line,
+    ///
+    /// Reads the request head, i.e. from a `ClientStream`, or from the bytes the
+    /// event loop read ahead chained before it.
+    ///
+    pub fn new<R: Read>(mut buffer: BufReader<R>) -> Result<Self, String> {
+        let mut http_request: Vec<String> = vec![];
+        // The browser signals the end of an HTTP request head by sending two newline characters in a row,
+        // so we take lines until we get a line that is the empty string, or the stream ends.
+        loop {
+            let mut line = String::new();
+            match buffer.read_line(&mut line) {
+                Ok(0) => break,
+                Ok(_) => {
+                    let line = line.trim_end_matches(['\r', '\n']);
+                    if line.is_empty() {
+                        break;
+                    }
+                    http_request.push(String::from(line));
+                }
                 Err(err) => {
                     Logger::warn(&format!(
                         "Request::new() Exception: Buffer yielded invalid byte line.\nError: {:#?}",
                         err
                     ));
-                    String::new()
+                    break;
                 }
-            }) // To get each String, we map and unwrap each Result. The Result might be an error if the data isn’t valid UTF-8 or if there was a problem reading from the stream.
-            .take_while(|line| !line.is_empty()) // The browser signals the end of an HTTP request by sending two newline characters in a row, so to get one request from the stream, we take lines until we get a line that is the empty string.
-            .collect();
+            }
+        }
+
+        // Whatever the reader buffered past the head belongs to the body.
+        let buffered_body = buffer.buffer().to_vec();
 
         if http_request.len() == 0 {
             return Err(String::from(
@@ -59,7 +93,7 @@ impl Request {
         let method = *(request_line_split_on_whitespace
             .get(0)
             .expect("Proved request_line_split_on_whitespace.len() == 3"));
-        let mut path = *(request_line_split_on_whitespace
+        let path = *(request_line_split_on_whitespace
             .get(1)
             .expect("Proved request_line_split_on_whitespace.len() == 3"));
         let protocol = *(request_line_split_on_whitespace
@@ -71,15 +105,18 @@ impl Request {
             .map(|item| item.clone())
             .collect();
 
-        if path == "/" {
-            path = "/index.html";
-        }
+        let (path, query) = match path.split_once('?') {
+            Some((path, query)) => (path, Some(String::from(query))),
+            None => (path, None),
+        };
 
         Ok(Request {
             path: String::from(path),
+            query,
             protocol: String::from(protocol),
             method: Request::get_enumerated_method_from_string(method),
             headers: Headers::new(Headers::convert_raw_headers(raw_headers)),
+            buffered_body,
         })
     }
 
@@ -104,7 +141,15 @@ impl Request {
             return HttpMethod::OPTIONS;
         }
 
-        HttpMethod::GET
+        if method_as_str.to_lowercase() == "head" {
+            return HttpMethod::HEAD;
+        }
+
+        if method_as_str.to_lowercase() == "patch" {
+            return HttpMethod::PATCH;
+        }
+
+        HttpMethod::Other(method_as_str.to_uppercase())
     }
 
     pub fn method(&self) -> &HttpMethod {
@@ -115,6 +160,24 @@ impl Request {
         &self.path
     }
 
+    ///
+    /// Internally rewrites the request path, i.e. when a fallback file is served in its place.
+    ///
+    pub fn set_path(&mut self, path: String) {
+        self.path = path;
+    }
+
+    ///
+    /// The query string of the request target, without the leading `?`
+    ///
+    pub fn query(&self) -> Option<&String> {
+        self.query.as_ref()
+    }
+
+    pub fn set_query(&mut self, query: Option<String>) {
+        self.query = query;
+    }
+
     pub fn protocol(&self) -> &String {
         &self.protocol
     }
@@ -122,4 +185,44 @@ impl Request {
     pub fn headers(&self) -> &Headers {
         &self.headers
     }
+
+    ///
+    /// The start of the body, read from the stream along with the request head.
+    /// The rest of the body, if any, is still waiting on the stream.
+    ///
+    pub fn buffered_body(&self) -> &[u8] {
+        &self.buffered_body
+    }
+
+    pub fn content_length(&self) -> Option<usize> {
+        self.headers
+            .map
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case("Content-Length"))
+            .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+    }
+
+    pub fn is_chunked(&self) -> bool {
+        self.headers.map.iter().any(|(key, value)| {
+            key.eq_ignore_ascii_case("Transfer-Encoding")
+                && value.to_lowercase().contains("chunked")
+        })
+    }
+
+    ///
+    /// Whether the client asks to switch this connection to the WebSocket protocol,
+    /// `Connection: Upgrade` along with `Upgrade: websocket`.
+    ///
+    pub fn is_websocket_upgrade(&self) -> bool {
+        let has_header_token = |name: &str, token: &str| {
+            self.headers.map.iter().any(|(key, value)| {
+                key.eq_ignore_ascii_case(name)
+                    && value
+                        .split(',')
+                        .any(|value_token| value_token.trim().eq_ignore_ascii_case(token))
+            })
+        };
+
+        has_header_token("Connection", "upgrade") && has_header_token("Upgrade", "websocket")
+    }
 }
@@ -1,10 +1,11 @@
 use std::collections::HashMap;
 use std::io::prelude::*;
-use std::net::TcpStream;
 
+use crate::client_stream::ClientStream;
 use crate::filelike::FileLike;
 use crate::gzip::Gzip;
 use crate::logger::Logger;
+use crate::status::StatusCode;
 
 pub struct Response {
     compress: bool,
@@ -35,11 +36,58 @@ impl Response {
     }
 }
 
+impl Response {
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    pub fn insert_header(&mut self, key: &str, value: &str) {
+        self.headers.insert(String::from(key), String::from(value));
+    }
+}
+
+impl Response {
+    pub fn redirect(status: u16, location: &str) -> Self {
+        let mut headers = HashMap::new();
+        headers.insert(String::from("Location"), String::from(location));
+        headers.insert(String::from("Content-Length"), String::from("0"));
+
+        Self::new(
+            String::from("HTTP/1.1"),
+            status,
+            String::from(StatusCode::reason_phrase(status)),
+            headers,
+            FileLike::TextFile(String::new()),
+            false,
+        )
+    }
+}
+
+impl Response {
+    ///
+    /// A response with no body, i.e. a `408` sent before the request was read.
+    ///
+    pub fn empty(status: u16) -> Self {
+        let mut headers = HashMap::new();
+        headers.insert(String::from("Content-Length"), String::from("0"));
+        headers.insert(String::from("Connection"), String::from("close"));
+
+        Self::new(
+            String::from("HTTP/1.1"),
+            status,
+            String::from(StatusCode::reason_phrase(status)),
+            headers,
+            FileLike::TextFile(String::new()),
+            false,
+        )
+    }
+}
+
 impl Response {
     pub fn build_as_string(&self) -> String {
         let status_line = format!("{} {} {}", self.protocol, self.status, self.status_text);
         let headers_as_string = self.headers_as_string();
-        format!("{status_line}\r\n{headers_as_string}\r\n\r\n{}", &self.body)
+        format!("{status_line}\r\n{headers_as_string}\r\n{}", &self.body)
     }
 }
 
@@ -54,7 +102,7 @@ impl Response {
 }
 
 impl Response {
-    pub fn respond(&self, stream: &mut TcpStream) {
+    pub fn respond(&self, stream: &mut ClientStream) {
         match &self.body {
             FileLike::TextFile(_) => {
                 if self.compress {
@@ -0,0 +1,643 @@
+use std::collections::HashMap;
+use std::fs;
+
+use serde_json::Value;
+
+use crate::header_rules::HeaderRules;
+use crate::request::{HttpMethod, Request};
+use crate::virtual_host::VirtualHosts;
+
+/// # RulePattern
+///
+/// A url path pattern, matched segment by segment.
+///
+/// - `:name` captures a single segment as `name`
+/// - `*` matches any characters within a single segment, i.e. `/docs/*.html`
+/// - `**` as the final segment matches everything that remains (including nothing),
+///   captured as `splat`
+///
+#[derive(Debug, Clone)]
+pub struct RulePattern {
+    segments: Vec<String>,
+}
+
+impl RulePattern {
+    pub fn new(source: &str) -> Self {
+        RulePattern {
+            segments: Self::split_segments(source),
+        }
+    }
+
+    pub fn captures(&self, path: &str) -> Option<HashMap<String, String>> {
+        let path_segments = Self::split_segments(path);
+        let mut captures: HashMap<String, String> = HashMap::new();
+
+        for (index, segment) in self.segments.iter().enumerate() {
+            if segment == "**" {
+                captures.insert(
+                    String::from("splat"),
+                    path_segments[index.min(path_segments.len())..].join("/"),
+                );
+                return Some(captures);
+            }
+
+            let path_segment = path_segments.get(index)?;
+
+            if let Some(name) = segment.strip_prefix(':') {
+                captures.insert(String::from(name), path_segment.clone());
+            } else if !Self::glob_matches(segment, path_segment) {
+                return None;
+            }
+        }
+
+        if path_segments.len() == self.segments.len() {
+            Some(captures)
+        } else {
+            None
+        }
+    }
+
+    ///
+    /// Replaces `:name` placeholders in a template with captured values,
+    /// leaving placeholders without a capture as they are.
+    ///
+    pub fn substitute(template: &str, captures: &HashMap<String, String>) -> String {
+        let mut substituted = String::new();
+        let mut characters = template.chars().peekable();
+
+        while let Some(character) = characters.next() {
+            if character != ':' {
+                substituted.push(character);
+                continue;
+            }
+
+            let mut name = String::new();
+            while let Some(next) = characters.peek() {
+                if next.is_ascii_alphanumeric() || *next == '_' {
+                    name.push(*next);
+                    characters.next();
+                } else {
+                    break;
+                }
+            }
+
+            match captures.get(&name) {
+                Some(value) => substituted.push_str(value),
+                None => {
+                    substituted.push(':');
+                    substituted.push_str(&name);
+                }
+            }
+        }
+
+        substituted
+    }
+
+    fn split_segments(path: &str) -> Vec<String> {
+        path.split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(String::from)
+            .collect()
+    }
+
+    ///
+    /// Matches a single path segment against a pattern containing `*` wildcards.
+    ///
+    pub fn glob_matches(pattern: &str, segment: &str) -> bool {
+        let parts: Vec<&str> = pattern.split('*').collect();
+        if parts.len() == 1 {
+            return pattern == segment;
+        }
+
+        let first = parts[0];
+        let last = parts[parts.len() - 1];
+        if segment.len() < first.len() + last.len()
+            || !segment.starts_with(first)
+            || !segment.ends_with(last)
+        {
+            return false;
+        }
+
+        let mut remaining = &segment[first.len()..segment.len() - last.len()];
+
+        for part in &parts[1..parts.len() - 1] {
+            match remaining.find(part) {
+                Some(position) => remaining = &remaining[position + part.len()..],
+                None => return false,
+            }
+        }
+
+        true
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleKind {
+    Redirect(u16),
+    Rewrite,
+}
+
+/// # Rule
+///
+/// A single redirect or rewrite, optionally conditioned on the request host or method.
+///
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub source: String,
+    pub destination: String,
+    pub kind: RuleKind,
+    pub hosts: Vec<String>,
+    pub methods: Vec<HttpMethod>,
+    pattern: RulePattern,
+}
+
+impl Rule {
+    pub fn new(source: &str, destination: &str, kind: RuleKind) -> Self {
+        Rule {
+            source: String::from(source),
+            destination: String::from(destination),
+            kind,
+            hosts: vec![],
+            methods: vec![],
+            pattern: RulePattern::new(source),
+        }
+    }
+
+    pub fn from_json(value: &Value, default_kind: RuleKind) -> Result<Self, String> {
+        let source = value["source"].as_str().ok_or_else(|| {
+            format!(
+                "Rule::from_json() Exception: Rule is missing a \"source\": {}",
+                value
+            )
+        })?;
+        let destination = value["destination"].as_str().ok_or_else(|| {
+            format!(
+                "Rule::from_json() Exception: Rule is missing a \"destination\": {}",
+                value
+            )
+        })?;
+
+        let kind = match (&default_kind, value["type"].as_u64()) {
+            (RuleKind::Redirect(_), Some(status)) => match status {
+                301 | 302 | 307 | 308 => RuleKind::Redirect(status as u16),
+                _ => {
+                    return Err(format!(
+                        "Rule::from_json() Exception: Unsupported redirect type {} for {}, expected 301, 302, 307 or 308.",
+                        status, source
+                    ))
+                }
+            },
+            _ => default_kind,
+        };
+
+        let mut rule = Rule::new(source, destination, kind);
+
+        match &value["host"] {
+            Value::Null => (),
+            Value::String(host) => rule.hosts.push(host.to_lowercase()),
+            Value::Array(hosts) => {
+                for host in hosts {
+                    let host = host.as_str().ok_or_else(|| {
+                        format!(
+                            "Rule::from_json() Exception: Hosts must be strings in {}",
+                            source
+                        )
+                    })?;
+                    rule.hosts.push(host.to_lowercase());
+                }
+            }
+            _ => {
+                return Err(format!(
+                    "Rule::from_json() Exception: \"host\" must be a string or an array in {}",
+                    source
+                ))
+            }
+        }
+
+        match &value["methods"] {
+            Value::Null => (),
+            Value::Array(methods) => {
+                for method in methods {
+                    let method = method.as_str().ok_or_else(|| {
+                        format!(
+                            "Rule::from_json() Exception: Methods must be strings in {}",
+                            source
+                        )
+                    })?;
+                    rule.methods
+                        .push(Request::get_enumerated_method_from_string(method));
+                }
+            }
+            _ => {
+                return Err(format!(
+                    "Rule::from_json() Exception: \"methods\" must be an array in {}",
+                    source
+                ))
+            }
+        }
+
+        Ok(rule)
+    }
+
+    pub fn applies_to(&self, method: &HttpMethod, host: Option<&str>) -> bool {
+        if !self.methods.is_empty() && !self.methods.contains(method) {
+            return false;
+        }
+
+        if self.hosts.is_empty() {
+            return true;
+        }
+
+        let host = match host {
+            Some(host) => VirtualHosts::strip_port(host).to_lowercase(),
+            None => return false,
+        };
+
+        self.hosts
+            .iter()
+            .any(|rule_host| match rule_host.strip_prefix("*.") {
+                Some(domain) => host.ends_with(&format!(".{}", domain)),
+                None => *rule_host == host,
+            })
+    }
+
+    ///
+    /// Substitutes `:name` placeholders in the destination with captured values.
+    ///
+    pub fn format_destination(&self, captures: &HashMap<String, String>) -> String {
+        RulePattern::substitute(&self.destination, captures)
+    }
+}
+
+/// # RuleAction
+///
+/// What the server should do with a request after the rules have been evaluated.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleAction {
+    /// Respond with a redirect to the location
+    Redirect(u16, String),
+    /// Serve the request as though it was made for this path (and query)
+    Rewrite(String, Option<String>),
+    /// No rule matched, serve the request as is
+    Continue,
+}
+
+/// # Rules
+///
+/// Redirect and rewrite rules, loaded from the json file supplied with `--rules=<path>`
+/// and from `[[redirects]]` / `[[rewrites]]` tables in the config file.
+/// The same file may also carry per-path response headers, see `HeaderRules`.
+///
+/// ```json
+/// {
+///   "redirects": [
+///     { "source": "/blog/:slug", "destination": "/posts/:slug", "type": 301 },
+///     { "source": "/**", "destination": "https://example.com/:splat", "type": 308, "host": "www.example.com" }
+///   ],
+///   "rewrites": [
+///     { "source": "/app/**", "destination": "/index.html", "methods": ["GET"] }
+///   ]
+/// }
+/// ```
+///
+/// Redirects are evaluated before rewrites, each in the order they are listed,
+/// and the first rule that matches wins. Redirects default to `301`.
+///
+#[derive(Debug, Clone, Default)]
+pub struct Rules {
+    pub rules: Vec<Rule>,
+    pub header_rules: HeaderRules,
+}
+
+impl Rules {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let value: Value = serde_json::from_str(json)
+            .map_err(|e| format!("Rules::from_json() Exception: Invalid json. {}", e))?;
+
+        Self::from_json_value(&value)
+    }
+
+    ///
+    /// Reads the `"redirects"`, `"rewrites"` and `"headers"` arrays of a json (or toml) table.
+    ///
+    pub fn from_json_value(value: &Value) -> Result<Self, String> {
+        let mut rules: Vec<Rule> = vec![];
+        for (key, kind) in [
+            ("redirects", RuleKind::Redirect(301)),
+            ("rewrites", RuleKind::Rewrite),
+        ] {
+            match &value[key] {
+                Value::Null => (),
+                Value::Array(entries) => {
+                    for entry in entries {
+                        rules.push(Rule::from_json(entry, kind.clone())?);
+                    }
+                }
+                _ => {
+                    return Err(format!(
+                        "Rules::from_json_value() Exception: \"{}\" must be an array.",
+                        key
+                    ))
+                }
+            }
+        }
+
+        let header_rules = HeaderRules::from_json_value(&value["headers"])?;
+
+        Ok(Rules {
+            rules,
+            header_rules,
+        })
+    }
+
+    pub fn from_file(rules_path: &str) -> Result<Self, String> {
+        let json = fs::read_to_string(rules_path).map_err(|e| {
+            format!(
+                "Rules::from_file() Exception: Unable to read rules file {}. {}",
+                rules_path, e
+            )
+        })?;
+
+        Self::from_json(&json)
+    }
+
+    ///
+    /// Evaluates the rules against a request, without touching the fs or the network.
+    ///
+    /// The query string of the request is preserved, for redirects it is merged
+    /// into the destination's own query string, for rewrites it is kept unless
+    /// the destination supplies a query string of its own.
+    ///
+    pub fn evaluate(
+        &self,
+        method: &HttpMethod,
+        host: Option<&str>,
+        path: &str,
+        query: Option<&str>,
+    ) -> RuleAction {
+        for rule in &self.rules {
+            if !rule.applies_to(method, host) {
+                continue;
+            }
+
+            let captures = match rule.pattern.captures(path) {
+                Some(captures) => captures,
+                None => continue,
+            };
+
+            let destination = rule.format_destination(&captures);
+            let (destination_path, destination_query) = match destination.split_once('?') {
+                Some((destination_path, destination_query)) => {
+                    (String::from(destination_path), Some(destination_query))
+                }
+                None => (destination, None),
+            };
+
+            return match &rule.kind {
+                RuleKind::Redirect(status) => {
+                    let merged_query: Vec<&str> = [destination_query, query]
+                        .into_iter()
+                        .flatten()
+                        .filter(|query| !query.is_empty())
+                        .collect();
+                    let location = if merged_query.is_empty() {
+                        destination_path
+                    } else {
+                        format!("{}?{}", destination_path, merged_query.join("&"))
+                    };
+                    RuleAction::Redirect(*status, location)
+                }
+                RuleKind::Rewrite => RuleAction::Rewrite(
+                    destination_path,
+                    destination_query.or(query).map(String::from),
+                ),
+            };
+        }
+
+        RuleAction::Continue
+    }
+
+    pub fn evaluate_request(&self, request: &Request) -> RuleAction {
+        self.evaluate(
+            request.method(),
+            request
+                .headers()
+                .get_header_by_key("Host")
+                .map(|host| host.as_str()),
+            request.path(),
+            request.query().map(|query| query.as_str()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Method, host, path and query of a request, and the action it evaluates to
+    type Case = (
+        HttpMethod,
+        Option<&'static str>,
+        &'static str,
+        Option<&'static str>,
+        RuleAction,
+    );
+
+    fn rules() -> Rules {
+        Rules::from_json(
+            r#"{
+                "redirects": [
+                    { "source": "/old", "destination": "/new" },
+                    { "source": "/found", "destination": "/elsewhere", "type": 302 },
+                    { "source": "/temporary", "destination": "/elsewhere", "type": 307 },
+                    { "source": "/blog/:year/:slug", "destination": "/posts/:slug?year=:year", "type": 308 },
+                    { "source": "/**", "destination": "https://example.com/:splat", "type": 308, "host": "www.example.com" },
+                    { "source": "/*.php", "destination": "/legacy" },
+                    { "source": "/first", "destination": "/winner" },
+                    { "source": "/first", "destination": "/loser" },
+                    { "source": "/docs/*.html", "destination": "/docs" },
+                    { "source": "/shop/**", "destination": "https://shop.example.com/:splat", "host": "*.example.org" }
+                ],
+                "rewrites": [
+                    { "source": "/old", "destination": "/never" },
+                    { "source": "/app/**", "destination": "/index.html", "methods": ["GET"] },
+                    { "source": "/search", "destination": "/search.html?engine=local" },
+                    { "source": "/users/:id", "destination": "/user.html" }
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn redirect(status: u16, location: &str) -> RuleAction {
+        RuleAction::Redirect(status, String::from(location))
+    }
+
+    fn rewrite(path: &str, query: Option<&str>) -> RuleAction {
+        RuleAction::Rewrite(String::from(path), query.map(String::from))
+    }
+
+    #[test]
+    fn evaluates_requests_to_actions() {
+        let rules = rules();
+        let cases: Vec<Case> = vec![
+            // Redirects default to 301 and come before rewrites of the same path
+            (HttpMethod::GET, None, "/old", None, redirect(301, "/new")),
+            (
+                HttpMethod::GET,
+                None,
+                "/found",
+                None,
+                redirect(302, "/elsewhere"),
+            ),
+            (
+                HttpMethod::GET,
+                None,
+                "/temporary",
+                None,
+                redirect(307, "/elsewhere"),
+            ),
+            // The first match wins
+            (
+                HttpMethod::GET,
+                None,
+                "/first",
+                None,
+                redirect(301, "/winner"),
+            ),
+            // Captures are substituted, the request query merged after the destination's
+            (
+                HttpMethod::GET,
+                None,
+                "/blog/2024/hello",
+                Some("ref=feed"),
+                redirect(308, "/posts/hello?year=2024&ref=feed"),
+            ),
+            (
+                HttpMethod::GET,
+                None,
+                "/old",
+                Some("a=1"),
+                redirect(301, "/new?a=1"),
+            ),
+            // Globs match within a segment only
+            (
+                HttpMethod::GET,
+                None,
+                "/index.php",
+                None,
+                redirect(301, "/legacy"),
+            ),
+            (
+                HttpMethod::GET,
+                None,
+                "/docs/a.html",
+                None,
+                redirect(301, "/docs"),
+            ),
+            (
+                HttpMethod::GET,
+                None,
+                "/docs/a/b.html",
+                None,
+                RuleAction::Continue,
+            ),
+            // Host conditions, ports ignored and wildcards matching subdomains
+            (
+                HttpMethod::GET,
+                Some("www.example.com:8080"),
+                "/a/b",
+                Some("q=1"),
+                redirect(308, "https://example.com/a/b?q=1"),
+            ),
+            (
+                HttpMethod::GET,
+                Some("example.com"),
+                "/a/b",
+                None,
+                RuleAction::Continue,
+            ),
+            (HttpMethod::GET, None, "/a/b", None, RuleAction::Continue),
+            (
+                HttpMethod::GET,
+                Some("Store.Example.org"),
+                "/shop/cart",
+                None,
+                redirect(301, "https://shop.example.com/cart"),
+            ),
+            (
+                HttpMethod::GET,
+                Some("example.org"),
+                "/shop/cart",
+                None,
+                RuleAction::Continue,
+            ),
+            // Rewrites keep the request query unless the destination has its own
+            (
+                HttpMethod::GET,
+                None,
+                "/app/settings",
+                Some("tab=2"),
+                rewrite("/index.html", Some("tab=2")),
+            ),
+            (
+                HttpMethod::GET,
+                None,
+                "/app",
+                None,
+                rewrite("/index.html", None),
+            ),
+            (
+                HttpMethod::GET,
+                None,
+                "/search",
+                Some("q=rust"),
+                rewrite("/search.html", Some("engine=local")),
+            ),
+            (
+                HttpMethod::DELETE,
+                None,
+                "/users/7",
+                None,
+                rewrite("/user.html", None),
+            ),
+            // Method conditions
+            (
+                HttpMethod::POST,
+                None,
+                "/app/settings",
+                None,
+                RuleAction::Continue,
+            ),
+            (
+                HttpMethod::GET,
+                None,
+                "/users/7/posts",
+                None,
+                RuleAction::Continue,
+            ),
+        ];
+
+        for (method, host, path, query, expected) in cases {
+            assert_eq!(
+                rules.evaluate(&method, host, path, query),
+                expected,
+                "{} {} (host {:?}, query {:?})",
+                method.as_str(),
+                path,
+                host,
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn refuses_unsupported_redirect_types() {
+        let error = Rules::from_json(
+            r#"{ "redirects": [{ "source": "/a", "destination": "/b", "type": 303 }] }"#,
+        )
+        .unwrap_err();
+        assert!(error.contains("Unsupported redirect type 303"), "{}", error);
+    }
+}
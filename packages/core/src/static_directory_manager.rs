@@ -1,8 +1,12 @@
+use crate::directory_listing::DirectoryListing;
 use crate::filelike::FileLike;
 use crate::logger::Logger;
+use crate::mount::Mount;
 use core::fmt::{Debug, Display};
 use std::error::Error;
 use std::fmt::write;
+use std::fs;
+use std::path::Path;
 
 pub struct UnknownFileError {
     path: String,
@@ -34,10 +38,28 @@ impl Display for UnknownFileError {
 
 impl Error for UnknownFileError {}
 
+/// # MountLookup
+///
+/// The outcome of resolving a request path against a `Mount`.
+///
+#[derive(Debug)]
+pub enum MountLookup {
+    File(FileLike),
+    /// The mount's fallback file was served, carrying the url path it was served from
+    Fallback(FileLike, String),
+    /// An html directory index, rendered because the mount has `listing` enabled
+    Listing(String),
+    /// The path names a directory, redirect to the same path with a trailing slash
+    Redirect(String),
+    NotFound,
+}
+
 #[derive(Debug, Clone)]
 pub struct StaticDirectoryManager {
     pub directories: Vec<String>,
+    pub mounts: Vec<Mount>,
     pub backup_file: String,
+    pub index_file: String,
 }
 
 impl StaticDirectoryManager {
@@ -48,6 +70,11 @@ impl StaticDirectoryManager {
                 has_ancestor_dir = true;
             }
         }
+        for mount in &self.mounts {
+            if value.starts_with(mount.directory.as_str()) {
+                has_ancestor_dir = true;
+            }
+        }
         has_ancestor_dir
     }
 }
@@ -85,4 +112,105 @@ impl StaticDirectoryManager {
         Logger::warn("Unable to find requested file in known static directories.");
         Err(())
     }
+
+    ///
+    /// Looks a path up in the directories without logging, for files that are usually
+    /// absent, such as custom error pages.
+    ///
+    pub fn find_optional_file(&self, path: &str) -> Option<FileLike> {
+        self.directories
+            .iter()
+            .map(|directory| format!("{}{}", directory, path))
+            .filter(|file_path| Path::new(file_path).is_file())
+            .find_map(|file_path| self.get_file(&file_path).ok())
+    }
+}
+
+impl StaticDirectoryManager {
+    ///
+    /// Appends the index file to paths that name a directory, i.e. `/docs/` => `/docs/index.html`
+    ///
+    pub fn resolve_index_file(&self, path: &str) -> Option<String> {
+        if path.ends_with('/') {
+            Some(format!("{}{}", path, self.index_file))
+        } else {
+            None
+        }
+    }
+
+    ///
+    /// Finds the mount with the longest prefix that matches the request path.
+    ///
+    pub fn find_mount(&self, path: &str) -> Option<&Mount> {
+        self.mounts
+            .iter()
+            .filter(|mount| mount.strip_prefix(path).is_some())
+            .max_by_key(|mount| mount.prefix.len())
+    }
+
+    pub fn search_for_file_path_in_mount(&self, mount: &Mount, path: &str) -> MountLookup {
+        let remainder = match mount.strip_prefix(path) {
+            Some(remainder) => remainder,
+            None => return MountLookup::NotFound,
+        };
+
+        if remainder.is_empty() {
+            return MountLookup::Redirect(format!("{}/", mount.prefix));
+        }
+
+        if remainder.split('/').any(|segment| segment == "..") {
+            Logger::warn(&format!("Refusing to traverse out of mount: {}", path));
+            return MountLookup::NotFound;
+        }
+
+        let absolute_path = format!("{}{}", mount.directory, remainder);
+
+        if Self::is_directory(&absolute_path) {
+            return MountLookup::Redirect(format!("{}/", path));
+        }
+
+        match self.get_file(&absolute_path) {
+            Ok(file) => {
+                Logger::info(&format!("Requested File: {}", &absolute_path));
+                return MountLookup::File(file);
+            }
+            Err(e) => {
+                Logger::warn(&format!("File Not Found: {}", &absolute_path));
+                Logger::error(e.to_string().as_str());
+            }
+        }
+
+        if mount.listing {
+            if let Some(directory_path) = absolute_path.strip_suffix(self.index_file.as_str()) {
+                if Self::is_directory(directory_path) {
+                    let url_path = path.strip_suffix(self.index_file.as_str()).unwrap_or(path);
+                    match DirectoryListing::render(url_path, directory_path) {
+                        Ok(listing) => return MountLookup::Listing(listing),
+                        Err(e) => Logger::error(e.to_string().as_str()),
+                    }
+                }
+            }
+        }
+
+        if let Some(fallback) = &mount.fallback {
+            let fallback_path = format!("{}/{}", mount.directory, fallback);
+            if let Ok(file) = self.get_file(&fallback_path) {
+                Logger::info(&format!("Serving Mount Fallback: {}", &fallback_path));
+                let fallback_url_path = match mount.prefix.as_str() {
+                    "/" => format!("/{}", fallback),
+                    prefix => format!("{}/{}", prefix, fallback),
+                };
+                return MountLookup::Fallback(file, fallback_url_path);
+            }
+        }
+
+        MountLookup::NotFound
+    }
+
+    fn is_directory(path: &str) -> bool {
+        match fs::metadata(path) {
+            Ok(metadata) => metadata.is_dir(),
+            Err(_) => false,
+        }
+    }
 }
@@ -1,51 +0,0 @@
-use crate::worker::{Job, Worker};
-use std::sync::{mpsc, Arc, Mutex};
-
-pub struct ThreadPool {
-    workers: Vec<Worker>,
-    sender: mpsc::Sender<Job>,
-}
-
-impl ThreadPool {
-    /// Create a new ThreadPool.
-    ///
-    /// The size is the number of threads in the pool.
-    ///
-    /// # Panics
-    ///
-    /// The `new` function will panic if the size is zero.
-    pub fn new(size: usize) -> ThreadPool {
-        assert!(size > 0);
-
-        let (sender, receiver) = mpsc::channel();
-
-        let receiver = Arc::new(Mutex::new(receiver));
-
-        let mut workers = Vec::with_capacity(size);
-
-        for i in 0..size {
-            workers.push(Worker::new(i, Arc::clone(&receiver)));
-        }
-
-        ThreadPool { workers, sender }
-    }
-
-    pub fn execute<F>(&self, f: F)
-    where
-        F: FnOnce() + Send + 'static,
-    {
-        let job = Box::new(f);
-
-        self.sender.send(job).unwrap();
-    }
-}
-
-// Note: If the operating system can’t create a thread
-// because there aren’t enough system resources,
-// thread::spawn will panic.
-// That will cause our whole server to panic,
-// even though the creation of some threads might succeed.
-// For simplicity’s sake, this behavior is fine,
-// but in a production thread pool implementation,
-// you’d likely want to use std::thread::Builder
-// and its spawn method that returns Result instead
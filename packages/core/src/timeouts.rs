@@ -0,0 +1,169 @@
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::io::{self, ErrorKind, Read};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+pub const DEFAULT_HEADER_READ_TIMEOUT: Duration = Duration::from_secs(10);
+pub const DEFAULT_BODY_READ_TIMEOUT: Duration = Duration::from_secs(30);
+pub const DEFAULT_WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+pub const DEFAULT_MIN_RATE: u64 = 240;
+
+/// How long a transfer runs before it is held to the minimum rate, covering slow starts
+pub const MIN_RATE_GRACE: Duration = Duration::from_secs(5);
+
+/// # TimeoutSettings
+///
+/// How long rsrv waits on clients, so stalled ones give up their connection rather
+/// than hold it. Set in seconds with `--idle-timeout`, `--header-timeout`,
+/// `--body-timeout` and `--write-timeout`, `RSRV_IDLE_TIMEOUT` and the like, or a
+/// `[timeouts]` table, `0` turning a timeout off.
+///
+/// ```toml
+/// [timeouts]
+/// idle = 30
+/// header_read = 10
+/// body_read = 30
+/// write = 30
+/// min_rate = 240
+/// ```
+///
+/// - `idle` is how long a new connection may wait before its request starts, the TLS
+///   handshake included. Idle connections are closed without a response.
+/// - `header_read` is how long the request head may take once it starts, a client
+///   that runs out of it gets a `408 Request Timeout`.
+/// - `body_read` and `write` bound each read of the body and each write of the
+///   response, a body that stalls gets a `408`.
+/// - `min_rate`, `--min-rate` or `RSRV_MIN_RATE`, in bytes per second, is the slowest
+///   a request head or body may arrive once `MIN_RATE_GRACE` has passed, `0` turning
+///   it off. It catches clients that trickle in just enough to beat the timeouts.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeoutSettings {
+    pub idle: Option<Duration>,
+    pub header_read: Option<Duration>,
+    pub body_read: Option<Duration>,
+    pub write: Option<Duration>,
+    pub min_rate: Option<u64>,
+}
+
+impl Default for TimeoutSettings {
+    fn default() -> Self {
+        TimeoutSettings {
+            idle: Some(DEFAULT_IDLE_TIMEOUT),
+            header_read: Some(DEFAULT_HEADER_READ_TIMEOUT),
+            body_read: Some(DEFAULT_BODY_READ_TIMEOUT),
+            write: Some(DEFAULT_WRITE_TIMEOUT),
+            min_rate: Some(DEFAULT_MIN_RATE),
+        }
+    }
+}
+
+impl TimeoutSettings {
+    pub fn describe(&self) -> String {
+        let seconds = |timeout: Option<Duration>| match timeout {
+            Some(timeout) => format!("{}s", timeout.as_secs()),
+            None => String::from("off"),
+        };
+        let min_rate = match self.min_rate {
+            Some(min_rate) => format!("{} bytes/s", min_rate),
+            None => String::from("off"),
+        };
+
+        format!(
+            "Timeouts: idle {}, request head {}, body reads {}, writes {}, minimum rate {}",
+            seconds(self.idle),
+            seconds(self.header_read),
+            seconds(self.body_read),
+            seconds(self.write),
+            min_rate
+        )
+    }
+}
+
+/// # Timeouts
+///
+/// A functional struct that tells client timeouts apart from other io errors, so
+/// handlers answer them with `408` rather than blaming the server or an upstream.
+///
+pub struct Timeouts;
+
+impl Timeouts {
+    pub fn client_timed_out(message: &str) -> io::Error {
+        io::Error::new(ErrorKind::TimedOut, ClientTimedOut(String::from(message)))
+    }
+
+    pub fn is_client_timeout(e: &io::Error) -> bool {
+        e.get_ref()
+            .is_some_and(|inner| inner.is::<ClientTimedOut>())
+    }
+
+    ///
+    /// Whether `transferred` bytes since `started` fall short of `min_rate`, once past the grace period.
+    ///
+    pub fn below_min_rate(transferred: u64, started: Instant, min_rate: Option<u64>) -> bool {
+        let elapsed = started.elapsed();
+        match min_rate {
+            Some(min_rate) if elapsed > MIN_RATE_GRACE => {
+                (transferred as f64) < min_rate as f64 * elapsed.as_secs_f64()
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ClientTimedOut(String);
+
+impl Display for ClientTimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for ClientTimedOut {}
+
+/// # TransferRate
+///
+/// Reads a request body from the client, failing with a client timeout when a read
+/// times out or the body arrives slower than the minimum rate.
+///
+pub struct TransferRate<R> {
+    reader: R,
+    min_rate: Option<u64>,
+    started: Instant,
+    transferred: u64,
+}
+
+impl<R: Read> TransferRate<R> {
+    pub fn new(reader: R, min_rate: Option<u64>) -> Self {
+        TransferRate {
+            reader,
+            min_rate,
+            started: Instant::now(),
+            transferred: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for TransferRate<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let read = match self.reader.read(buffer) {
+            Ok(read) => read,
+            Err(e) if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock) => {
+                return Err(Timeouts::client_timed_out(
+                    "the client stopped sending the body",
+                ))
+            }
+            Err(e) => return Err(e),
+        };
+
+        self.transferred += read as u64;
+        if read > 0 && Timeouts::below_min_rate(self.transferred, self.started, self.min_rate) {
+            return Err(Timeouts::client_timed_out(
+                "the client sent the body slower than the minimum rate",
+            ));
+        }
+        Ok(read)
+    }
+}
@@ -0,0 +1,345 @@
+use std::fs;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use rustls::crypto::{ring, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use rustls::ServerConfig;
+
+use crate::config::Config;
+use crate::dev_certificate::DevCertificate;
+use crate::logger::Logger;
+use crate::virtual_host::VirtualHost;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// `Strict-Transport-Security` sent for `hsts = true`, a year
+pub const DEFAULT_HSTS: &str = "max-age=31536000";
+
+/// # TlsSettings
+///
+/// The certificate rsrv serves HTTPS with, set with `--tls-cert` and `--tls-key`,
+/// `RSRV_TLS_CERT` and `RSRV_TLS_KEY`, or a `[tls]` table. Without one rsrv serves
+/// plain HTTP.
+///
+/// ```toml
+/// [tls]
+/// cert = "certs/localhost.pem"
+/// key = "certs/localhost-key.pem"
+/// ```
+///
+/// `--https`, `RSRV_HTTPS` or `enabled = true` without a certificate serves a
+/// generated development certificate instead, see `DevCertificate`.
+///
+/// ```toml
+/// [tls]
+/// enabled = true
+/// redirect_port = 8080
+/// hsts = "max-age=31536000; includeSubDomains"
+/// ```
+///
+/// `redirect_port`, `--http-redirect-port` or `RSRV_HTTP_REDIRECT_PORT` opens a
+/// plain HTTP listener that redirects every request to HTTPS, see `HttpsRedirect`.
+///
+/// `hsts`, `--hsts` or `RSRV_HSTS` is sent as `Strict-Transport-Security` on files
+/// served over TLS, `true` sending `max-age=31536000`. It is off by default,
+/// browsers remember it for the hostname on every port, `localhost` included.
+///
+/// Both are PEM files, the certificate file holding the leaf certificate followed
+/// by any intermediates. Virtual hosts may present their own certificate, see
+/// `VirtualHost`, clients that send no matching SNI hostname get this one.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsSettings {
+    pub https: bool,
+    pub cert: Option<String>,
+    pub key: Option<String>,
+    pub redirect_port: Option<u16>,
+    pub hsts: Option<String>,
+}
+
+impl TlsSettings {
+    pub fn enabled(&self) -> bool {
+        self.https || self.cert.is_some()
+    }
+
+    pub fn uses_development_certificate(&self) -> bool {
+        self.https && self.cert.is_none()
+    }
+
+    ///
+    /// Parses an `hsts` setting, a boolean or the header's directives.
+    ///
+    pub fn parse_hsts(hsts: &str) -> Result<Option<String>, String> {
+        let hsts = hsts.trim();
+        match hsts.to_lowercase().as_str() {
+            "true" | "1" | "yes" | "on" => Ok(Some(String::from(DEFAULT_HSTS))),
+            "false" | "0" | "no" | "off" => Ok(None),
+            directives if directives.contains("max-age=") => Ok(Some(String::from(hsts))),
+            _ => Err(format!(
+                "expected true, false or directives with a max-age, i.e. \"{}\", received {}",
+                DEFAULT_HSTS, hsts
+            )),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match &self.cert {
+            Some(cert) => format!(
+                "TLS enabled with {}, certificates reload when their files change",
+                cert
+            ),
+            None => String::from(
+                "TLS enabled with the development certificate, certificates reload when their files change",
+            ),
+        }
+    }
+}
+
+/// # CertificateFiles
+///
+/// A certificate and its key on disk, with the hostname pattern it is presented
+/// for, or `None` for the default certificate.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertificateFiles {
+    pub hostname: Option<String>,
+    pub cert: String,
+    pub key: String,
+}
+
+/// # Tls
+///
+/// A functional struct that builds the rustls `ServerConfig` connections are
+/// accepted with.
+///
+/// The certificate is chosen per connection from the SNI hostname the client
+/// sends, exact virtual host names first and then the longest matching wildcard.
+/// ALPN offers `h2` ahead of `http/1.1` unless HTTP/2 is turned off, see `Http2`.
+///
+/// A watcher thread polls the certificate files and swaps in the new certificates
+/// when any of them changes, so renewed certificates apply to new connections
+/// without a restart. A reload that fails to parse keeps the previous certificates.
+///
+/// The set of certificate files is read once at startup, changing which files are
+/// used needs a restart.
+///
+pub struct Tls;
+
+impl Tls {
+    pub fn server_config(config: &Config) -> Result<Option<Arc<ServerConfig>>, String> {
+        let certificate_files = Self::certificate_files(config);
+        if certificate_files.is_empty() {
+            return Ok(None);
+        }
+
+        let provider = Arc::new(ring::default_provider());
+        let certificates = Self::load_certificates(&certificate_files, &provider)?;
+        let resolver = Arc::new(CertificateResolver {
+            certificates: RwLock::new(certificates),
+        });
+        Self::watch(
+            certificate_files,
+            Arc::clone(&resolver),
+            Arc::clone(&provider),
+        );
+
+        let mut server_config = ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .map_err(|e| format!("Tls::server_config() Exception: {}", e))?
+            .with_no_client_auth()
+            .with_cert_resolver(resolver);
+        server_config.alpn_protocols = match config.http2.enabled {
+            true => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+            false => vec![b"http/1.1".to_vec()],
+        };
+
+        Ok(Some(Arc::new(server_config)))
+    }
+
+    ///
+    /// Every certificate the config presents, the default one first.
+    ///
+    pub fn certificate_files(config: &Config) -> Vec<CertificateFiles> {
+        let default_certificate = match (&config.tls.cert, &config.tls.key) {
+            (Some(cert), Some(key)) => CertificateFiles {
+                hostname: None,
+                cert: cert.clone(),
+                key: key.clone(),
+            },
+            _ if config.tls.https => match DevCertificate::files() {
+                Some(files) => files,
+                None => return vec![],
+            },
+            _ => return vec![],
+        };
+
+        let mut certificate_files = vec![default_certificate];
+        for virtual_host in &config.sites.hosts {
+            if let (Some(cert), Some(key)) = (&virtual_host.tls_cert, &virtual_host.tls_key) {
+                certificate_files.push(CertificateFiles {
+                    hostname: Some(virtual_host.hostname.clone()),
+                    cert: cert.clone(),
+                    key: key.clone(),
+                });
+            }
+        }
+        certificate_files
+    }
+
+    fn load_certificates(
+        certificate_files: &[CertificateFiles],
+        provider: &CryptoProvider,
+    ) -> Result<Certificates, String> {
+        let mut certificates = Certificates::default();
+
+        for files in certificate_files {
+            let certified_key = Arc::new(Self::load_certified_key(files, provider)?);
+            match &files.hostname {
+                Some(hostname) => certificates.hosts.push((hostname.clone(), certified_key)),
+                None => certificates.default = Some(certified_key),
+            }
+        }
+
+        Ok(certificates)
+    }
+
+    fn load_certified_key(
+        files: &CertificateFiles,
+        provider: &CryptoProvider,
+    ) -> Result<CertifiedKey, String> {
+        let chain = CertificateDer::pem_file_iter(&files.cert)
+            .and_then(|certificates| certificates.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| {
+                format!(
+                    "Tls::load_certified_key() Exception: Unable to read the certificate {}. {}",
+                    files.cert, e
+                )
+            })?;
+        if chain.is_empty() {
+            return Err(format!(
+                "Tls::load_certified_key() Exception: {} holds no PEM certificate.",
+                files.cert
+            ));
+        }
+
+        let key = PrivateKeyDer::from_pem_file(&files.key).map_err(|e| {
+            format!(
+                "Tls::load_certified_key() Exception: Unable to read the private key {}. {}",
+                files.key, e
+            )
+        })?;
+
+        CertifiedKey::from_der(chain, key, provider).map_err(|e| {
+            format!(
+                "Tls::load_certified_key() Exception: The key {} does not fit the certificate {}. {}",
+                files.key, files.cert, e
+            )
+        })
+    }
+
+    fn watch(
+        certificate_files: Vec<CertificateFiles>,
+        resolver: Arc<CertificateResolver>,
+        provider: Arc<CryptoProvider>,
+    ) {
+        thread::spawn(move || {
+            let mut modified_times = Self::modified_times(&certificate_files);
+
+            loop {
+                thread::sleep(POLL_INTERVAL);
+
+                let current_modified_times = Self::modified_times(&certificate_files);
+                if current_modified_times == modified_times {
+                    continue;
+                }
+                modified_times = current_modified_times;
+
+                match Self::load_certificates(&certificate_files, &provider) {
+                    Ok(certificates) => {
+                        resolver.replace(certificates);
+                        Logger::info("Reloaded TLS certificates");
+                    }
+                    Err(e) => Logger::warn(&format!(
+                        "Rejected TLS certificate reload, keeping the previous certificates. {}",
+                        e
+                    )),
+                }
+            }
+        });
+    }
+
+    fn modified_times(certificate_files: &[CertificateFiles]) -> Vec<Option<SystemTime>> {
+        certificate_files
+            .iter()
+            .flat_map(|files| [&files.cert, &files.key])
+            .map(|path| {
+                fs::metadata(path)
+                    .and_then(|metadata| metadata.modified())
+                    .ok()
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Default)]
+struct Certificates {
+    default: Option<Arc<CertifiedKey>>,
+    hosts: Vec<(String, Arc<CertifiedKey>)>,
+}
+
+/// # CertificateResolver
+///
+/// Picks the certificate for a connection from its SNI hostname, matched
+/// the same way `VirtualHosts` matches `Host` headers.
+///
+#[derive(Debug)]
+struct CertificateResolver {
+    certificates: RwLock<Certificates>,
+}
+
+impl CertificateResolver {
+    fn replace(&self, certificates: Certificates) {
+        match self.certificates.write() {
+            Ok(mut current) => *current = certificates,
+            Err(poisoned) => *poisoned.into_inner() = certificates,
+        }
+    }
+}
+
+impl ResolvesServerCert for CertificateResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let certificates = self
+            .certificates
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let hostname = match client_hello.server_name() {
+            Some(hostname) => hostname.to_lowercase(),
+            None => return certificates.default.clone(),
+        };
+
+        let exact_match = certificates
+            .hosts
+            .iter()
+            .find(|(pattern, _)| !pattern.starts_with("*.") && *pattern == hostname);
+        let wildcard_match = || {
+            certificates
+                .hosts
+                .iter()
+                .filter(|(pattern, _)| {
+                    pattern.starts_with("*.") && VirtualHost::pattern_matches(pattern, &hostname)
+                })
+                .max_by_key(|(pattern, _)| pattern.len())
+        };
+
+        exact_match
+            .or_else(wildcard_match)
+            .map(|(_, certified_key)| Arc::clone(certified_key))
+            .or_else(|| certificates.default.clone())
+    }
+}
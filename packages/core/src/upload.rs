@@ -0,0 +1,786 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use base64::Engine;
+use serde_json::Value;
+
+use crate::client_stream::ClientStream;
+use crate::config::Config;
+use crate::error_page::ErrorPage;
+use crate::filelike::FileLike;
+use crate::headers::Headers;
+use crate::logger::Logger;
+use crate::request::{HttpMethod, Request};
+use crate::response::Response;
+use crate::static_directory_manager::StaticDirectoryManager;
+use crate::status::StatusCode;
+use crate::timeouts::{Timeouts, TransferRate};
+
+pub const DEFAULT_MAX_UPLOAD_SIZE: u64 = 100 * 1024 * 1024;
+
+pub const BEARER_CHALLENGE: &str = "Bearer realm=\"rsrv\"";
+
+static TEMPORARY_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A file part of a multipart body, its filename and contents
+type MultipartFile<'a> = (String, &'a [u8]);
+
+/// # UploadPolicy
+///
+/// Whether, and by whom, files may be written into the served directories.
+/// Uploads are off unless enabled with `--uploads`, `RSRV_UPLOADS` or an `[uploads]` table,
+/// and every upload must carry `Authorization: Bearer <token>`, or the token as the
+/// password of `Basic` credentials for clients that only speak Basic.
+/// Deleting and moving files, see `FileManager`, is enabled separately with `manage`
+/// and takes the same token.
+///
+/// ```toml
+/// [uploads]
+/// enabled = true
+/// manage = true
+/// token = "s3cret"
+/// max_size = "250MB"
+/// ```
+///
+/// `max_size` takes a number of bytes or a size such as `512KB`, `250MB` or `1GB`,
+/// it defaults to 100MB.
+///
+#[derive(Debug, Clone)]
+pub struct UploadPolicy {
+    pub enabled: bool,
+    pub manage: bool,
+    pub token: Option<String>,
+    pub max_size: u64,
+}
+
+impl Default for UploadPolicy {
+    fn default() -> Self {
+        UploadPolicy {
+            enabled: false,
+            manage: false,
+            token: None,
+            max_size: DEFAULT_MAX_UPLOAD_SIZE,
+        }
+    }
+}
+
+impl UploadPolicy {
+    pub fn parse_size(size: &str) -> Result<u64, String> {
+        let size = size.trim();
+        let digits_end = size
+            .find(|character: char| !character.is_ascii_digit())
+            .unwrap_or(size.len());
+        let (number, unit) = size.split_at(digits_end);
+
+        let multiplier: u64 = match unit.trim().to_uppercase().as_str() {
+            "" | "B" => 1,
+            "K" | "KB" => 1024,
+            "M" | "MB" => 1024 * 1024,
+            "G" | "GB" => 1024 * 1024 * 1024,
+            _ => 0,
+        };
+
+        number
+            .parse::<u64>()
+            .ok()
+            .filter(|_| multiplier > 0)
+            .and_then(|number| number.checked_mul(multiplier))
+            .filter(|bytes| *bytes > 0)
+            .ok_or_else(|| {
+                format!(
+                    "UploadPolicy::parse_size() Exception: Expected a size such as 1048576, 512KB or 250MB, received {}",
+                    size
+                )
+            })
+    }
+
+    ///
+    /// Checks the request's bearer token, or Basic password, comparing in constant time.
+    ///
+    pub fn authorizes(&self, request: &Request) -> bool {
+        let token = match &self.token {
+            Some(token) => token,
+            None => return false,
+        };
+
+        request
+            .headers()
+            .map
+            .iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case("Authorization"))
+            .filter_map(|(_, value)| {
+                let (scheme, credentials) = value.trim().split_once(' ')?;
+                if scheme.eq_ignore_ascii_case("Bearer") {
+                    Some(String::from(credentials.trim()))
+                } else if scheme.eq_ignore_ascii_case("Basic") {
+                    let credentials = base64::engine::general_purpose::STANDARD
+                        .decode(credentials.trim())
+                        .ok()?;
+                    let credentials = String::from_utf8(credentials).ok()?;
+                    credentials
+                        .split_once(':')
+                        .map(|(_, password)| String::from(password))
+                } else {
+                    None
+                }
+            })
+            .any(|credentials| Self::constant_time_eq(credentials.as_bytes(), token.as_bytes()))
+    }
+
+    pub fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
+        if left.len() != right.len() {
+            return false;
+        }
+        left.iter()
+            .zip(right)
+            .fold(0u8, |difference, (left, right)| difference | (left ^ right))
+            == 0
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "Uploads enabled: PUT writes a file, multipart POST writes into a directory, up to {} bytes, bearer token required",
+            self.max_size
+        )
+    }
+}
+
+/// # Upload
+///
+/// A functional struct that writes uploaded files into the served directories.
+///
+/// - `PUT /path/file` writes the body to `file`, answering `201 Created` for a new file
+///   and `204 No Content` for a replaced one
+/// - `POST /path/` with a `multipart/form-data` body writes every file part into the
+///   directory, named after its filename, which may contain subdirectories
+///
+/// Paths resolve like reads, through the longest matching mount or else the first served
+/// directory, and may not leave it, through `..` or a symbolic link. Files are written to a
+/// temporary file beside the target and renamed over it once complete, so readers never
+/// see a partial upload. Bodies over the size limit are refused with `413`.
+///
+/// Multipart bodies are read whole before they are split, so they are held in memory,
+/// within the size limit.
+///
+/// ```sh
+/// $ curl -T dist/app.js -H "Authorization: Bearer $TOKEN" http://preview:8080/pr-12/app.js
+/// $ curl -F "file=@dist/index.html;filename=index.html" -H "Authorization: Bearer $TOKEN" http://preview:8080/pr-12/
+/// ```
+///
+pub struct Upload;
+
+impl Upload {
+    pub fn handle(
+        request: &Request,
+        static_directory_manager: &StaticDirectoryManager,
+        config: &Config,
+        stream: &mut ClientStream,
+    ) {
+        if !config.uploads.authorizes(request) {
+            return Self::respond_unauthorized(
+                request,
+                BEARER_CHALLENGE,
+                static_directory_manager,
+                config,
+                stream,
+            );
+        }
+
+        let result = match request.method() {
+            HttpMethod::PUT => Self::put(
+                request,
+                request.path(),
+                static_directory_manager,
+                config,
+                stream,
+            ),
+            _ => Self::post(request, static_directory_manager, config, stream),
+        };
+
+        Self::respond_with_result(result, request, static_directory_manager, config, stream);
+    }
+
+    pub fn respond_unauthorized(
+        request: &Request,
+        challenge: &str,
+        static_directory_manager: &StaticDirectoryManager,
+        config: &Config,
+        stream: &mut ClientStream,
+    ) {
+        Logger::warn(&format!(
+            "Refusing unauthorized {} {}",
+            request.method().as_str(),
+            request.path()
+        ));
+        ErrorPage::respond_with_headers(
+            401,
+            String::from("A valid bearer token is required to write files."),
+            &[("WWW-Authenticate", challenge)],
+            Some(request),
+            static_directory_manager,
+            &config.error_pages,
+            stream,
+        );
+    }
+
+    ///
+    /// Writes the response, or the error page for the status and message the write failed with.
+    ///
+    pub fn respond_with_result(
+        result: Result<Response, (u16, String)>,
+        request: &Request,
+        static_directory_manager: &StaticDirectoryManager,
+        config: &Config,
+        stream: &mut ClientStream,
+    ) {
+        match result {
+            Ok(response) => response.respond(stream),
+            Err((status, message)) => {
+                Logger::warn(&format!(
+                    "{} {} failed: {}",
+                    request.method().as_str(),
+                    request.path(),
+                    message
+                ));
+                ErrorPage::respond(
+                    status,
+                    message,
+                    Some(request),
+                    static_directory_manager,
+                    &config.error_pages,
+                    stream,
+                );
+            }
+        }
+    }
+
+    ///
+    /// Writes the request body to the file at `path`, the request path,
+    /// or for WebDAV the decoded request path.
+    ///
+    pub fn put(
+        request: &Request,
+        path: &str,
+        static_directory_manager: &StaticDirectoryManager,
+        config: &Config,
+        stream: &mut ClientStream,
+    ) -> Result<Response, (u16, String)> {
+        if path.ends_with('/') {
+            return Err((
+                400,
+                format!("PUT needs a file path, {} names a directory.", path),
+            ));
+        }
+
+        let (root, target) = Self::resolve_target(path, static_directory_manager)?;
+        if target.is_dir() {
+            return Err((409, format!("{} is a directory.", path)));
+        }
+        Self::ensure_body_allowed(request, config.uploads.max_size)?;
+
+        let existed = target.exists();
+        let mut body = Self::body_reader(request, stream, config.timeouts.min_rate);
+        let size = Self::write_atomically(&root, &target, |file| {
+            Self::copy_body(request, &mut body, file, config.uploads.max_size)
+        })?;
+
+        Logger::info(&format!(
+            "Uploaded {} ({} bytes) -> {}",
+            path,
+            size,
+            target.display()
+        ));
+
+        if existed {
+            Ok(Self::build_response(204, None, &[]))
+        } else {
+            Ok(Self::build_response(
+                201,
+                Some(serde_json::json!({ "path": path, "size": size })),
+                &[("Location", request.path())],
+            ))
+        }
+    }
+
+    fn post(
+        request: &Request,
+        static_directory_manager: &StaticDirectoryManager,
+        config: &Config,
+        stream: &mut ClientStream,
+    ) -> Result<Response, (u16, String)> {
+        let boundary = Self::multipart_boundary(request).ok_or_else(|| {
+            (
+                415,
+                String::from("POST uploads need a multipart/form-data body with a boundary."),
+            )
+        })?;
+
+        let (root, directory) = Self::resolve_target(request.path(), static_directory_manager)?;
+        if directory.is_file() {
+            return Err((
+                409,
+                format!(
+                    "{} is a file, POST uploads into a directory.",
+                    request.path()
+                ),
+            ));
+        }
+        Self::ensure_body_allowed(request, config.uploads.max_size)?;
+
+        let mut body: Vec<u8> = vec![];
+        let mut body_reader = Self::body_reader(request, stream, config.timeouts.min_rate);
+        Self::copy_body(
+            request,
+            &mut body_reader,
+            &mut body,
+            config.uploads.max_size,
+        )?;
+
+        let url_directory = format!("{}/", request.path().trim_end_matches('/'));
+        let mut uploaded_paths: Vec<String> = vec![];
+
+        for (filename, contents) in Self::parse_multipart(&body, &boundary)? {
+            let segments = Self::path_segments(&filename)?;
+            if segments.is_empty() {
+                return Err((400, format!("Invalid upload filename {}.", filename)));
+            }
+
+            let target = segments
+                .iter()
+                .fold(directory.clone(), |path, segment| path.join(segment));
+            if target.is_dir() {
+                return Err((409, format!("{} is a directory.", filename)));
+            }
+
+            Self::write_atomically(&root, &target, |file| {
+                file.write_all(contents)
+                    .map(|_| contents.len() as u64)
+                    .map_err(|e| (500, format!("Unable to write {}. {}", filename, e)))
+            })?;
+
+            let url_path = format!("{}{}", url_directory, segments.join("/"));
+            Logger::info(&format!(
+                "Uploaded {} ({} bytes) -> {}",
+                url_path,
+                contents.len(),
+                target.display()
+            ));
+            uploaded_paths.push(url_path);
+        }
+
+        if uploaded_paths.is_empty() {
+            return Err((400, String::from("The multipart body contained no files.")));
+        }
+
+        Ok(Self::build_response(
+            201,
+            Some(serde_json::json!({ "files": uploaded_paths })),
+            &[],
+        ))
+    }
+}
+
+impl Upload {
+    ///
+    /// Maps a url path onto the served directory it falls under, returning that directory
+    /// and the path inside it. Paths that would leave the directory are refused with `403`.
+    ///
+    pub fn resolve_target(
+        path: &str,
+        static_directory_manager: &StaticDirectoryManager,
+    ) -> Result<(PathBuf, PathBuf), (u16, String)> {
+        let (root, target) = Self::resolve_path(path, static_directory_manager)?;
+        Self::ensure_confined(&root, &target)?;
+        Ok((root, target))
+    }
+
+    ///
+    /// Like `resolve_target`, without following the target if it is a symbolic link,
+    /// for operations that act on the link itself.
+    ///
+    pub fn resolve_path(
+        path: &str,
+        static_directory_manager: &StaticDirectoryManager,
+    ) -> Result<(PathBuf, PathBuf), (u16, String)> {
+        let (root, remainder) = match static_directory_manager.find_mount(path) {
+            Some(mount) => (
+                mount.directory.clone(),
+                mount.strip_prefix(path).unwrap_or_default(),
+            ),
+            None => match static_directory_manager.directories.first() {
+                Some(directory) => (directory.clone(), path),
+                None => return Err((403, format!("{} is not inside a served directory.", path))),
+            },
+        };
+
+        let root = PathBuf::from(root);
+        let target = Self::path_segments(remainder)?
+            .iter()
+            .fold(root.clone(), |target, segment| target.join(segment));
+
+        // The root itself has no parent inside it
+        if let Some(parent) = target.parent().filter(|_| target != root) {
+            Self::ensure_confined(&root, parent)?;
+        }
+        Ok((root, target))
+    }
+
+    ///
+    /// Splits a relative path into its segments, refusing any that could escape a directory.
+    ///
+    pub fn path_segments(path: &str) -> Result<Vec<String>, (u16, String)> {
+        let mut segments: Vec<String> = vec![];
+
+        for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+            if segment == "." || segment == ".." || segment.contains(['\\', '\0']) {
+                return Err((
+                    403,
+                    format!("Refusing to write outside a served directory: {}", path),
+                ));
+            }
+            segments.push(String::from(segment));
+        }
+
+        Ok(segments)
+    }
+
+    ///
+    /// Checks that the closest existing ancestor of the target resolves inside the root,
+    /// so symbolic links cannot point a write elsewhere.
+    ///
+    pub fn ensure_confined(root: &Path, target: &Path) -> Result<(), (u16, String)> {
+        let canonical_root = fs::canonicalize(root)
+            .map_err(|e| (500, format!("Unable to resolve {}. {}", root.display(), e)))?;
+
+        let existing_ancestor = target
+            .ancestors()
+            .find(|ancestor| ancestor.exists())
+            .unwrap_or(root);
+        let canonical_ancestor = fs::canonicalize(existing_ancestor).map_err(|e| {
+            (
+                500,
+                format!("Unable to resolve {}. {}", existing_ancestor.display(), e),
+            )
+        })?;
+
+        if canonical_ancestor.starts_with(&canonical_root) {
+            Ok(())
+        } else {
+            Err((
+                403,
+                format!(
+                    "Refusing to write outside a served directory: {}",
+                    target.display()
+                ),
+            ))
+        }
+    }
+
+    ///
+    /// Writes a file beside the target, then renames it over the target, removing it on failure.
+    ///
+    pub fn write_atomically(
+        root: &Path,
+        target: &Path,
+        write: impl FnOnce(&mut File) -> Result<u64, (u16, String)>,
+    ) -> Result<u64, (u16, String)> {
+        let parent = target
+            .parent()
+            .ok_or_else(|| (403, format!("Unable to write {}.", target.display())))?;
+        let filename = target
+            .file_name()
+            .map(|filename| filename.to_string_lossy().to_string())
+            .ok_or_else(|| (403, format!("Unable to write {}.", target.display())))?;
+
+        // Checked before creating the missing directories, so a symbolic link among the
+        // existing ones cannot have them created elsewhere, and again once they exist
+        Self::ensure_confined(root, parent)?;
+        fs::create_dir_all(parent).map_err(|e| {
+            let status = if e.kind() == ErrorKind::AlreadyExists {
+                409
+            } else {
+                500
+            };
+            (
+                status,
+                format!("Unable to create {}. {}", parent.display(), e),
+            )
+        })?;
+        Self::ensure_confined(root, parent)?;
+
+        let temporary_path = parent.join(format!(
+            ".{}.rsrv-upload-{}-{}",
+            filename,
+            process::id(),
+            TEMPORARY_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let result = File::options()
+            .write(true)
+            .create_new(true)
+            .open(&temporary_path)
+            .map_err(|e| {
+                (
+                    500,
+                    format!("Unable to create {}. {}", temporary_path.display(), e),
+                )
+            })
+            .and_then(|mut file| {
+                let size = write(&mut file)?;
+                file.sync_all().map_err(|e| {
+                    (
+                        500,
+                        format!("Unable to write {}. {}", temporary_path.display(), e),
+                    )
+                })?;
+                Ok(size)
+            })
+            .and_then(|size| {
+                fs::rename(&temporary_path, target).map_err(|e| {
+                    (
+                        500,
+                        format!("Unable to move the upload to {}. {}", target.display(), e),
+                    )
+                })?;
+                Ok(size)
+            });
+
+        if result.is_err() {
+            let _ = fs::remove_file(&temporary_path);
+        }
+        result
+    }
+}
+
+impl Upload {
+    pub fn ensure_body_allowed(request: &Request, max_size: u64) -> Result<(), (u16, String)> {
+        match request.content_length() {
+            Some(content_length) if content_length as u64 > max_size => Err((
+                413,
+                format!(
+                    "The body is {} bytes, uploads are limited to {} bytes.",
+                    content_length, max_size
+                ),
+            )),
+            Some(_) => Ok(()),
+            None if request.is_chunked() => Ok(()),
+            None => Err((
+                411,
+                String::from("Uploads need a Content-Length or a chunked body."),
+            )),
+        }
+    }
+
+    ///
+    /// The body as read so far, followed by the rest of the stream,
+    /// held to the minimum transfer rate.
+    ///
+    pub fn body_reader<'a>(
+        request: &'a Request,
+        stream: &'a ClientStream,
+        min_rate: Option<u64>,
+    ) -> BufReader<TransferRate<io::Chain<&'a [u8], &'a ClientStream>>> {
+        BufReader::new(TransferRate::new(
+            request.buffered_body().chain(stream),
+            min_rate,
+        ))
+    }
+
+    ///
+    /// Copies the body into the writer, decoding a chunked body,
+    /// and refusing it with `413` once it grows past `max_size`
+    /// or `408` once the client times out.
+    ///
+    pub fn copy_body(
+        request: &Request,
+        body: &mut impl BufRead,
+        writer: &mut impl Write,
+        max_size: u64,
+    ) -> Result<u64, (u16, String)> {
+        let too_large = || (413, format!("Uploads are limited to {} bytes.", max_size));
+        let read_error = |e: io::Error| match Timeouts::is_client_timeout(&e) {
+            true => (
+                408,
+                format!("The client took too long to send the body, {}.", e),
+            ),
+            false => (400, format!("Unable to read the body. {}", e)),
+        };
+        let copy_error = |e: io::Error| match Timeouts::is_client_timeout(&e) {
+            true => read_error(e),
+            false => (500, format!("Unable to write the body. {}", e)),
+        };
+
+        if !request.is_chunked() {
+            let content_length = request.content_length().unwrap_or_default() as u64;
+            let copied =
+                io::copy(&mut body.by_ref().take(content_length), writer).map_err(copy_error)?;
+            if copied < content_length {
+                return Err((
+                    400,
+                    String::from("The client closed the connection before sending the whole body."),
+                ));
+            }
+            return Ok(copied);
+        }
+
+        let mut copied: u64 = 0;
+        loop {
+            let mut size_line = String::new();
+            if body.read_line(&mut size_line).map_err(read_error)? == 0 {
+                return Err((400, String::from("The chunked body ended early.")));
+            }
+
+            let size = size_line
+                .trim()
+                .split(';')
+                .next()
+                .unwrap_or_default()
+                .trim();
+            let size = u64::from_str_radix(size, 16)
+                .map_err(|_| (400, format!("Invalid chunk size {}.", size)))?;
+
+            if size == 0 {
+                // Skip any trailers up to the blank line that ends the body
+                loop {
+                    let mut trailer_line = String::new();
+                    if body.read_line(&mut trailer_line).map_err(read_error)? == 0
+                        || trailer_line.trim_end().is_empty()
+                    {
+                        return Ok(copied);
+                    }
+                }
+            }
+
+            copied = copied.checked_add(size).ok_or_else(too_large)?;
+            if copied > max_size {
+                return Err(too_large());
+            }
+
+            let chunk_copied =
+                io::copy(&mut body.by_ref().take(size), writer).map_err(copy_error)?;
+            if chunk_copied < size {
+                return Err((400, String::from("The chunked body ended early.")));
+            }
+
+            let mut chunk_end = [0u8; 2];
+            body.read_exact(&mut chunk_end).map_err(read_error)?;
+        }
+    }
+
+    fn multipart_boundary(request: &Request) -> Option<String> {
+        let content_type = request
+            .headers()
+            .map
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case("Content-Type"))
+            .map(|(_, value)| value)?;
+
+        let mut parameters = content_type.split(';').map(|parameter| parameter.trim());
+        if !parameters
+            .next()?
+            .eq_ignore_ascii_case("multipart/form-data")
+        {
+            return None;
+        }
+
+        parameters
+            .filter_map(|parameter| parameter.split_once('='))
+            .find(|(key, _)| key.trim().eq_ignore_ascii_case("boundary"))
+            .map(|(_, boundary)| String::from(boundary.trim().trim_matches('"')))
+            .filter(|boundary| !boundary.is_empty())
+    }
+
+    ///
+    /// Splits a multipart body into the filename and contents of each file part,
+    /// skipping plain form fields.
+    ///
+    fn parse_multipart<'a>(
+        body: &'a [u8],
+        boundary: &str,
+    ) -> Result<Vec<MultipartFile<'a>>, (u16, String)> {
+        let malformed = || (400, String::from("The multipart body is malformed."));
+        let delimiter = format!("--{}", boundary).into_bytes();
+        let part_delimiter = format!("\r\n--{}", boundary).into_bytes();
+
+        let mut position =
+            Self::find_bytes(body, &delimiter, 0).ok_or_else(malformed)? + delimiter.len();
+        let mut files: Vec<MultipartFile> = vec![];
+
+        loop {
+            if body[position..].starts_with(b"--") {
+                return Ok(files);
+            }
+            if !body[position..].starts_with(b"\r\n") {
+                return Err(malformed());
+            }
+            position += 2;
+
+            let head_end = Self::find_bytes(body, b"\r\n\r\n", position).ok_or_else(malformed)?;
+            let head = String::from_utf8_lossy(&body[position..head_end]);
+            let contents_start = head_end + 4;
+            let contents_end =
+                Self::find_bytes(body, &part_delimiter, contents_start).ok_or_else(malformed)?;
+
+            if let Some(filename) = Self::part_filename(&head) {
+                files.push((filename, &body[contents_start..contents_end]));
+            }
+
+            position = contents_end + part_delimiter.len();
+        }
+    }
+
+    fn part_filename(head: &str) -> Option<String> {
+        let disposition = head.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim()
+                .eq_ignore_ascii_case("Content-Disposition")
+                .then(|| value.trim())
+        })?;
+
+        disposition
+            .split(';')
+            .filter_map(|parameter| parameter.trim().split_once('='))
+            .find(|(key, _)| key.trim().eq_ignore_ascii_case("filename"))
+            .map(|(_, filename)| String::from(filename.trim().trim_matches('"')))
+            .filter(|filename| !filename.is_empty())
+    }
+
+    fn find_bytes(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+        haystack
+            .get(from..)?
+            .windows(needle.len())
+            .position(|window| window == needle)
+            .map(|position| position + from)
+    }
+
+    pub fn build_response(status: u16, body: Option<Value>, headers: &[(&str, &str)]) -> Response {
+        let body = body.map(|body| body.to_string()).unwrap_or_default();
+        let mut response_headers = Headers::new(vec![(
+            String::from("Content-Length"),
+            body.len().to_string(),
+        )]);
+        if !body.is_empty() {
+            response_headers.map.insert(
+                String::from("Content-Type"),
+                Headers::format_content_type_header_based_on_request_path(".json"),
+            );
+        }
+        for (key, value) in headers {
+            response_headers
+                .map
+                .insert(String::from(*key), String::from(*value));
+        }
+
+        Response::new(
+            String::from("HTTP/1.1"),
+            status,
+            String::from(StatusCode::reason_phrase(status)),
+            response_headers.map,
+            FileLike::TextFile(body),
+            false,
+        )
+    }
+}
@@ -0,0 +1,414 @@
+use serde_json::Value;
+
+use crate::directory::Directory;
+use crate::static_directory_manager::StaticDirectoryManager;
+
+/// # VirtualHost
+///
+/// A site served for one hostname (or a wildcard of subdomains),
+/// with its own `StaticDirectoryManager` and outgoing headers.
+///
+/// Virtual hosts are supplied as `--vhost=<hostname>:<directory>[,option...]`, i.e.
+///
+/// ```sh
+/// $ rsrv --dir=public --vhost=blog.local:./blog,fallback=index.html --vhost=*.docs.local:./docs,header=X-Frame-Options=DENY
+/// ```
+///
+/// Repeating `--vhost=` for the same hostname adds another root to that site.
+/// The config file takes `[[vhosts]]` tables instead, see `VirtualHost::from_json`.
+/// Supported options are
+///
+/// - `index=<file>` served for paths ending in `/`, defaults to the global index file
+/// - `fallback=<file>` served when a file is not found in any of the site's roots
+/// - `header=<name>=<value>` added to every response from the site
+/// - `cert=<file>` and `key=<file>` the PEM certificate and key presented over TLS
+///   to clients asking for this hostname, instead of the `--tls-cert` one
+///
+#[derive(Debug, Clone)]
+pub struct VirtualHost {
+    pub hostname: String,
+    pub static_directory_manager: StaticDirectoryManager,
+    pub headers: Vec<(String, String)>,
+    pub tls_cert: Option<String>,
+    pub tls_key: Option<String>,
+}
+
+impl VirtualHost {
+    pub fn new(hostname: &str, static_directory_manager: StaticDirectoryManager) -> Self {
+        VirtualHost {
+            hostname: hostname.to_lowercase(),
+            static_directory_manager,
+            headers: vec![],
+            tls_cert: None,
+            tls_key: None,
+        }
+    }
+
+    ///
+    /// Applies a single `--vhost=` argument, either to an existing site
+    /// with the same hostname or to a freshly created one.
+    ///
+    pub fn apply_argument(
+        virtual_hosts: &mut Vec<VirtualHost>,
+        vhost_argument: &str,
+        default_backup_file: &str,
+        default_index_file: &str,
+    ) -> Result<(), String> {
+        let mut options = vhost_argument.split(',');
+        let route = options.next().unwrap_or_default();
+        let (hostname, directory) = route.split_once(':').ok_or_else(|| {
+            format!(
+                "VirtualHost::apply_argument() Exception: Expected <hostname>:<directory>, received {}",
+                vhost_argument
+            )
+        })?;
+
+        if hostname.is_empty() || directory.is_empty() {
+            return Err(format!(
+                "VirtualHost::apply_argument() Exception: Virtual host {} is missing a hostname or directory.",
+                vhost_argument
+            ));
+        }
+
+        let hostname = hostname.to_lowercase();
+        let position = match virtual_hosts
+            .iter()
+            .position(|virtual_host| virtual_host.hostname == hostname)
+        {
+            Some(position) => position,
+            None => {
+                virtual_hosts.push(VirtualHost::new(
+                    &hostname,
+                    StaticDirectoryManager {
+                        directories: vec![],
+                        mounts: vec![],
+                        backup_file: String::from(default_backup_file),
+                        index_file: String::from(default_index_file),
+                    },
+                ));
+                virtual_hosts.len() - 1
+            }
+        };
+
+        let virtual_host = &mut virtual_hosts[position];
+        virtual_host
+            .static_directory_manager
+            .directories
+            .push(Directory::get_absolute_path(directory));
+
+        for option in options {
+            match option.split_once('=') {
+                Some(("index", index_file)) => {
+                    virtual_host.static_directory_manager.index_file =
+                        String::from(index_file.trim_start_matches('/'))
+                }
+                Some(("fallback", fallback)) => {
+                    virtual_host.static_directory_manager.backup_file =
+                        String::from(fallback.trim_start_matches('/'))
+                }
+                Some(("cert", cert)) if !cert.is_empty() => {
+                    virtual_host.tls_cert = Some(String::from(cert))
+                }
+                Some(("key", key)) if !key.is_empty() => {
+                    virtual_host.tls_key = Some(String::from(key))
+                }
+                Some(("header", header)) => match header.split_once('=') {
+                    Some((key, value)) => virtual_host
+                        .headers
+                        .push((String::from(key.trim()), String::from(value.trim()))),
+                    None => {
+                        return Err(format!(
+                            "VirtualHost::apply_argument() Exception: Expected header=<name>=<value>, received {}",
+                            option
+                        ))
+                    }
+                },
+                _ => {
+                    return Err(format!(
+                        "VirtualHost::apply_argument() Exception: Unknown virtual host option {} in {}",
+                        option, vhost_argument
+                    ))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Parses a `[[vhosts]]` table from the config file.
+    ///
+    /// ```toml
+    /// [[vhosts]]
+    /// hostname = "blog.local"
+    /// directories = ["./blog"]
+    /// index = "index.html"
+    /// fallback = "index.html"
+    /// headers = { "X-Frame-Options" = "DENY" }
+    /// tls_cert = "certs/blog.local.pem"
+    /// tls_key = "certs/blog.local-key.pem"
+    /// ```
+    ///
+    pub fn from_json(
+        value: &Value,
+        default_backup_file: &str,
+        default_index_file: &str,
+    ) -> Result<Self, String> {
+        let table = value.as_object().ok_or_else(|| {
+            format!(
+                "VirtualHost::from_json() Exception: Expected a table with a hostname and directories, received {}",
+                value
+            )
+        })?;
+
+        if let Some(key) = table.keys().find(|key| {
+            ![
+                "hostname",
+                "directories",
+                "index",
+                "fallback",
+                "headers",
+                "tls_cert",
+                "tls_key",
+            ]
+            .contains(&key.as_str())
+        }) {
+            return Err(format!(
+                "VirtualHost::from_json() Exception: Unknown virtual host option {}",
+                key
+            ));
+        }
+
+        let hostname = value["hostname"]
+            .as_str()
+            .filter(|hostname| !hostname.is_empty())
+            .ok_or_else(|| {
+                String::from(
+                    "VirtualHost::from_json() Exception: Virtual host is missing a \"hostname\".",
+                )
+            })?;
+
+        let directories = match &value["directories"] {
+            Value::Array(directories) if !directories.is_empty() => directories
+                .iter()
+                .map(|directory| {
+                    directory
+                        .as_str()
+                        .map(Directory::get_absolute_path)
+                        .ok_or_else(|| {
+                            format!(
+                                "VirtualHost::from_json() Exception: Directories must be strings for virtual host {}",
+                                hostname
+                            )
+                        })
+                })
+                .collect::<Result<Vec<String>, String>>()?,
+            _ => {
+                return Err(format!(
+                    "VirtualHost::from_json() Exception: Virtual host {} needs a non empty \"directories\" array.",
+                    hostname
+                ))
+            }
+        };
+
+        let mut files: Vec<String> = vec![];
+        for (key, default_file) in [
+            ("index", default_index_file),
+            ("fallback", default_backup_file),
+        ] {
+            match &value[key] {
+                Value::Null => files.push(String::from(default_file)),
+                Value::String(file) => files.push(String::from(file.trim_start_matches('/'))),
+                _ => {
+                    return Err(format!(
+                        "VirtualHost::from_json() Exception: \"{}\" must be a string for virtual host {}",
+                        key, hostname
+                    ))
+                }
+            }
+        }
+        let backup_file = files.pop().unwrap_or_default();
+        let index_file = files.pop().unwrap_or_default();
+
+        let mut virtual_host = VirtualHost::new(
+            hostname,
+            StaticDirectoryManager {
+                directories,
+                mounts: vec![],
+                backup_file,
+                index_file,
+            },
+        );
+
+        match &value["headers"] {
+            Value::Null => (),
+            Value::Object(headers) => {
+                for (key, header_value) in headers {
+                    let header_value = header_value.as_str().ok_or_else(|| {
+                        format!(
+                            "VirtualHost::from_json() Exception: Header {} must be a string for virtual host {}",
+                            key, hostname
+                        )
+                    })?;
+                    virtual_host
+                        .headers
+                        .push((key.clone(), String::from(header_value)));
+                }
+            }
+            _ => {
+                return Err(format!(
+                    "VirtualHost::from_json() Exception: \"headers\" must be a table for virtual host {}",
+                    hostname
+                ))
+            }
+        }
+
+        for key in ["tls_cert", "tls_key"] {
+            let file = match &value[key] {
+                Value::Null => None,
+                Value::String(file) if !file.is_empty() => Some(file.clone()),
+                _ => {
+                    return Err(format!(
+                        "VirtualHost::from_json() Exception: \"{}\" must be a file path for virtual host {}",
+                        key, hostname
+                    ))
+                }
+            };
+            match key {
+                "tls_cert" => virtual_host.tls_cert = file,
+                _ => virtual_host.tls_key = file,
+            }
+        }
+
+        Ok(virtual_host)
+    }
+
+    ///
+    /// Whether this site answers for the supplied hostname.
+    ///
+    pub fn matches(&self, hostname: &str) -> bool {
+        Self::pattern_matches(&self.hostname, hostname)
+    }
+
+    ///
+    /// Whether a hostname pattern covers the supplied hostname.
+    ///
+    /// `*.example.com` matches any subdomain of `example.com`, but not `example.com` itself.
+    ///
+    pub fn pattern_matches(pattern: &str, hostname: &str) -> bool {
+        match pattern.strip_prefix("*.") {
+            Some(domain) => hostname
+                .strip_suffix(domain)
+                .map(|subdomain| subdomain.len() > 1 && subdomain.ends_with('.'))
+                .unwrap_or(false),
+            None => pattern == hostname,
+        }
+    }
+
+    pub fn is_wildcard(&self) -> bool {
+        self.hostname.starts_with("*.")
+    }
+
+    pub fn describe(&self) -> Vec<String> {
+        let static_directory_manager = &self.static_directory_manager;
+        let mut lines = vec![format!(
+            "{} [index: {}, fallback: {}]",
+            self.hostname,
+            static_directory_manager.index_file,
+            static_directory_manager.backup_file
+        )];
+        for mount in &static_directory_manager.mounts {
+            lines.push(format!("  {}", mount.describe()));
+        }
+        for directory in &static_directory_manager.directories {
+            lines.push(format!("  / -> {}", directory));
+        }
+        for (key, value) in &self.headers {
+            lines.push(format!("  header {}: {}", key, value));
+        }
+        if let Some(tls_cert) = &self.tls_cert {
+            lines.push(format!("  tls certificate {}", tls_cert));
+        }
+        lines
+    }
+}
+
+/// # VirtualHosts
+///
+/// Routes requests to a `VirtualHost` based on their `Host` header.
+///
+/// Exact hostnames take priority over wildcards, and longer wildcards over shorter ones.
+/// Requests that match no site, or carry no `Host` header, are served by the default host,
+/// which is either the site named by `--default-host=` or the top level `--dir`/`--mount` roots.
+///
+#[derive(Debug, Clone)]
+pub struct VirtualHosts {
+    pub hosts: Vec<VirtualHost>,
+    pub default_host: VirtualHost,
+}
+
+impl VirtualHosts {
+    pub fn new(
+        hosts: Vec<VirtualHost>,
+        default_hostname: Option<String>,
+        default_static_directory_manager: StaticDirectoryManager,
+    ) -> Result<Self, String> {
+        let default_host = match default_hostname {
+            Some(default_hostname) => hosts
+                .iter()
+                .find(|virtual_host| virtual_host.hostname == default_hostname.to_lowercase())
+                .cloned()
+                .ok_or_else(|| {
+                    format!(
+                        "VirtualHosts::new() Exception: The default host {} does not name a virtual host.",
+                        default_hostname
+                    )
+                })?,
+            None => VirtualHost::new("*", default_static_directory_manager),
+        };
+
+        Ok(VirtualHosts {
+            hosts,
+            default_host,
+        })
+    }
+
+    pub fn resolve(&self, host_header: Option<&String>) -> &VirtualHost {
+        let hostname = match host_header {
+            Some(host_header) => Self::strip_port(host_header).to_lowercase(),
+            None => return &self.default_host,
+        };
+
+        if let Some(virtual_host) = self
+            .hosts
+            .iter()
+            .find(|virtual_host| !virtual_host.is_wildcard() && virtual_host.matches(&hostname))
+        {
+            return virtual_host;
+        }
+
+        self.hosts
+            .iter()
+            .filter(|virtual_host| virtual_host.is_wildcard() && virtual_host.matches(&hostname))
+            .max_by_key(|virtual_host| virtual_host.hostname.len())
+            .unwrap_or(&self.default_host)
+    }
+
+    ///
+    /// Removes the port from a `Host` header, i.e. `example.com:8080` or `[::1]:8080`
+    ///
+    pub fn strip_port(host_header: &str) -> &str {
+        let host_header = host_header.trim();
+        if host_header.starts_with('[') {
+            return match host_header.find(']') {
+                Some(end) => &host_header[..=end],
+                None => host_header,
+            };
+        }
+
+        match host_header.rsplit_once(':') {
+            Some((hostname, _)) => hostname,
+            None => host_header,
+        }
+    }
+}
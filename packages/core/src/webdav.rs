@@ -0,0 +1,1268 @@
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+use chrono::{DateTime, Utc};
+use roxmltree::{Document, Node};
+
+use crate::client_stream::ClientStream;
+use crate::config::Config;
+use crate::error_page::ErrorPage;
+use crate::file_manager::FileManager;
+use crate::filelike::FileLike;
+use crate::headers::Headers;
+use crate::logger::Logger;
+use crate::request::{HttpMethod, Request};
+use crate::response::Response;
+use crate::static_directory_manager::StaticDirectoryManager;
+use crate::status::StatusCode;
+use crate::upload::Upload;
+use crate::webdav_locks::{Lock, WebDavLocks};
+use crate::webdav_properties::{Properties, PropertyStore};
+
+pub const DEFAULT_PROPS_FILE: &str = ".rsrv-webdav-props.json";
+
+/// File managers prompt for a username and password, the password is the upload token
+pub const BASIC_CHALLENGE: &str = "Basic realm=\"rsrv\"";
+
+const DAV_NAMESPACE: &str = "DAV:";
+
+/// Property and lock requests are small XML documents, anything larger is refused
+const MAX_XML_BODY_SIZE: u64 = 1024 * 1024;
+
+const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+const MAX_LOCK_TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
+
+const WEBDAV_METHODS: [&str; 7] = [
+    "PROPFIND",
+    "PROPPATCH",
+    "MKCOL",
+    "COPY",
+    "MOVE",
+    "LOCK",
+    "UNLOCK",
+];
+
+const ALLOWED_METHODS: &str =
+    "OPTIONS, GET, HEAD, PUT, DELETE, PROPFIND, PROPPATCH, MKCOL, COPY, MOVE, LOCK, UNLOCK";
+
+const LIVE_PROPERTIES: [&str; 9] = [
+    "creationdate",
+    "displayname",
+    "getcontentlength",
+    "getcontenttype",
+    "getetag",
+    "getlastmodified",
+    "resourcetype",
+    "supportedlock",
+    "lockdiscovery",
+];
+
+const SUPPORTED_LOCK: &str = "<D:lockentry><D:lockscope><D:exclusive/></D:lockscope><D:locktype><D:write/></D:locktype></D:lockentry><D:lockentry><D:lockscope><D:shared/></D:lockscope><D:locktype><D:write/></D:locktype></D:lockentry>";
+
+const XML_DECLARATION: &str = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n";
+
+/// # WebDavSettings
+///
+/// Whether the served directories are shared over WebDAV, enabled with `--webdav`,
+/// `RSRV_WEBDAV` or a `[webdav]` table. WebDAV writes take the upload token, so
+/// `uploads.token` must be set as well.
+///
+/// ```toml
+/// [webdav]
+/// enabled = true
+/// props_file = ".rsrv-webdav-props.json"
+/// ```
+///
+/// `props_file` is where properties set by clients are kept, see `PropertyStore`.
+///
+#[derive(Debug, Clone)]
+pub struct WebDavSettings {
+    pub enabled: bool,
+    pub props_file: String,
+}
+
+impl Default for WebDavSettings {
+    fn default() -> Self {
+        WebDavSettings {
+            enabled: false,
+            props_file: String::from(DEFAULT_PROPS_FILE),
+        }
+    }
+}
+
+impl WebDavSettings {
+    ///
+    /// Whether a file name is rsrv's own bookkeeping, the property store or an
+    /// upload in progress, which WebDAV clients never see.
+    ///
+    pub fn is_hidden(&self, name: &str) -> bool {
+        let props_file_name = Path::new(&self.props_file)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        name == props_file_name
+            || name == format!("{}.tmp", props_file_name)
+            || name.contains(".rsrv-upload-")
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "WebDAV enabled (class 1 and 2): properties in {}, Basic credentials with the upload token as password",
+            self.props_file
+        )
+    }
+}
+
+/// # PropfindQuery
+///
+/// The properties a `PROPFIND` asks for.
+///
+#[derive(Debug)]
+enum PropfindQuery {
+    AllProp,
+    PropName,
+    /// Property names as `{namespace}name`
+    Prop(Vec<String>),
+}
+
+/// # WebDav
+///
+/// A functional struct that serves the directories to WebDAV clients, such as file
+/// managers mounting rsrv as a network drive, over the same path confinement as uploads.
+///
+/// - `PROPFIND` lists properties as a `207 Multi-Status`, with a `Depth` of `0` or `1`
+/// - `PROPPATCH` sets and removes properties, kept in the `PropertyStore`
+/// - `MKCOL` creates a directory, `PUT` writes a file and `DELETE` removes either
+/// - `COPY` and `MOVE` take a `Destination` header and honour `Overwrite: F`
+/// - `LOCK` and `UNLOCK` take and release write locks, see `WebDavLocks`
+///
+/// Every method but `OPTIONS` needs the upload token, as a bearer token or as the
+/// password of Basic credentials, with any username. Paths are percent-decoded and
+/// listings are percent-encoded, and `/` lists the first served directory.
+///
+/// ```sh
+/// $ curl -u dav:$TOKEN -X PROPFIND -H "Depth: 1" http://localhost:8080/docs/
+/// $ curl -u dav:$TOKEN -X MKCOL http://localhost:8080/docs/drafts/
+/// $ curl -u dav:$TOKEN -X MOVE -H "Destination: /docs/final/" http://localhost:8080/docs/drafts/
+/// ```
+///
+pub struct WebDav;
+
+/// The decoded request path and what every method needs to act on it
+struct WebDavContext<'a> {
+    path: String,
+    static_directory_manager: &'a StaticDirectoryManager,
+    settings: &'a WebDavSettings,
+    locks: &'a WebDavLocks,
+    /// Lock tokens submitted in the `If` header
+    tokens: Vec<String>,
+    /// The slowest an XML body may arrive
+    min_rate: Option<u64>,
+}
+
+impl WebDav {
+    pub fn handles(request: &Request) -> bool {
+        match request.method() {
+            HttpMethod::OPTIONS | HttpMethod::PUT | HttpMethod::DELETE => true,
+            HttpMethod::Other(method) => WEBDAV_METHODS.contains(&method.as_str()),
+            _ => false,
+        }
+    }
+
+    pub fn handle(
+        request: &Request,
+        static_directory_manager: &StaticDirectoryManager,
+        config: &Config,
+        locks: &WebDavLocks,
+        stream: &mut ClientStream,
+    ) {
+        if request.method() == &HttpMethod::OPTIONS {
+            return Upload::build_response(
+                200,
+                None,
+                &[
+                    ("DAV", "1, 2"),
+                    ("Allow", ALLOWED_METHODS),
+                    ("MS-Author-Via", "DAV"),
+                ],
+            )
+            .respond(stream);
+        }
+
+        if !config.uploads.authorizes(request) {
+            return Upload::respond_unauthorized(
+                request,
+                BASIC_CHALLENGE,
+                static_directory_manager,
+                config,
+                stream,
+            );
+        }
+
+        let result = Self::decode_path(request.path(), &config.webdav).and_then(|path| {
+            let context = WebDavContext {
+                path,
+                static_directory_manager,
+                settings: &config.webdav,
+                locks,
+                tokens: Self::submitted_tokens(request),
+                min_rate: config.timeouts.min_rate,
+            };
+
+            match request.method().as_str() {
+                "PUT" => Self::put(request, &context, config, stream),
+                "DELETE" => Self::delete(&context),
+                "PROPFIND" => Self::propfind(request, &context, stream),
+                "PROPPATCH" => Self::proppatch(request, &context, stream),
+                "MKCOL" => Self::mkcol(request, &context),
+                "COPY" => Self::copy_or_move(request, &context, false),
+                "MOVE" => Self::copy_or_move(request, &context, true),
+                "LOCK" => Self::lock(request, &context, stream),
+                _ => Self::unlock(request, &context),
+            }
+        });
+
+        Upload::respond_with_result(result, request, static_directory_manager, config, stream);
+    }
+
+    ///
+    /// Percent-decodes a request path, refusing `.` and `..` segments and hiding rsrv's own files.
+    ///
+    pub fn decode_path(path: &str, settings: &WebDavSettings) -> Result<String, (u16, String)> {
+        let invalid = || (400, format!("Invalid percent-encoding in {}.", path));
+        let mut decoded: Vec<u8> = Vec::with_capacity(path.len());
+        let mut position = 0;
+
+        while position < path.len() {
+            let byte = path.as_bytes()[position];
+            if byte == b'%' {
+                let hex = path
+                    .get(position + 1..position + 3)
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(invalid)?;
+                decoded.push(hex);
+                position += 3;
+            } else {
+                decoded.push(byte);
+                position += 1;
+            }
+        }
+
+        let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+        for segment in decoded.split('/') {
+            if segment == "." || segment == ".." || segment.contains(['\\', '\0']) {
+                return Err((403, format!("Refusing the path {}.", path)));
+            }
+            if settings.is_hidden(segment) {
+                return Err((
+                    404,
+                    format!("The requested path {} could not be found.", path),
+                ));
+            }
+        }
+
+        Ok(decoded)
+    }
+
+    ///
+    /// Percent-encodes a path for an href, leaving only unreserved characters and `/`.
+    ///
+    fn encode_href(path: &str) -> String {
+        path.bytes()
+            .map(|byte| match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                    String::from(byte as char)
+                }
+                _ => format!("%{:02X}", byte),
+            })
+            .collect()
+    }
+
+    fn href(path: &str, is_collection: bool) -> String {
+        let mut href = Self::encode_href(path);
+        if is_collection && !href.ends_with('/') {
+            href.push('/');
+        }
+        href
+    }
+
+    fn header<'a>(request: &'a Request, name: &str) -> Option<&'a String> {
+        request
+            .headers()
+            .map
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    fn depth(request: &Request) -> Option<String> {
+        Self::header(request, "Depth").map(|depth| depth.trim().to_lowercase())
+    }
+
+    ///
+    /// The `opaquelocktoken:` uris in the `If` header, ignoring the rest of its conditions.
+    ///
+    fn submitted_tokens(request: &Request) -> Vec<String> {
+        Self::header(request, "If")
+            .map(|condition| {
+                condition
+                    .split('<')
+                    .filter_map(|part| part.split_once('>'))
+                    .map(|(token, _)| token.trim())
+                    .filter(|token| token.starts_with("opaquelocktoken:"))
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl WebDav {
+    fn propfind(
+        request: &Request,
+        context: &WebDavContext,
+        stream: &mut ClientStream,
+    ) -> Result<Response, (u16, String)> {
+        let depth = match Self::depth(request).as_deref() {
+            Some("0") => 0,
+            Some("1") => 1,
+            _ => {
+                return Ok(Self::xml_response(
+                    403,
+                    &format!(
+                        "{}<D:error xmlns:D=\"DAV:\"><D:propfind-finite-depth/></D:error>",
+                        XML_DECLARATION
+                    ),
+                    &[],
+                ))
+            }
+        };
+        let body = Self::read_xml_body(request, context, stream)?;
+        let query = Self::parse_propfind(&body)?;
+
+        let (root, target) =
+            Upload::resolve_target(&context.path, context.static_directory_manager)?;
+        let metadata = fs::metadata(&target)
+            .map_err(|_| (404, format!("{} does not exist.", context.path)))?;
+        let href = Self::href(&context.path, metadata.is_dir());
+        let store = PropertyStore::load(&context.settings.props_file);
+
+        let mut responses = vec![Self::propfind_response(
+            &href, &target, &metadata, &query, &store, context,
+        )];
+
+        if depth == 1 && metadata.is_dir() {
+            let mut entries: Vec<fs::DirEntry> = fs::read_dir(&target)
+                .map_err(|e| FileManager::io_error(&format!("Unable to list {}.", href), e))?
+                .filter_map(Result::ok)
+                .collect();
+            entries.sort_by_key(|entry| entry.file_name());
+
+            for entry in entries {
+                let name = entry.file_name().to_string_lossy().to_string();
+                // Links are listed as what they point at, unless that is outside the root
+                if context.settings.is_hidden(&name)
+                    || Upload::ensure_confined(&root, &entry.path()).is_err()
+                {
+                    continue;
+                }
+                let entry_metadata = match fs::metadata(entry.path()) {
+                    Ok(metadata) => metadata,
+                    Err(_) => continue,
+                };
+                let entry_href = Self::href(
+                    &format!("{}/{}", context.path.trim_end_matches('/'), name),
+                    entry_metadata.is_dir(),
+                );
+                responses.push(Self::propfind_response(
+                    &entry_href,
+                    &entry.path(),
+                    &entry_metadata,
+                    &query,
+                    &store,
+                    context,
+                ));
+            }
+        }
+
+        Ok(Self::multistatus(&responses))
+    }
+
+    fn parse_propfind(body: &str) -> Result<PropfindQuery, (u16, String)> {
+        if body.trim().is_empty() {
+            return Ok(PropfindQuery::AllProp);
+        }
+
+        let document = Self::parse_xml(body)?;
+        let propfind = document.root_element();
+        if !Self::is_dav_element(propfind, "propfind") {
+            return Err((400, String::from("Expected a DAV: propfind body.")));
+        }
+
+        for child in propfind.children().filter(Node::is_element) {
+            if Self::is_dav_element(child, "allprop") {
+                return Ok(PropfindQuery::AllProp);
+            }
+            if Self::is_dav_element(child, "propname") {
+                return Ok(PropfindQuery::PropName);
+            }
+            if Self::is_dav_element(child, "prop") {
+                return Ok(PropfindQuery::Prop(
+                    child
+                        .children()
+                        .filter(Node::is_element)
+                        .map(Self::qualified_name)
+                        .collect(),
+                ));
+            }
+        }
+
+        Err((
+            400,
+            String::from("The propfind body needs an allprop, propname or prop element."),
+        ))
+    }
+
+    fn propfind_response(
+        href: &str,
+        path: &Path,
+        metadata: &Metadata,
+        query: &PropfindQuery,
+        store: &std::collections::HashMap<String, Properties>,
+        context: &WebDavContext,
+    ) -> String {
+        let dead_properties = PropertyStore::properties(store, path);
+        let live_property = |name: &str| Self::live_property(name, path, metadata, context);
+        let mut found: Vec<String> = vec![];
+        let mut missing: Vec<String> = vec![];
+
+        match query {
+            PropfindQuery::AllProp | PropfindQuery::PropName => {
+                let names_only = matches!(query, PropfindQuery::PropName);
+                for name in LIVE_PROPERTIES {
+                    if let Some(value) = live_property(name) {
+                        let value = if names_only { String::new() } else { value };
+                        found.push(Self::property_element(
+                            &format!("{{{}}}{}", DAV_NAMESPACE, name),
+                            &value,
+                        ));
+                    }
+                }
+                for (name, value) in dead_properties.into_iter().flatten() {
+                    let value = if names_only {
+                        String::new()
+                    } else {
+                        ErrorPage::escape_html(value)
+                    };
+                    found.push(Self::property_element(name, &value));
+                }
+            }
+            PropfindQuery::Prop(names) => {
+                for name in names {
+                    let value = match Self::split_name(name) {
+                        (DAV_NAMESPACE, local_name) => live_property(local_name),
+                        _ => None,
+                    }
+                    .or_else(|| {
+                        dead_properties
+                            .and_then(|properties| properties.get(name))
+                            .map(|value| ErrorPage::escape_html(value))
+                    });
+
+                    match value {
+                        Some(value) => found.push(Self::property_element(name, &value)),
+                        None => missing.push(Self::property_element(name, "")),
+                    }
+                }
+            }
+        }
+
+        Self::response_element(href, &[(200, found), (404, missing)])
+    }
+
+    ///
+    /// A live property's value as XML, or `None` when it does not apply to the resource.
+    ///
+    fn live_property(
+        name: &str,
+        path: &Path,
+        metadata: &Metadata,
+        context: &WebDavContext,
+    ) -> Option<String> {
+        match name {
+            "creationdate" => metadata
+                .created()
+                .or_else(|_| metadata.modified())
+                .ok()
+                .map(|created| {
+                    DateTime::<Utc>::from(created)
+                        .format("%Y-%m-%dT%H:%M:%SZ")
+                        .to_string()
+                }),
+            "displayname" => path
+                .file_name()
+                .map(|name| ErrorPage::escape_html(&name.to_string_lossy())),
+            "getcontentlength" => metadata.is_file().then(|| metadata.len().to_string()),
+            "getcontenttype" => metadata.is_file().then(|| {
+                ErrorPage::escape_html(&Headers::format_content_type_header_based_on_request_path(
+                    &path.to_string_lossy(),
+                ))
+            }),
+            "getetag" => metadata.modified().ok().map(|modified| {
+                let modified = modified
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_nanos();
+                format!("&quot;{:x}-{:x}&quot;", metadata.len(), modified)
+            }),
+            "getlastmodified" => metadata.modified().ok().map(|modified| {
+                DateTime::<Utc>::from(modified)
+                    .format("%a, %d %b %Y %H:%M:%S GMT")
+                    .to_string()
+            }),
+            "resourcetype" => Some(if metadata.is_dir() {
+                String::from("<D:collection/>")
+            } else {
+                String::new()
+            }),
+            "supportedlock" => Some(String::from(SUPPORTED_LOCK)),
+            "lockdiscovery" => Some(
+                context
+                    .locks
+                    .locks_on(path)
+                    .iter()
+                    .map(Self::active_lock)
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+}
+
+impl WebDav {
+    fn proppatch(
+        request: &Request,
+        context: &WebDavContext,
+        stream: &mut ClientStream,
+    ) -> Result<Response, (u16, String)> {
+        let (_, target) = Upload::resolve_target(&context.path, context.static_directory_manager)?;
+        if !target.exists() {
+            return Err((404, format!("{} does not exist.", context.path)));
+        }
+        context
+            .locks
+            .ensure_unlocked(&target, false, &context.tokens)?;
+
+        let body = Self::read_xml_body(request, context, stream)?;
+        let document = Self::parse_xml(&body)?;
+        let property_update = document.root_element();
+        if !Self::is_dav_element(property_update, "propertyupdate") {
+            return Err((400, String::from("Expected a DAV: propertyupdate body.")));
+        }
+
+        // Instructions apply in document order, so a later remove undoes an earlier set
+        let mut changes: Vec<(String, Option<String>)> = vec![];
+        for instruction in property_update.children().filter(Node::is_element) {
+            let set = Self::is_dav_element(instruction, "set");
+            if !set && !Self::is_dav_element(instruction, "remove") {
+                continue;
+            }
+            for prop in instruction
+                .children()
+                .filter(|prop| Self::is_dav_element(*prop, "prop"))
+            {
+                for property in prop.children().filter(Node::is_element) {
+                    let value = set.then(|| Self::text_of(property));
+                    changes.push((Self::qualified_name(property), value));
+                }
+            }
+        }
+        if changes.is_empty() {
+            return Err((
+                400,
+                String::from("The propertyupdate body changes nothing."),
+            ));
+        }
+
+        let href = Self::href(&context.path, target.is_dir());
+        let mut names: Vec<&String> = vec![];
+        for (name, _) in &changes {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        let elements_of = |names: Vec<&&String>| -> Vec<String> {
+            names
+                .into_iter()
+                .map(|name| Self::property_element(name, ""))
+                .collect()
+        };
+
+        // Live properties cannot be changed, and then nothing is
+        let (protected, others): (Vec<&&String>, Vec<&&String>) = names
+            .iter()
+            .partition(|name| Self::split_name(name).0 == DAV_NAMESPACE);
+        if !protected.is_empty() {
+            return Ok(Self::multistatus(&[Self::response_element(
+                &href,
+                &[(403, elements_of(protected)), (424, elements_of(others))],
+            )]));
+        }
+
+        PropertyStore::update(&context.settings.props_file, &target, &changes)
+            .map_err(|e| (500, e))?;
+
+        Logger::info(&format!(
+            "Updated {} properties of {}",
+            names.len(),
+            context.path
+        ));
+        Ok(Self::multistatus(&[Self::response_element(
+            &href,
+            &[(200, elements_of(others))],
+        )]))
+    }
+
+    fn mkcol(request: &Request, context: &WebDavContext) -> Result<Response, (u16, String)> {
+        if request.content_length().unwrap_or_default() > 0 || request.is_chunked() {
+            return Err((415, String::from("MKCOL does not take a body.")));
+        }
+
+        let (root, target) =
+            Upload::resolve_target(&context.path, context.static_directory_manager)?;
+        if fs::symlink_metadata(&target).is_ok() {
+            return Err((405, format!("{} already exists.", context.path)));
+        }
+        let parent = target.parent().unwrap_or(&root);
+        if !parent.is_dir() {
+            return Err((
+                409,
+                format!("The parent collection of {} does not exist.", context.path),
+            ));
+        }
+        context
+            .locks
+            .ensure_unlocked(parent, false, &context.tokens)?;
+
+        fs::create_dir(&target).map_err(|e| {
+            FileManager::io_error(&format!("Unable to create {}.", context.path), e)
+        })?;
+
+        Logger::info(&format!(
+            "Created collection {} -> {}",
+            context.path,
+            target.display()
+        ));
+        Ok(Upload::build_response(201, None, &[]))
+    }
+
+    fn put(
+        request: &Request,
+        context: &WebDavContext,
+        config: &Config,
+        stream: &mut ClientStream,
+    ) -> Result<Response, (u16, String)> {
+        let (root, target) =
+            Upload::resolve_target(&context.path, context.static_directory_manager)?;
+        let parent = target.parent().unwrap_or(&root);
+        if !parent.is_dir() {
+            return Err((
+                409,
+                format!(
+                    "The parent collection of {} does not exist, create it with MKCOL.",
+                    context.path
+                ),
+            ));
+        }
+
+        context
+            .locks
+            .ensure_unlocked(&target, false, &context.tokens)?;
+        if !target.exists() {
+            context
+                .locks
+                .ensure_unlocked(parent, false, &context.tokens)?;
+        }
+
+        Upload::put(
+            request,
+            &context.path,
+            context.static_directory_manager,
+            config,
+            stream,
+        )
+    }
+
+    fn delete(context: &WebDavContext) -> Result<Response, (u16, String)> {
+        let (root, target) = Upload::resolve_path(&context.path, context.static_directory_manager)?;
+        if fs::symlink_metadata(&target).is_ok() {
+            context
+                .locks
+                .ensure_unlocked(&target, true, &context.tokens)?;
+            context.locks.ensure_unlocked(
+                target.parent().unwrap_or(&root),
+                false,
+                &context.tokens,
+            )?;
+        }
+
+        let response = FileManager::delete(&context.path, context.static_directory_manager)?;
+
+        PropertyStore::remove(&context.settings.props_file, &target);
+        context.locks.release(&target);
+        Ok(response)
+    }
+}
+
+impl WebDav {
+    fn copy_or_move(
+        request: &Request,
+        context: &WebDavContext,
+        is_move: bool,
+    ) -> Result<Response, (u16, String)> {
+        let method = request.method().as_str();
+        let destination = Self::destination_path(request, context.settings)?;
+        let overwrite = !Self::header(request, "Overwrite")
+            .map(|overwrite| overwrite.trim().eq_ignore_ascii_case("F"))
+            .unwrap_or(false);
+        let shallow = match Self::depth(request).as_deref() {
+            None | Some("infinity") => false,
+            Some("0") if !is_move => true,
+            _ => {
+                return Err((
+                    400,
+                    String::from("COPY takes a Depth of 0 or infinity, MOVE only infinity."),
+                ))
+            }
+        };
+
+        // Symbolic links are copied and moved themselves
+        let (source_root, source) =
+            Upload::resolve_path(&context.path, context.static_directory_manager)?;
+        let (destination_root, destination_target) =
+            Upload::resolve_path(&destination, context.static_directory_manager)?;
+
+        let source_metadata = fs::symlink_metadata(&source)
+            .map_err(|_| (404, format!("{} does not exist.", context.path)))?;
+        if source == destination_target {
+            return Err((
+                403,
+                format!("Unable to {} {} onto itself.", method, context.path),
+            ));
+        }
+        if (is_move && source == source_root) || destination_target == destination_root {
+            return Err((
+                403,
+                format!("Refusing to {} over a served directory.", method),
+            ));
+        }
+        if source_metadata.is_dir() && destination_target.starts_with(&source) {
+            return Err((
+                409,
+                format!("Unable to {} {} into itself.", method, context.path),
+            ));
+        }
+
+        let destination_parent = destination_target.parent().unwrap_or(&destination_root);
+        if !destination_parent.is_dir() {
+            return Err((
+                409,
+                format!("The parent collection of {} does not exist.", destination),
+            ));
+        }
+
+        let existed = fs::symlink_metadata(&destination_target).is_ok();
+        if existed && !overwrite {
+            return Err((
+                412,
+                format!("{} already exists and Overwrite is F.", destination),
+            ));
+        }
+
+        if is_move {
+            context
+                .locks
+                .ensure_unlocked(&source, true, &context.tokens)?;
+            context.locks.ensure_unlocked(
+                source.parent().unwrap_or(&source_root),
+                false,
+                &context.tokens,
+            )?;
+        }
+        context
+            .locks
+            .ensure_unlocked(&destination_target, true, &context.tokens)?;
+        if !existed {
+            context
+                .locks
+                .ensure_unlocked(destination_parent, false, &context.tokens)?;
+        }
+
+        let failed = |e: io::Error| {
+            FileManager::io_error(
+                &format!("Unable to {} {} to {}.", method, context.path, destination),
+                e,
+            )
+        };
+        let props_file = &context.settings.props_file;
+
+        // The destination is replaced whole, not merged into
+        if existed {
+            Self::remove_resource(&destination_target).map_err(failed)?;
+            PropertyStore::remove(props_file, &destination_target);
+            context.locks.release(&destination_target);
+        }
+
+        if is_move {
+            // Served directories may be on different file systems, where renaming fails
+            if fs::rename(&source, &destination_target).is_err() {
+                Self::copy_resource(&source, &destination_target, false, context.settings)
+                    .and_then(|_| Self::remove_resource(&source))
+                    .map_err(failed)?;
+            }
+            PropertyStore::rename(props_file, &source, &destination_target);
+            context.locks.release(&source);
+        } else {
+            Self::copy_resource(&source, &destination_target, shallow, context.settings)
+                .map_err(failed)?;
+            PropertyStore::copy(props_file, &source, &destination_target, shallow);
+        }
+
+        Logger::info(&format!(
+            "{} {} -> {} ({} -> {})",
+            if is_move { "Moved" } else { "Copied" },
+            context.path,
+            destination,
+            source.display(),
+            destination_target.display()
+        ));
+        Ok(Upload::build_response(
+            if existed { 204 } else { 201 },
+            None,
+            &[],
+        ))
+    }
+
+    ///
+    /// The decoded path of the `Destination` header, an absolute url on this server or a path.
+    ///
+    fn destination_path(
+        request: &Request,
+        settings: &WebDavSettings,
+    ) -> Result<String, (u16, String)> {
+        let destination = Self::header(request, "Destination").ok_or_else(|| {
+            (
+                400,
+                String::from("COPY and MOVE need a Destination header."),
+            )
+        })?;
+
+        let path = match destination.split_once("://") {
+            Some((_, url)) => {
+                let (authority, path) = url.split_at(url.find('/').unwrap_or(url.len()));
+                // HTTP/2 requests carry their :authority as the Host header
+                match Self::header(request, "Host") {
+                    Some(host) if host.eq_ignore_ascii_case(authority) => (),
+                    Some(_) => {
+                        return Err((
+                            502,
+                            format!("The destination {} is on another server.", destination),
+                        ))
+                    }
+                    None => {
+                        return Err((
+                            502,
+                            format!(
+                                "The destination {} can't be told apart from another server without a Host header.",
+                                destination
+                            ),
+                        ))
+                    }
+                }
+                path
+            }
+            None => destination.as_str(),
+        };
+        let path = path.split(['?', '#']).next().unwrap_or_default();
+        if !path.starts_with('/') {
+            return Err((400, format!("Invalid destination {}.", destination)));
+        }
+
+        Self::decode_path(path, settings)
+    }
+
+    fn copy_resource(
+        from: &Path,
+        to: &Path,
+        shallow: bool,
+        settings: &WebDavSettings,
+    ) -> io::Result<()> {
+        let metadata = fs::symlink_metadata(from)?;
+
+        if metadata.file_type().is_symlink() {
+            return Self::copy_link(from, to);
+        }
+        if !metadata.is_dir() {
+            return fs::copy(from, to).map(|_| ());
+        }
+
+        fs::create_dir(to)?;
+        if shallow {
+            return Ok(());
+        }
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            if settings.is_hidden(&entry.file_name().to_string_lossy()) {
+                continue;
+            }
+            Self::copy_resource(&entry.path(), &to.join(entry.file_name()), false, settings)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn copy_link(from: &Path, to: &Path) -> io::Result<()> {
+        std::os::unix::fs::symlink(fs::read_link(from)?, to)
+    }
+
+    #[cfg(not(unix))]
+    fn copy_link(from: &Path, to: &Path) -> io::Result<()> {
+        fs::copy(from, to).map(|_| ())
+    }
+
+    fn remove_resource(path: &Path) -> io::Result<()> {
+        if fs::symlink_metadata(path)?.is_dir() {
+            fs::remove_dir_all(path)
+        } else {
+            fs::remove_file(path)
+        }
+    }
+}
+
+impl WebDav {
+    fn lock(
+        request: &Request,
+        context: &WebDavContext,
+        stream: &mut ClientStream,
+    ) -> Result<Response, (u16, String)> {
+        let timeout = Self::lock_timeout(request);
+        let body = Self::read_xml_body(request, context, stream)?;
+        let (root, target) =
+            Upload::resolve_target(&context.path, context.static_directory_manager)?;
+
+        // Without a body, the lock named in the If header is refreshed
+        if body.trim().is_empty() {
+            let lock = context
+                .locks
+                .refresh(&target, &context.tokens, timeout)
+                .ok_or_else(|| {
+                    (
+                        412,
+                        format!(
+                            "Refreshing a lock on {} needs its token in the If header.",
+                            context.path
+                        ),
+                    )
+                })?;
+            return Ok(Self::lock_response(200, &lock));
+        }
+
+        let infinite = match Self::depth(request).as_deref() {
+            None | Some("infinity") => true,
+            Some("0") => false,
+            _ => return Err((400, String::from("LOCK takes a Depth of 0 or infinity."))),
+        };
+
+        let document = Self::parse_xml(&body)?;
+        let lock_info = document.root_element();
+        if !Self::is_dav_element(lock_info, "lockinfo") {
+            return Err((400, String::from("Expected a DAV: lockinfo body.")));
+        }
+        let child = |name: &str| {
+            lock_info
+                .children()
+                .find(|child| Self::is_dav_element(*child, name))
+        };
+
+        let scope = child("lockscope")
+            .and_then(|scope| scope.children().find(Node::is_element))
+            .filter(|scope| scope.tag_name().namespace() == Some(DAV_NAMESPACE));
+        let exclusive = match scope.map(|scope| scope.tag_name().name()) {
+            Some("exclusive") => true,
+            Some("shared") => false,
+            _ => {
+                return Err((
+                    400,
+                    String::from("The lockinfo needs an exclusive or shared lockscope."),
+                ))
+            }
+        };
+        let is_write_lock = child("locktype").is_some_and(|lock_type| {
+            lock_type
+                .children()
+                .any(|lock_type| Self::is_dav_element(lock_type, "write"))
+        });
+        if !is_write_lock {
+            return Err((400, String::from("Only write locks are supported.")));
+        }
+        let owner = child("owner").map(|owner| {
+            owner
+                .children()
+                .map(Self::serialize_node)
+                .collect::<String>()
+        });
+
+        // Locking a path that does not exist yet creates an empty file to hold the lock
+        let existed = target.exists();
+        let parent = target.parent().unwrap_or(&root);
+        if !existed {
+            if !parent.is_dir() {
+                return Err((
+                    409,
+                    format!("The parent collection of {} does not exist.", context.path),
+                ));
+            }
+            context
+                .locks
+                .ensure_unlocked(parent, false, &context.tokens)?;
+        }
+
+        let href = Self::href(&context.path, target.is_dir());
+        let lock = context
+            .locks
+            .lock(&target, &href, exclusive, infinite, owner, timeout)
+            .ok_or_else(|| (423, format!("{} is already locked.", context.path)))?;
+
+        if !existed {
+            if let Err(e) = Upload::write_atomically(&root, &target, |_| Ok(0)) {
+                context.locks.unlock(&target, &lock.token);
+                return Err(e);
+            }
+        }
+
+        Logger::info(&format!(
+            "Locked {} ({}, {} seconds)",
+            context.path,
+            if exclusive { "exclusive" } else { "shared" },
+            timeout.as_secs()
+        ));
+        Ok(Self::lock_response(if existed { 200 } else { 201 }, &lock))
+    }
+
+    fn unlock(request: &Request, context: &WebDavContext) -> Result<Response, (u16, String)> {
+        let token = Self::header(request, "Lock-Token")
+            .map(|token| String::from(token.trim().trim_start_matches('<').trim_end_matches('>')))
+            .ok_or_else(|| (400, String::from("UNLOCK needs a Lock-Token header.")))?;
+        let (_, target) = Upload::resolve_path(&context.path, context.static_directory_manager)?;
+
+        if !context.locks.unlock(&target, &token) {
+            return Err((
+                409,
+                format!("{} holds no lock with the token {}.", context.path, token),
+            ));
+        }
+
+        Logger::info(&format!("Unlocked {}", context.path));
+        Ok(Upload::build_response(204, None, &[]))
+    }
+
+    ///
+    /// The first timeout the client asks for, `Second-<n>` or `Infinite`, capped at a day.
+    ///
+    fn lock_timeout(request: &Request) -> Duration {
+        Self::header(request, "Timeout")
+            .and_then(|timeouts| {
+                timeouts.split(',').map(str::trim).find_map(|timeout| {
+                    if timeout.eq_ignore_ascii_case("Infinite") {
+                        return Some(MAX_LOCK_TIMEOUT);
+                    }
+                    timeout
+                        .strip_prefix("Second-")
+                        .and_then(|seconds| seconds.parse::<u64>().ok())
+                        .map(Duration::from_secs)
+                })
+            })
+            .map(|timeout| timeout.min(MAX_LOCK_TIMEOUT))
+            .unwrap_or(DEFAULT_LOCK_TIMEOUT)
+    }
+
+    fn lock_response(status: u16, lock: &Lock) -> Response {
+        let body = format!(
+            "{}<D:prop xmlns:D=\"DAV:\"><D:lockdiscovery>{}</D:lockdiscovery></D:prop>",
+            XML_DECLARATION,
+            Self::active_lock(lock)
+        );
+        Self::xml_response(
+            status,
+            &body,
+            &[("Lock-Token", &format!("<{}>", lock.token))],
+        )
+    }
+
+    fn active_lock(lock: &Lock) -> String {
+        format!(
+            "<D:activelock><D:locktype><D:write/></D:locktype><D:lockscope><D:{}/></D:lockscope><D:depth>{}</D:depth>{}<D:timeout>Second-{}</D:timeout><D:locktoken><D:href>{}</D:href></D:locktoken><D:lockroot><D:href>{}</D:href></D:lockroot></D:activelock>",
+            if lock.exclusive { "exclusive" } else { "shared" },
+            if lock.infinite { "infinity" } else { "0" },
+            lock.owner
+                .as_ref()
+                .map(|owner| format!("<D:owner>{}</D:owner>", owner))
+                .unwrap_or_default(),
+            lock.remaining().as_secs(),
+            lock.token,
+            ErrorPage::escape_html(&lock.href)
+        )
+    }
+}
+
+impl WebDav {
+    fn read_xml_body(
+        request: &Request,
+        context: &WebDavContext,
+        stream: &ClientStream,
+    ) -> Result<String, (u16, String)> {
+        if request.content_length().unwrap_or_default() == 0 && !request.is_chunked() {
+            return Ok(String::new());
+        }
+
+        Upload::ensure_body_allowed(request, MAX_XML_BODY_SIZE)?;
+        let mut body: Vec<u8> = vec![];
+        let mut body_reader = Upload::body_reader(request, stream, context.min_rate);
+        Upload::copy_body(request, &mut body_reader, &mut body, MAX_XML_BODY_SIZE)?;
+
+        String::from_utf8(body).map_err(|_| (400, String::from("The XML body is not utf-8.")))
+    }
+
+    fn parse_xml(body: &str) -> Result<Document<'_>, (u16, String)> {
+        Document::parse(body).map_err(|e| (400, format!("The XML body is malformed. {}", e)))
+    }
+
+    fn is_dav_element(node: Node, name: &str) -> bool {
+        node.is_element()
+            && node.tag_name().namespace() == Some(DAV_NAMESPACE)
+            && node.tag_name().name() == name
+    }
+
+    fn qualified_name(node: Node) -> String {
+        format!(
+            "{{{}}}{}",
+            node.tag_name().namespace().unwrap_or_default(),
+            node.tag_name().name()
+        )
+    }
+
+    ///
+    /// Splits `{namespace}name` into the namespace and the name.
+    ///
+    fn split_name(qualified_name: &str) -> (&str, &str) {
+        qualified_name
+            .strip_prefix('{')
+            .and_then(|name| name.split_once('}'))
+            .unwrap_or(("", qualified_name))
+    }
+
+    fn text_of(node: Node) -> String {
+        node.descendants()
+            .filter(Node::is_text)
+            .filter_map(|text| text.text())
+            .collect()
+    }
+
+    ///
+    /// Writes a node back out, declaring each element's namespace on itself
+    /// so it stands alone wherever it is embedded.
+    ///
+    fn serialize_node(node: Node) -> String {
+        if node.is_text() {
+            return ErrorPage::escape_html(node.text().unwrap_or_default());
+        }
+        if !node.is_element() {
+            return String::new();
+        }
+
+        format!(
+            "<{0} xmlns=\"{1}\">{2}</{0}>",
+            node.tag_name().name(),
+            ErrorPage::escape_html(node.tag_name().namespace().unwrap_or_default()),
+            node.children()
+                .map(Self::serialize_node)
+                .collect::<String>()
+        )
+    }
+
+    ///
+    /// A property element holding `value`, which is already XML.
+    ///
+    fn property_element(qualified_name: &str, value: &str) -> String {
+        match Self::split_name(qualified_name) {
+            (DAV_NAMESPACE, name) if value.is_empty() => format!("<D:{}/>", name),
+            (DAV_NAMESPACE, name) => format!("<D:{0}>{1}</D:{0}>", name, value),
+            (namespace, name) => format!(
+                "<{0} xmlns=\"{1}\">{2}</{0}>",
+                name,
+                ErrorPage::escape_html(namespace),
+                value
+            ),
+        }
+    }
+
+    ///
+    /// A multistatus `response`, with a `propstat` for each status that has properties.
+    ///
+    fn response_element(href: &str, propstats: &[(u16, Vec<String>)]) -> String {
+        let propstats: String = propstats
+            .iter()
+            .filter(|(_, properties)| !properties.is_empty())
+            .map(|(status, properties)| {
+                format!(
+                    "<D:propstat><D:prop>{}</D:prop><D:status>HTTP/1.1 {} {}</D:status></D:propstat>",
+                    properties.concat(),
+                    status,
+                    StatusCode::reason_phrase(*status)
+                )
+            })
+            .collect();
+
+        format!(
+            "<D:response><D:href>{}</D:href>{}</D:response>",
+            ErrorPage::escape_html(href),
+            propstats
+        )
+    }
+
+    fn multistatus(responses: &[String]) -> Response {
+        let body = format!(
+            "{}<D:multistatus xmlns:D=\"DAV:\">{}</D:multistatus>",
+            XML_DECLARATION,
+            responses.concat()
+        );
+        Self::xml_response(207, &body, &[])
+    }
+
+    fn xml_response(status: u16, body: &str, headers: &[(&str, &str)]) -> Response {
+        let mut response_headers = Headers::new(vec![
+            (String::from("Content-Length"), body.len().to_string()),
+            (
+                String::from("Content-Type"),
+                String::from("application/xml; charset=utf-8"),
+            ),
+        ]);
+        for (key, value) in headers {
+            response_headers
+                .map
+                .insert(String::from(*key), String::from(*value));
+        }
+
+        Response::new(
+            String::from("HTTP/1.1"),
+            status,
+            String::from(StatusCode::reason_phrase(status)),
+            response_headers.map,
+            FileLike::TextFile(String::from(body)),
+            false,
+        )
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+static LOCK_TOKEN_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// # Lock
+///
+/// A WebDAV write lock on a resource, and with `infinite` depth everything inside it.
+///
+#[derive(Debug, Clone)]
+pub struct Lock {
+    pub token: String,
+    pub path: PathBuf,
+    /// The url the lock was taken on, reported as its lock root
+    pub href: String,
+    pub exclusive: bool,
+    pub infinite: bool,
+    /// The `owner` element's contents as sent by the client, already serialized
+    pub owner: Option<String>,
+    pub timeout: Duration,
+    expires: Instant,
+}
+
+impl Lock {
+    pub fn remaining(&self) -> Duration {
+        self.expires.saturating_duration_since(Instant::now())
+    }
+
+    fn covers(&self, path: &Path) -> bool {
+        self.path == path || (self.infinite && path.starts_with(&self.path))
+    }
+}
+
+/// # WebDavLocks
+///
+/// The locks taken with WebDAV `LOCK`, shared across requests and kept in memory,
+/// so they are released when rsrv restarts. Locks expire after their timeout
+/// unless refreshed.
+///
+/// A write needs the token of every lock on the resources it changes, sent in the
+/// `If` header. Any one token satisfies the shared locks on a resource.
+///
+#[derive(Debug, Clone, Default)]
+pub struct WebDavLocks {
+    locks: Arc<Mutex<Vec<Lock>>>,
+}
+
+impl WebDavLocks {
+    ///
+    /// Takes a lock, or returns `None` when it conflicts with one already held.
+    ///
+    pub fn lock(
+        &self,
+        path: &Path,
+        href: &str,
+        exclusive: bool,
+        infinite: bool,
+        owner: Option<String>,
+        timeout: Duration,
+    ) -> Option<Lock> {
+        let mut locks = self.current_locks();
+
+        let conflicts = locks.iter().any(|lock| {
+            let overlaps = lock.covers(path) || (infinite && lock.path.starts_with(path));
+            overlaps && (exclusive || lock.exclusive)
+        });
+        if conflicts {
+            return None;
+        }
+
+        let lock = Lock {
+            token: Self::new_token(),
+            path: path.to_path_buf(),
+            href: String::from(href),
+            exclusive,
+            infinite,
+            owner,
+            timeout,
+            expires: Instant::now() + timeout,
+        };
+        locks.push(lock.clone());
+        Some(lock)
+    }
+
+    ///
+    /// Restarts the timeout of the lock on `path` held with one of `tokens`.
+    ///
+    pub fn refresh(&self, path: &Path, tokens: &[String], timeout: Duration) -> Option<Lock> {
+        let mut locks = self.current_locks();
+        let lock = locks
+            .iter_mut()
+            .find(|lock| lock.covers(path) && tokens.contains(&lock.token))?;
+
+        lock.timeout = timeout;
+        lock.expires = Instant::now() + timeout;
+        Some(lock.clone())
+    }
+
+    pub fn unlock(&self, path: &Path, token: &str) -> bool {
+        let mut locks = self.current_locks();
+        let count = locks.len();
+        locks.retain(|lock| !(lock.token == token && lock.covers(path)));
+        locks.len() != count
+    }
+
+    ///
+    /// Drops the locks on a resource that no longer exists, and on everything inside it.
+    ///
+    pub fn release(&self, path: &Path) {
+        self.current_locks()
+            .retain(|lock| !lock.path.starts_with(path));
+    }
+
+    ///
+    /// The locks that apply to `path`, its own and those inherited from a collection.
+    ///
+    pub fn locks_on(&self, path: &Path) -> Vec<Lock> {
+        self.current_locks()
+            .iter()
+            .filter(|lock| lock.covers(path))
+            .cloned()
+            .collect()
+    }
+
+    ///
+    /// Checks that `tokens` satisfy the locks on `path`, with `subtree` also those
+    /// inside it, for writes that replace or remove a whole collection.
+    ///
+    pub fn ensure_unlocked(
+        &self,
+        path: &Path,
+        subtree: bool,
+        tokens: &[String],
+    ) -> Result<(), (u16, String)> {
+        let locks = self.current_locks();
+        let blocking: Vec<&Lock> = locks
+            .iter()
+            .filter(|lock| lock.covers(path) || (subtree && lock.path.starts_with(path)))
+            .collect();
+
+        let shared_token_held = blocking
+            .iter()
+            .any(|lock| !lock.exclusive && tokens.contains(&lock.token));
+        let unsatisfied = blocking
+            .iter()
+            .find(|lock| !tokens.contains(&lock.token) && (lock.exclusive || !shared_token_held));
+
+        match unsatisfied {
+            Some(lock) => Err((
+                423,
+                format!(
+                    "{} is locked, submit its lock token in the If header.",
+                    lock.href
+                ),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn current_locks(&self) -> std::sync::MutexGuard<'_, Vec<Lock>> {
+        let mut locks = self
+            .locks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let now = Instant::now();
+        locks.retain(|lock| lock.expires > now);
+        locks
+    }
+
+    ///
+    /// A unique `opaquelocktoken:` uri, formatted as a uuid.
+    ///
+    fn new_token() -> String {
+        let random = |salt: u64| {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u64(salt);
+            hasher.write_u64(LOCK_TOKEN_COUNTER.fetch_add(1, Ordering::Relaxed));
+            hasher.write_u128(
+                SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_nanos(),
+            );
+            hasher.finish()
+        };
+        let digits = format!("{:016x}{:016x}", random(0), random(1));
+
+        format!(
+            "opaquelocktoken:{}-{}-4{}-{}-{}",
+            &digits[0..8],
+            &digits[8..12],
+            &digits[13..16],
+            &digits[16..20],
+            &digits[20..32]
+        )
+    }
+}
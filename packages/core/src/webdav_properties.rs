@@ -0,0 +1,149 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::logger::Logger;
+
+/// Properties of a single resource, by `{namespace}name`
+pub type Properties = BTreeMap<String, String>;
+
+/// # PropertyStore
+///
+/// Dead properties set with WebDAV `PROPPATCH`, kept in a json sidecar file
+/// rather than next to every file, keyed by the resource's path on disk.
+///
+/// ```json
+/// {
+///   "/srv/site/report.pdf": { "{http://example.com/ns}reviewed": "yes" }
+/// }
+/// ```
+///
+/// Properties keep their text value. The store follows resources as they are
+/// copied, moved and deleted through WebDAV, not through other means.
+///
+pub struct PropertyStore;
+
+impl PropertyStore {
+    ///
+    /// The properties of a resource in a store loaded with `load`,
+    /// so listing a collection reads the store once.
+    ///
+    pub fn properties<'a>(
+        store: &'a HashMap<String, Properties>,
+        path: &Path,
+    ) -> Option<&'a Properties> {
+        store.get(&Self::key(path))
+    }
+
+    ///
+    /// Sets, or with `None` removes, each property of the resource in one write.
+    ///
+    pub fn update(
+        store_file: &str,
+        path: &Path,
+        changes: &[(String, Option<String>)],
+    ) -> Result<(), String> {
+        let mut store = Self::load(store_file);
+        let properties = store.entry(Self::key(path)).or_default();
+
+        for (name, value) in changes {
+            match value {
+                Some(value) => properties.insert(name.clone(), value.clone()),
+                None => properties.remove(name),
+            };
+        }
+        if properties.is_empty() {
+            store.remove(&Self::key(path));
+        }
+
+        Self::save(store_file, &store)
+    }
+
+    ///
+    /// Drops the properties of a resource and, for a collection, of everything in it.
+    ///
+    pub fn remove(store_file: &str, path: &Path) {
+        let mut store = Self::load(store_file);
+        let count = store.len();
+        store.retain(|key, _| !Path::new(key).starts_with(path));
+
+        if store.len() != count {
+            Self::save_or_warn(store_file, &store);
+        }
+    }
+
+    ///
+    /// Copies the properties of a resource and, unless `shallow`, of everything inside it.
+    ///
+    pub fn copy(store_file: &str, from: &Path, to: &Path, shallow: bool) {
+        Self::transfer(store_file, from, to, shallow, false);
+    }
+
+    pub fn rename(store_file: &str, from: &Path, to: &Path) {
+        Self::transfer(store_file, from, to, false, true);
+    }
+
+    fn transfer(store_file: &str, from: &Path, to: &Path, shallow: bool, remove_source: bool) {
+        let mut store = Self::load(store_file);
+        let transferred: Vec<(String, Properties)> = store
+            .iter()
+            .filter_map(|(key, properties)| {
+                let relative = Path::new(key).strip_prefix(from).ok()?;
+                if shallow && !relative.as_os_str().is_empty() {
+                    return None;
+                }
+                Some((Self::key(&to.join(relative)), properties.clone()))
+            })
+            .collect();
+
+        if transferred.is_empty() {
+            return;
+        }
+        if remove_source {
+            store.retain(|key, _| !Path::new(key).starts_with(from));
+        }
+        store.extend(transferred);
+
+        Self::save_or_warn(store_file, &store);
+    }
+
+    fn key(path: &Path) -> String {
+        path.to_string_lossy().to_string()
+    }
+
+    pub fn load(store_file: &str) -> HashMap<String, Properties> {
+        let contents = match fs::read_to_string(store_file) {
+            Ok(contents) => contents,
+            Err(_) => return HashMap::new(),
+        };
+
+        serde_json::from_str(&contents).unwrap_or_else(|e| {
+            Logger::warn(&format!(
+                "PropertyStore::load() Exception: Ignoring the unreadable property store {}. {}",
+                store_file, e
+            ));
+            HashMap::new()
+        })
+    }
+
+    fn save(store_file: &str, store: &HashMap<String, Properties>) -> Result<(), String> {
+        let contents = serde_json::to_string_pretty(store).map_err(|e| e.to_string())?;
+
+        // Written beside the store and renamed over it, so a crash never truncates it
+        let temporary_file = PathBuf::from(format!("{}.tmp", store_file));
+        fs::write(&temporary_file, contents)
+            .and_then(|_| fs::rename(&temporary_file, store_file))
+            .map_err(|e| {
+                format!(
+                    "PropertyStore::save() Exception: Unable to write the property store {}. {}",
+                    store_file, e
+                )
+            })
+    }
+
+    fn save_or_warn(store_file: &str, store: &HashMap<String, Properties>) {
+        if let Err(e) = Self::save(store_file, store) {
+            Logger::warn(&e);
+        }
+    }
+}
@@ -0,0 +1,266 @@
+//! Starts the rsrv binary on a temporary directory and speaks raw HTTP/1.1 to it.
+
+#![allow(dead_code)]
+
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{self, Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+static TEMP_DIRECTORIES: AtomicUsize = AtomicUsize::new(0);
+
+/// A directory under the system temp directory, unique to this test process.
+pub fn temp_directory(name: &str) -> PathBuf {
+    let directory = env::temp_dir().join(format!(
+        "rsrv-test-{}-{}-{}",
+        name,
+        process::id(),
+        TEMP_DIRECTORIES.fetch_add(1, Ordering::SeqCst)
+    ));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+/// A port nothing listens on, at least when it is picked.
+pub fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// # TestServer
+///
+/// rsrv serving `root` on a free port, without a config file or `RSRV_*` variables,
+/// killed and cleaned up on drop.
+pub struct TestServer {
+    process: Child,
+    pub port: u16,
+    pub root: PathBuf,
+}
+
+impl TestServer {
+    pub fn start(flags: &[&str]) -> Self {
+        Self::start_in(temp_directory("root"), flags)
+    }
+
+    pub fn start_in(root: PathBuf, flags: &[&str]) -> Self {
+        Self::launch(root, Path::new("/dev/null"), flags)
+    }
+
+    ///
+    /// Starts rsrv on `root` with `config` as its config file, written into the root
+    /// since fixture paths and the like resolve against the cwd.
+    ///
+    pub fn start_configured_in(root: PathBuf, config: &str, flags: &[&str]) -> Self {
+        let config_path = root.join("rsrv-test.toml");
+        write_file(&config_path, config);
+        Self::launch(root, &config_path, flags)
+    }
+
+    fn launch(root: PathBuf, config_path: &Path, flags: &[&str]) -> Self {
+        // The port may be taken between picking and binding it, rsrv then exits
+        for _ in 0..5 {
+            let port = free_port();
+            let mut command = Command::new(env!("CARGO_BIN_EXE_rsrv"));
+            command
+                .current_dir(&root)
+                .arg("-c")
+                .arg(config_path)
+                .arg("--log-level=Silent")
+                .arg(format!("--port={}", port))
+                .args(flags)
+                .arg(&root)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null());
+            for (key, _) in env::vars().filter(|(key, _)| key.starts_with("RSRV_")) {
+                command.env_remove(key);
+            }
+
+            let mut server = TestServer {
+                process: command.spawn().unwrap(),
+                port,
+                root: root.clone(),
+            };
+            if server.wait_until_listening() {
+                return server;
+            }
+        }
+
+        panic!("rsrv did not start listening");
+    }
+
+    fn wait_until_listening(&mut self) -> bool {
+        let started = Instant::now();
+        while started.elapsed() < STARTUP_TIMEOUT {
+            if let Ok(Some(_)) = self.process.try_wait() {
+                return false;
+            }
+            if TcpStream::connect(("127.0.0.1", self.port)).is_ok() {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        false
+    }
+
+    pub fn connect(&self) -> TcpStream {
+        let stream = TcpStream::connect(("127.0.0.1", self.port)).unwrap();
+        stream.set_read_timeout(Some(RESPONSE_TIMEOUT)).unwrap();
+        stream
+    }
+
+    ///
+    /// Sends a request, `Host` and `Content-Length` added unless given, and reads the
+    /// response until rsrv closes the connection.
+    ///
+    pub fn request(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> TestResponse {
+        let mut head = format!("{} {} HTTP/1.1\r\n", method, path);
+        let has_header = |name: &str| {
+            headers
+                .iter()
+                .any(|(key, _)| key.eq_ignore_ascii_case(name))
+        };
+        if !has_header("Host") {
+            head.push_str(&format!("Host: 127.0.0.1:{}\r\n", self.port));
+        }
+        if !has_header("Content-Length") && !has_header("Transfer-Encoding") && !body.is_empty() {
+            head.push_str(&format!("Content-Length: {}\r\n", body.len()));
+        }
+        for (key, value) in headers {
+            head.push_str(&format!("{}: {}\r\n", key, value));
+        }
+        head.push_str("\r\n");
+
+        let mut raw = head.into_bytes();
+        raw.extend_from_slice(body);
+        self.send(&raw)
+    }
+
+    pub fn get(&self, path: &str) -> TestResponse {
+        self.request("GET", path, &[], b"")
+    }
+
+    pub fn send(&self, raw: &[u8]) -> TestResponse {
+        let mut stream = self.connect();
+        stream.write_all(raw).unwrap();
+        TestResponse::read(&mut stream)
+    }
+
+    pub fn path(&self, relative: &str) -> PathBuf {
+        self.root.join(relative)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// # TestResponse
+///
+/// A response read to the end of the connection, its body as sent.
+#[derive(Debug)]
+pub struct TestResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl TestResponse {
+    pub fn read(stream: &mut impl Read) -> Self {
+        let mut raw = vec![];
+        // A reset after the response is as good as a close
+        let _ = stream.read_to_end(&mut raw);
+        Self::parse(&raw)
+    }
+
+    pub fn parse(raw: &[u8]) -> Self {
+        let head_end = raw
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .unwrap_or_else(|| panic!("no response head in {:?}", String::from_utf8_lossy(raw)));
+        let head = String::from_utf8_lossy(&raw[..head_end]).into_owned();
+        let mut lines = head.split("\r\n");
+
+        let status = lines
+            .next()
+            .and_then(|status_line| status_line.split(' ').nth(1))
+            .and_then(|status| status.parse::<u16>().ok())
+            .unwrap_or_else(|| panic!("invalid status line in {:?}", head));
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(key, value)| (String::from(key.trim()), String::from(value.trim())))
+            .collect();
+
+        TestResponse {
+            status,
+            headers,
+            body: raw[head_end + 4..].to_vec(),
+        }
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+/// Reads a request or response head, up to and including the blank line.
+pub fn read_head(stream: &mut impl Read) -> String {
+    let mut head = vec![];
+    let mut byte = [0; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+        match stream.read(&mut byte) {
+            Ok(1) => head.push(byte[0]),
+            _ => break,
+        }
+    }
+    String::from_utf8_lossy(&head).into_owned()
+}
+
+/// Reads until `needle` has arrived, returning everything read.
+pub fn read_until(stream: &mut impl Read, needle: &[u8]) -> Vec<u8> {
+    let mut read = vec![];
+    let mut buffer = [0; 1024];
+    while !read.windows(needle.len()).any(|window| window == needle) {
+        match stream.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(length) => read.extend_from_slice(&buffer[..length]),
+        }
+    }
+    read
+}
+
+pub fn write_file(path: &Path, contents: &str) {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).unwrap();
+    }
+    fs::write(path, contents).unwrap();
+}
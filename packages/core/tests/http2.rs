@@ -0,0 +1,111 @@
+mod common;
+
+use std::time::Duration;
+
+use bytes::Bytes;
+use h2::client::{self, SendRequest};
+use h2::Reason;
+use http::{Method, Request};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+use common::{write_file, TestServer};
+
+const WAIT: Duration = Duration::from_secs(10);
+
+/// An HTTP/2 connection with prior knowledge, driven on its own task.
+async fn connect(server: &TestServer) -> SendRequest<Bytes> {
+    let stream = TcpStream::connect(("127.0.0.1", server.port))
+        .await
+        .unwrap();
+    let (sender, connection) = client::handshake(stream).await.unwrap();
+    tokio::spawn(connection);
+    sender.ready().await.unwrap()
+}
+
+fn request(method: Method, path: &str, headers: &[(&str, &str)]) -> Request<()> {
+    let mut request = Request::builder()
+        .method(method)
+        .uri(format!("http://127.0.0.1{}", path));
+    for (key, value) in headers {
+        request = request.header(*key, *value);
+    }
+    request.body(()).unwrap()
+}
+
+#[tokio::test]
+async fn answers_408_when_a_stream_body_stalls() {
+    let server = TestServer::start(&["--uploads", "--upload-token=s3cret", "--body-timeout=1"]);
+    let mut sender = connect(&server).await;
+
+    let put = request(
+        Method::PUT,
+        "/stalled.txt",
+        &[("authorization", "Bearer s3cret"), ("content-length", "10")],
+    );
+    let (response, mut body) = sender.send_request(put, false).unwrap();
+    body.send_data(Bytes::from_static(b"st"), false).unwrap();
+
+    let response = timeout(WAIT, response).await.unwrap().unwrap();
+    assert_eq!(response.status(), 408);
+    assert!(!server.path("stalled.txt").exists());
+}
+
+#[tokio::test]
+async fn resets_a_stream_whose_client_stops_reading() {
+    let server = TestServer::start(&["--write-timeout=1"]);
+    write_file(&server.path("large.txt"), &"x".repeat(4 * 1024 * 1024));
+    let mut sender = connect(&server).await;
+
+    let (response, _) = sender
+        .send_request(request(Method::GET, "/large.txt", &[]), true)
+        .unwrap();
+    let response = timeout(WAIT, response).await.unwrap().unwrap();
+    assert_eq!(response.status(), 200);
+
+    // Reads what the window allows without releasing capacity, so the server runs out of room
+    let mut body = response.into_body();
+    let reset = timeout(WAIT, async {
+        loop {
+            match body.data().await {
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => return e.reason(),
+                None => return None,
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(reset, Some(Reason::CANCEL));
+}
+
+#[tokio::test]
+async fn closes_connections_without_streams_after_the_idle_timeout() {
+    let server = TestServer::start(&["--uploads", "--upload-token=s3cret", "--idle-timeout=1"]);
+    let stream = TcpStream::connect(("127.0.0.1", server.port))
+        .await
+        .unwrap();
+    let (sender, connection) = client::handshake(stream).await.unwrap();
+    let mut connection = tokio::spawn(connection);
+
+    // An upload still being sent keeps the connection open past the timeout
+    let mut sender = sender.ready().await.unwrap();
+    let put = request(
+        Method::PUT,
+        "/held.txt",
+        &[("authorization", "Bearer s3cret")],
+    );
+    let (response, mut body) = sender.send_request(put, false).unwrap();
+    assert!(timeout(Duration::from_millis(1500), &mut connection)
+        .await
+        .is_err());
+    body.send_data(Bytes::from_static(b"held"), true).unwrap();
+    assert_eq!(
+        timeout(WAIT, response).await.unwrap().unwrap().status(),
+        201
+    );
+
+    let closed = timeout(WAIT, connection).await;
+    assert!(closed.is_ok(), "the idle connection was never closed");
+    drop(sender);
+}
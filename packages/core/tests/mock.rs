@@ -0,0 +1,43 @@
+mod common;
+
+use std::fs;
+
+use common::{temp_directory, write_file, TestServer};
+
+const FIXTURE_MOCKS: &str = r#"
+[[mocks]]
+path = "/api/**"
+file = "fixtures/:splat"
+"#;
+
+#[test]
+fn serves_fixtures_named_by_the_captured_path() {
+    let server = TestServer::start_configured_in(temp_directory("root"), FIXTURE_MOCKS, &[]);
+    write_file(&server.path("fixtures/users/1.json"), r#"{ "id": 1 }"#);
+
+    let response = server.get("/api/users/1.json");
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), r#"{"id":1}"#);
+    assert_eq!(server.get("/api/users/2.json").status, 404);
+}
+
+#[test]
+fn refuses_captures_that_climb_out_of_the_fixtures() {
+    let outside = temp_directory("outside");
+    write_file(&outside.join("secret.json"), r#"{ "password": "hunter2" }"#);
+    let server = TestServer::start_configured_in(outside.join("site"), FIXTURE_MOCKS, &[]);
+    write_file(&server.path("fixtures/users/1.json"), r#"{ "id": 1 }"#);
+
+    for path in [
+        "/api/../../secret.json",
+        "/api/users/../../../secret.json",
+        "/api/./users/1.json",
+    ] {
+        let response = server.get(path);
+        assert_eq!(response.status, 404, "{}", path);
+        assert!(!response.text().contains("hunter2"), "{}", path);
+    }
+
+    drop(server);
+    fs::remove_dir_all(outside).unwrap();
+}
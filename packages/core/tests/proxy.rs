@@ -0,0 +1,268 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use common::{read_head, read_until, TestResponse, TestServer};
+
+const WAIT: Duration = Duration::from_secs(10);
+
+/// A stub upstream on a free port, handling one connection on its own thread.
+fn stub_upstream<F>(handle: F) -> u16
+where
+    F: FnOnce(TcpStream) + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        if let Ok((upstream, _)) = listener.accept() {
+            upstream.set_read_timeout(Some(WAIT)).unwrap();
+            handle(upstream);
+        }
+    });
+    port
+}
+
+fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.split("\r\n")
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim())
+}
+
+#[test]
+fn drops_hop_by_hop_headers_and_adds_forwarding_headers() {
+    let (sender, received) = mpsc::channel();
+    let upstream_port = stub_upstream(move |mut upstream| {
+        sender.send(read_head(&mut upstream)).unwrap();
+        upstream
+            .write_all(
+                b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nKeep-Alive: timeout=5\r\nConnection: keep-alive, X-Upstream-Hop\r\nX-Upstream-Hop: 1\r\nX-Upstream: kept\r\n\r\nok",
+            )
+            .unwrap();
+    });
+    let server = TestServer::start(&[&format!("--proxy=/api:http://127.0.0.1:{}", upstream_port)]);
+
+    let response = server.request(
+        "GET",
+        "/api/items?page=2",
+        &[
+            ("Host", "app.test"),
+            ("Connection", "keep-alive, X-Client-Hop"),
+            ("X-Client-Hop", "1"),
+            ("Keep-Alive", "timeout=5"),
+            ("Proxy-Authorization", "Basic dXNlcjpwYXNz"),
+            ("TE", "trailers"),
+            ("X-Forwarded-For", "203.0.113.9"),
+            ("X-Client", "kept"),
+        ],
+        b"",
+    );
+    let upstream_head = received.recv_timeout(WAIT).unwrap();
+
+    assert!(
+        upstream_head.starts_with("GET /api/items?page=2 HTTP/1.1\r\n"),
+        "{}",
+        upstream_head
+    );
+    assert_eq!(
+        header(&upstream_head, "Host"),
+        Some(format!("127.0.0.1:{}", upstream_port).as_str())
+    );
+    assert_eq!(header(&upstream_head, "Connection"), Some("close"));
+    for hop_by_hop in ["X-Client-Hop", "Keep-Alive", "Proxy-Authorization", "TE"] {
+        assert_eq!(header(&upstream_head, hop_by_hop), None, "{}", hop_by_hop);
+    }
+    assert_eq!(header(&upstream_head, "X-Client"), Some("kept"));
+    assert_eq!(
+        header(&upstream_head, "X-Forwarded-For"),
+        Some("203.0.113.9, 127.0.0.1")
+    );
+    assert_eq!(header(&upstream_head, "X-Forwarded-Host"), Some("app.test"));
+    assert_eq!(header(&upstream_head, "X-Forwarded-Proto"), Some("http"));
+    assert_eq!(
+        header(&upstream_head, "Forwarded"),
+        Some("for=127.0.0.1;host=\"app.test\";proto=http")
+    );
+
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), "ok");
+    assert_eq!(response.header("X-Upstream"), Some("kept"));
+    assert_eq!(response.header("X-Upstream-Hop"), None);
+    assert_eq!(response.header("Keep-Alive"), None);
+    assert_eq!(response.header("Connection"), Some("close"));
+}
+
+#[test]
+fn streams_chunked_bodies_both_ways() {
+    let (first_chunk_sender, first_chunk_received) = mpsc::channel();
+    let (body_sender, body_received) = mpsc::channel();
+    let (finish_sender, finish_received) = mpsc::channel::<()>();
+    let upstream_port = stub_upstream(move |mut upstream| {
+        let head = read_head(&mut upstream);
+        let first_chunk = read_until(&mut upstream, b"hello\r\n");
+        first_chunk_sender.send(head).unwrap();
+
+        let rest = read_until(&mut upstream, b"0\r\n\r\n");
+        body_sender.send([first_chunk, rest].concat()).unwrap();
+
+        upstream
+            .write_all(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4\r\npong\r\n")
+            .unwrap();
+        upstream.flush().unwrap();
+        finish_received.recv_timeout(WAIT).unwrap();
+        upstream.write_all(b"5\r\n pong\r\n0\r\n\r\n").unwrap();
+    });
+    let server = TestServer::start(&[&format!("--proxy=/api:http://127.0.0.1:{}", upstream_port)]);
+
+    let mut client = server.connect();
+    client
+        .write_all(b"POST /api/echo HTTP/1.1\r\nHost: app.test\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n")
+        .unwrap();
+    // The upstream has the first chunk before the client sends the rest
+    let upstream_head = first_chunk_received.recv_timeout(WAIT).unwrap();
+    assert_eq!(header(&upstream_head, "Transfer-Encoding"), Some("chunked"));
+    client.write_all(b"6\r\n world\r\n0\r\n\r\n").unwrap();
+    assert_eq!(
+        body_received.recv_timeout(WAIT).unwrap(),
+        b"5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n"
+    );
+
+    // And the client has the first chunk of the response before the upstream sends the rest
+    let first_chunk = read_until(&mut client, b"pong\r\n");
+    assert!(first_chunk.ends_with(b"4\r\npong\r\n"));
+    finish_sender.send(()).unwrap();
+    let mut rest = vec![];
+    let _ = client.read_to_end(&mut rest);
+
+    let response = TestResponse::parse(&[first_chunk, rest].concat());
+    assert_eq!(response.status, 200);
+    assert_eq!(response.header("Transfer-Encoding"), Some("chunked"));
+    assert_eq!(response.text(), "4\r\npong\r\n5\r\n pong\r\n0\r\n\r\n");
+}
+
+#[test]
+fn answers_502_when_the_upstream_refuses_connections() {
+    let server = TestServer::start(&[&format!(
+        "--proxy=/api:http://127.0.0.1:{}",
+        common::free_port()
+    )]);
+
+    assert_eq!(server.get("/api/items").status, 502);
+}
+
+#[test]
+fn answers_504_when_the_upstream_stalls_past_the_timeout() {
+    let upstream_port = stub_upstream(|mut upstream| {
+        read_head(&mut upstream);
+        // Holds the connection open without answering
+        thread::sleep(Duration::from_secs(5));
+    });
+    let server = TestServer::start(&[&format!(
+        "--proxy=/api:http://127.0.0.1:{},timeout=1",
+        upstream_port
+    )]);
+
+    assert_eq!(server.get("/api/slow").status, 504);
+}
+
+#[test]
+fn holds_a_slow_client_body_to_the_body_timeout_rather_than_the_route_timeout() {
+    let (sender, received) = mpsc::channel();
+    let upstream_port = stub_upstream(move |mut upstream| {
+        read_head(&mut upstream);
+        sender
+            .send(read_until(&mut upstream, b"0123456789"))
+            .unwrap();
+        upstream
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+            .unwrap();
+    });
+    let server = TestServer::start(&[
+        &format!("--proxy=/api:http://127.0.0.1:{},timeout=1", upstream_port),
+        "--body-timeout=5",
+    ]);
+
+    // Pauses for longer than the route's timeout, well within the body's
+    let mut client = server.connect();
+    client
+        .write_all(
+            b"POST /api/upload HTTP/1.1\r\nHost: app.test\r\nContent-Length: 10\r\n\r\n01234",
+        )
+        .unwrap();
+    thread::sleep(Duration::from_secs(2));
+    client.write_all(b"56789").unwrap();
+
+    assert_eq!(received.recv_timeout(WAIT).unwrap(), b"0123456789");
+    let response = TestResponse::read(&mut client);
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), "ok");
+}
+
+#[test]
+fn answers_408_when_a_client_body_stalls_through_a_proxy() {
+    let upstream_port = stub_upstream(|mut upstream| {
+        read_head(&mut upstream);
+        let mut body = vec![];
+        let _ = upstream.read_to_end(&mut body);
+    });
+    let server = TestServer::start(&[
+        &format!("--proxy=/api:http://127.0.0.1:{},timeout=30", upstream_port),
+        "--body-timeout=1",
+    ]);
+
+    let mut client = server.connect();
+    client
+        .write_all(
+            b"POST /api/upload HTTP/1.1\r\nHost: app.test\r\nContent-Length: 10\r\n\r\n01234",
+        )
+        .unwrap();
+
+    assert_eq!(TestResponse::read(&mut client).status, 408);
+}
+
+#[test]
+fn open_websocket_tunnels_count_against_the_connection_limit() {
+    let upstream_port = stub_upstream(|mut upstream| {
+        read_head(&mut upstream);
+        upstream
+            .write_all(b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\r\n")
+            .unwrap();
+        // Echoes until the client side of the tunnel closes
+        let mut buffer = [0; 64];
+        while let Ok(length @ 1..) = upstream.read(&mut buffer) {
+            let _ = upstream.write_all(&buffer[..length]);
+        }
+    });
+    let server = TestServer::start(&[
+        &format!("--proxy=/ws:http://127.0.0.1:{}", upstream_port),
+        "--max-connections-per-ip=1",
+    ]);
+    let released = || {
+        (0..50).any(|_| {
+            thread::sleep(Duration::from_millis(50));
+            server.get("/").status != 429
+        })
+    };
+    // The probe that saw the server start may still hold the only slot
+    assert!(
+        released(),
+        "the startup probe's connection was never released"
+    );
+
+    let mut tunnel = server.connect();
+    tunnel
+        .write_all(b"GET /ws HTTP/1.1\r\nHost: app.test\r\nConnection: Upgrade\r\nUpgrade: websocket\r\nSec-WebSocket-Version: 13\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n")
+        .unwrap();
+    assert!(read_head(&mut tunnel).starts_with("HTTP/1.1 101"));
+    tunnel.write_all(b"ping").unwrap();
+    assert_eq!(read_until(&mut tunnel, b"ping"), b"ping");
+
+    assert_eq!(server.get("/").status, 429);
+
+    drop(tunnel);
+    assert!(released(), "the tunnel's connection was never released");
+}
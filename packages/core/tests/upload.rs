@@ -0,0 +1,66 @@
+mod common;
+
+use std::fs;
+use std::os::unix::fs::symlink;
+
+use common::{temp_directory, TestServer};
+
+const TOKEN: &str = "s3cret";
+
+fn upload_server() -> TestServer {
+    TestServer::start(&["--uploads", &format!("--upload-token={}", TOKEN)])
+}
+
+fn multipart(filename: &str, contents: &str) -> Vec<u8> {
+    format!(
+        "--XBOUNDARYX\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: text/plain\r\n\r\n{}\r\n--XBOUNDARYX--\r\n",
+        filename, contents
+    )
+    .into_bytes()
+}
+
+fn post_multipart(server: &TestServer, path: &str, filename: &str) -> u16 {
+    server
+        .request(
+            "POST",
+            path,
+            &[
+                ("Authorization", &format!("Bearer {}", TOKEN)),
+                ("Content-Type", "multipart/form-data; boundary=XBOUNDARYX"),
+            ],
+            &multipart(filename, "uploaded"),
+        )
+        .status
+}
+
+#[test]
+fn writes_multipart_uploads_into_nested_directories() {
+    let server = upload_server();
+
+    assert_eq!(post_multipart(&server, "/", "docs/new/readme.txt"), 201);
+    assert_eq!(
+        fs::read_to_string(server.path("docs/new/readme.txt")).unwrap(),
+        "uploaded"
+    );
+}
+
+#[test]
+fn refuses_to_create_directories_through_a_symlinked_subdirectory() {
+    let server = upload_server();
+    let outside = temp_directory("outside");
+    symlink(&outside, server.path("link")).unwrap();
+
+    assert_eq!(post_multipart(&server, "/", "link/newdir/x"), 403);
+    assert!(!outside.join("newdir").exists());
+
+    let put = server.request(
+        "PUT",
+        "/link/putdir/x",
+        &[("Authorization", &format!("Bearer {}", TOKEN))],
+        b"uploaded",
+    );
+    assert_eq!(put.status, 403);
+    assert!(!outside.join("putdir").exists());
+
+    fs::remove_dir_all(outside).unwrap();
+}
@@ -0,0 +1,426 @@
+mod common;
+
+use std::fs;
+
+use rsrv::webdav::{WebDav, WebDavSettings};
+use serde_json::Value;
+
+use common::{write_file, TestResponse, TestServer};
+
+const AUTHORIZATION: &str = "Bearer s3cret";
+
+fn webdav_server() -> TestServer {
+    let server = TestServer::start(&["--webdav", "--upload-token=s3cret"]);
+    write_file(&server.path("report.txt"), "quarterly");
+    write_file(&server.path("docs/guide.txt"), "read me");
+    server
+}
+
+/// Sends an authenticated WebDAV request with extra headers.
+fn dav(
+    server: &TestServer,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> TestResponse {
+    let mut all_headers = vec![("Authorization", AUTHORIZATION)];
+    all_headers.extend_from_slice(headers);
+    server.request(method, path, &all_headers, body.as_bytes())
+}
+
+fn propfind(server: &TestServer, path: &str, depth: &str) -> TestResponse {
+    dav(server, "PROPFIND", path, &[("Depth", depth)], "")
+}
+
+/// The hrefs of a multistatus body, in order.
+fn hrefs(response: &TestResponse) -> Vec<String> {
+    response
+        .text()
+        .split("<D:href>")
+        .skip(1)
+        .filter_map(|part| part.split_once("</D:href>"))
+        .map(|(href, _)| String::from(href))
+        .collect()
+}
+
+/// The dead properties kept in the sidecar store for a file under the root.
+fn stored_properties(server: &TestServer, relative: &str) -> Option<Value> {
+    let store: Value =
+        serde_json::from_str(&fs::read_to_string(server.path(".rsrv-webdav-props.json")).ok()?)
+            .unwrap();
+    store
+        .as_object()
+        .unwrap()
+        .iter()
+        .find(|(key, _)| key.ends_with(&format!("/{}", relative)))
+        .map(|(_, properties)| properties.clone())
+}
+
+fn destination(server: &TestServer, path: &str) -> String {
+    format!("http://127.0.0.1:{}{}", server.port, path)
+}
+
+#[test]
+fn propfind_lists_a_resource_or_its_members() {
+    let server = webdav_server();
+
+    let resource = propfind(&server, "/report.txt", "0");
+    assert_eq!(resource.status, 207);
+    assert_eq!(hrefs(&resource), ["/report.txt"]);
+    assert!(resource
+        .text()
+        .contains("<D:getcontentlength>9</D:getcontentlength>"));
+
+    let collection = propfind(&server, "/docs/", "0");
+    assert_eq!(hrefs(&collection), ["/docs/"]);
+    assert!(collection.text().contains("<D:collection/>"));
+
+    let members = propfind(&server, "/docs/", "1");
+    assert_eq!(members.status, 207);
+    assert_eq!(hrefs(&members), ["/docs/", "/docs/guide.txt"]);
+
+    assert_eq!(propfind(&server, "/docs/", "infinity").status, 403);
+    assert_eq!(propfind(&server, "/missing.txt", "0").status, 404);
+}
+
+#[test]
+fn propfind_hides_the_property_store_and_uploads_in_progress() {
+    let server = webdav_server();
+    write_file(&server.path(".rsrv-webdav-props.json"), "{}");
+    write_file(&server.path(".report.txt.rsrv-upload-1-0"), "partial");
+
+    let listing = propfind(&server, "/", "1");
+    assert_eq!(listing.status, 207);
+    assert_eq!(hrefs(&listing), ["/", "/docs/", "/report.txt"]);
+
+    assert_eq!(
+        propfind(&server, "/.rsrv-webdav-props.json", "0").status,
+        404
+    );
+    assert_eq!(
+        dav(&server, "GET", "/.report.txt.rsrv-upload-1-0", &[], "").status,
+        404
+    );
+}
+
+#[test]
+fn proppatch_sets_and_removes_dead_properties() {
+    let server = webdav_server();
+
+    let set = dav(
+        &server,
+        "PROPPATCH",
+        "/report.txt",
+        &[("Content-Type", "application/xml")],
+        r#"<?xml version="1.0"?>
+        <D:propertyupdate xmlns:D="DAV:" xmlns:Z="http://example.com/ns">
+          <D:set><D:prop><Z:reviewed>yes</Z:reviewed><Z:author>ana</Z:author></D:prop></D:set>
+        </D:propertyupdate>"#,
+    );
+    assert_eq!(set.status, 207);
+    assert!(set.text().contains("HTTP/1.1 200 OK"), "{}", set.text());
+    assert_eq!(
+        stored_properties(&server, "report.txt"),
+        Some(serde_json::json!({
+            "{http://example.com/ns}author": "ana",
+            "{http://example.com/ns}reviewed": "yes",
+        }))
+    );
+
+    let remove = dav(
+        &server,
+        "PROPPATCH",
+        "/report.txt",
+        &[("Content-Type", "application/xml")],
+        r#"<?xml version="1.0"?>
+        <D:propertyupdate xmlns:D="DAV:" xmlns:Z="http://example.com/ns">
+          <D:remove><D:prop><Z:author/></D:prop></D:remove>
+        </D:propertyupdate>"#,
+    );
+    assert_eq!(remove.status, 207);
+    assert_eq!(
+        stored_properties(&server, "report.txt"),
+        Some(serde_json::json!({ "{http://example.com/ns}reviewed": "yes" }))
+    );
+
+    let found = dav(
+        &server,
+        "PROPFIND",
+        "/report.txt",
+        &[("Depth", "0")],
+        r#"<?xml version="1.0"?>
+        <D:propfind xmlns:D="DAV:" xmlns:Z="http://example.com/ns">
+          <D:prop><Z:reviewed/><Z:author/></D:prop>
+        </D:propfind>"#,
+    );
+    assert_eq!(found.status, 207);
+    assert!(found.text().contains(">yes</"), "{}", found.text());
+    assert!(
+        found.text().contains("HTTP/1.1 404 Not Found"),
+        "{}",
+        found.text()
+    );
+}
+
+#[test]
+fn mkcol_creates_collections_under_existing_ones() {
+    let server = webdav_server();
+
+    assert_eq!(dav(&server, "MKCOL", "/drafts/", &[], "").status, 201);
+    assert!(server.path("drafts").is_dir());
+    assert_eq!(dav(&server, "MKCOL", "/drafts/", &[], "").status, 405);
+    assert_eq!(dav(&server, "MKCOL", "/report.txt", &[], "").status, 405);
+    assert_eq!(
+        dav(&server, "MKCOL", "/missing/drafts/", &[], "").status,
+        409
+    );
+    assert!(!server.path("missing").exists());
+}
+
+#[test]
+fn copy_and_move_honour_overwrite() {
+    let server = webdav_server();
+    let copy = destination(&server, "/copy.txt");
+
+    assert_eq!(
+        dav(
+            &server,
+            "COPY",
+            "/report.txt",
+            &[("Destination", &copy)],
+            ""
+        )
+        .status,
+        201
+    );
+    assert_eq!(
+        fs::read_to_string(server.path("copy.txt")).unwrap(),
+        "quarterly"
+    );
+
+    write_file(&server.path("report.txt"), "revised");
+    assert_eq!(
+        dav(
+            &server,
+            "COPY",
+            "/report.txt",
+            &[("Destination", &copy), ("Overwrite", "F")],
+            ""
+        )
+        .status,
+        412
+    );
+    assert_eq!(
+        fs::read_to_string(server.path("copy.txt")).unwrap(),
+        "quarterly"
+    );
+    assert_eq!(
+        dav(
+            &server,
+            "COPY",
+            "/report.txt",
+            &[("Destination", &copy)],
+            ""
+        )
+        .status,
+        204
+    );
+    assert_eq!(
+        fs::read_to_string(server.path("copy.txt")).unwrap(),
+        "revised"
+    );
+
+    assert_eq!(
+        dav(
+            &server,
+            "MOVE",
+            "/docs/",
+            &[("Destination", "/report.txt"), ("Overwrite", "F")],
+            ""
+        )
+        .status,
+        412
+    );
+    assert_eq!(
+        dav(
+            &server,
+            "MOVE",
+            "/docs/",
+            &[("Destination", "/manual/")],
+            ""
+        )
+        .status,
+        201
+    );
+    assert!(!server.path("docs").exists());
+    assert_eq!(
+        fs::read_to_string(server.path("manual/guide.txt")).unwrap(),
+        "read me"
+    );
+}
+
+#[test]
+fn copy_and_move_refuse_destinations_on_other_servers() {
+    let server = webdav_server();
+
+    assert_eq!(
+        dav(
+            &server,
+            "COPY",
+            "/report.txt",
+            &[("Destination", "http://elsewhere.test/report.txt")],
+            ""
+        )
+        .status,
+        502
+    );
+    assert_eq!(
+        dav(
+            &server,
+            "MOVE",
+            "/report.txt",
+            &[("Destination", "http://elsewhere.test/report.txt")],
+            ""
+        )
+        .status,
+        502
+    );
+
+    // Without a Host header there is nothing to compare the authority with
+    let without_host = server.send(
+        format!(
+            "MOVE /report.txt HTTP/1.0\r\nAuthorization: {}\r\nDestination: {}\r\n\r\n",
+            AUTHORIZATION,
+            destination(&server, "/moved.txt")
+        )
+        .as_bytes(),
+    );
+    assert_eq!(without_host.status, 502);
+    assert!(server.path("report.txt").exists());
+    assert!(!server.path("moved.txt").exists());
+}
+
+#[test]
+fn locks_refuse_writes_without_their_token() {
+    let server = webdav_server();
+
+    let lock = dav(
+        &server,
+        "LOCK",
+        "/report.txt",
+        &[
+            ("Content-Type", "application/xml"),
+            ("Timeout", "Second-600"),
+        ],
+        r#"<?xml version="1.0"?>
+        <D:lockinfo xmlns:D="DAV:">
+          <D:lockscope><D:exclusive/></D:lockscope>
+          <D:locktype><D:write/></D:locktype>
+          <D:owner>ana</D:owner>
+        </D:lockinfo>"#,
+    );
+    assert_eq!(lock.status, 200);
+    let lock_token = lock.header("Lock-Token").unwrap().to_string();
+    let token = lock_token.trim_start_matches('<').trim_end_matches('>');
+    assert!(token.starts_with("opaquelocktoken:"), "{}", lock_token);
+
+    assert_eq!(
+        dav(&server, "PUT", "/report.txt", &[], "overwritten").status,
+        423
+    );
+    assert_eq!(dav(&server, "DELETE", "/report.txt", &[], "").status, 423);
+    assert_eq!(
+        dav(
+            &server,
+            "PUT",
+            "/report.txt",
+            &[("If", "(<opaquelocktoken:not-the-token>)")],
+            "overwritten"
+        )
+        .status,
+        423
+    );
+    assert_eq!(
+        fs::read_to_string(server.path("report.txt")).unwrap(),
+        "quarterly"
+    );
+
+    let condition = format!("(<{}>)", token);
+    let put = dav(
+        &server,
+        "PUT",
+        "/report.txt",
+        &[("If", &condition)],
+        "overwritten",
+    );
+    assert!(matches!(put.status, 200 | 201 | 204), "{}", put.status);
+    assert_eq!(
+        fs::read_to_string(server.path("report.txt")).unwrap(),
+        "overwritten"
+    );
+
+    assert_eq!(
+        dav(
+            &server,
+            "UNLOCK",
+            "/report.txt",
+            &[("Lock-Token", &lock_token)],
+            ""
+        )
+        .status,
+        204
+    );
+    assert_eq!(
+        dav(
+            &server,
+            "UNLOCK",
+            "/report.txt",
+            &[("Lock-Token", &lock_token)],
+            ""
+        )
+        .status,
+        409
+    );
+    let unlocked = dav(&server, "PUT", "/report.txt", &[], "unlocked");
+    assert!(
+        matches!(unlocked.status, 200 | 201 | 204),
+        "{}",
+        unlocked.status
+    );
+}
+
+#[test]
+fn decode_path_refuses_encoded_dot_segments() {
+    let settings = WebDavSettings::default();
+
+    assert_eq!(
+        WebDav::decode_path("/docs/%20notes%2Ftodo.txt", &settings),
+        Ok(String::from("/docs/ notes/todo.txt"))
+    );
+    for path in [
+        "/%2e%2e/etc/passwd",
+        "/docs/%2E%2E/%2e%2e/secret",
+        "/docs/..%2fsecret",
+        "/docs/%2e/guide.txt",
+        "/docs/%00",
+    ] {
+        assert_eq!(
+            WebDav::decode_path(path, &settings).map_err(|(status, _)| status),
+            Err(403),
+            "{}",
+            path
+        );
+    }
+    assert_eq!(
+        WebDav::decode_path("/docs/%zz", &settings).map_err(|(status, _)| status),
+        Err(400)
+    );
+    assert_eq!(
+        WebDav::decode_path("/.rsrv-webdav-props.json", &settings).map_err(|(status, _)| status),
+        Err(404)
+    );
+
+    let server = webdav_server();
+    assert_eq!(propfind(&server, "/docs/%2e%2e/%2e%2e/", "0").status, 403);
+}